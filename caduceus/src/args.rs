@@ -0,0 +1,548 @@
+// Module to parse the flags given to a command, like `add --at "2026-11-02 09:30"`
+
+use crate::timespec;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use hermes_proto::recurrence::Rule;
+use hermes_proto::reminder::{self, CatchUp, Frequency, Reminder, Sink};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+pub struct Flags {
+    values: HashMap<String, String>,
+    pub positional: Vec<String>,
+}
+
+impl Flags {
+    // Accepts `--name value` and `--name=value` for each name in `known`, and `--name` for switches
+    pub fn parse(args: &[String], known: &[&str], switches: &[&str]) -> Result<Flags, String> {
+        let mut values = HashMap::new();
+        let mut positional = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+                continue;
+            }
+
+            let (name, value) = match arg[2..].split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg[2..].to_string(), None),
+            };
+            let switch = switches.contains(&name.as_str());
+            if !switch && !known.contains(&name.as_str()) {
+                return Err(format!("Unknown flag --{}", name));
+            }
+            let value = match value {
+                Some(_) if switch => return Err(format!("Flag --{} takes no value", name)),
+                None if switch => String::new(),
+                Some(value) => value,
+                None => match iter.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("Flag --{} needs a value", name)),
+                },
+            };
+            if values.insert(name.clone(), value).is_some() {
+                return Err(format!("Flag --{} given more than once", name));
+            }
+        }
+
+        return Ok(Flags { values, positional });
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        return self.values.contains_key(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        return self.values.get(name).map(|value| value.as_str());
+    }
+}
+
+/*
+* Parses a recurrence such as "once", "daily", "monthly", "3d", "2w", "weekdays", or a rule
+* like "FREQ=MONTHLY;BYDAY=1MO". Returns the frequency, the number of days between
+* notifications for N days, and the rule for a custom recurrence.
+*/
+pub fn parse_every(spec: &str) -> Result<(Frequency, Option<u32>, Option<Rule>), String> {
+    if spec.contains('=') {
+        return Ok((Frequency::RULE, None, Some(spec.parse()?)));
+    }
+
+    let spec = spec.trim().to_lowercase();
+    match spec.as_str() {
+        "once" => return Ok((Frequency::ONCE, None, None)),
+        "daily" | "d" => return Ok((Frequency::DAILY, None, None)),
+        "weekly" | "w" => return Ok((Frequency::WEEKLY, None, None)),
+        "monthly" => return Ok((Frequency::MONTHLY, None, None)),
+        "yearly" | "annually" => return Ok((Frequency::YEARLY, None, None)),
+        "weekdays" => {
+            let rule = "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".parse()?;
+            return Ok((Frequency::RULE, None, Some(rule)));
+        }
+        _ => {}
+    }
+
+    let invalid = || {
+        format!(
+            "Invalid recurrence \"{}\", expected e.g. once, daily, 3d, 2w, weekdays or a rule",
+            spec
+        )
+    };
+    if spec.len() < 2 {
+        return Err(invalid());
+    }
+    let (count, unit) = spec.split_at(spec.len() - 1);
+    let count = count.parse::<u32>().map_err(|_| invalid())?;
+    let days = match unit {
+        "d" => count,
+        "w" => count.checked_mul(7).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+
+    return match (days, unit) {
+        (0, _) => Err(invalid()),
+        (1, _) => Ok((Frequency::DAILY, None, None)),
+        (7, "w") => Ok((Frequency::WEEKLY, None, None)),
+        _ => Ok((Frequency::NDAYS, Some(days), None)),
+    };
+}
+
+// Minutes between notifications of a persistent reminder, unless --renotify is given
+const DEFAULT_RENOTIFY_MINUTES: u32 = 5;
+
+/*
+* Reads --persistent and --renotify, where "--renotify off" makes a reminder send only once.
+* Returns None when neither is given, otherwise the new re-notification interval in minutes.
+*/
+pub fn parse_renotify(flags: &Flags) -> Result<Option<Option<u32>>, String> {
+    let minutes = match flags.get("renotify") {
+        Some(spec) if spec.trim().eq_ignore_ascii_case("off") => None,
+        Some(spec) => {
+            let duration = timespec::parse_duration(spec)?;
+            Some(
+                u32::try_from(duration.num_minutes())
+                    .map_err(|_| "Interval too long".to_string())?,
+            )
+        }
+        None if flags.has("persistent") => Some(DEFAULT_RENOTIFY_MINUTES),
+        None => return Ok(None),
+    };
+    return Ok(Some(minutes));
+}
+
+// Reads --catch-up, where "default" leaves missed occurrences to the setting of Hermes
+pub fn parse_catch_up(flags: &Flags) -> Result<Option<Option<CatchUp>>, String> {
+    return match flags.get("catch-up") {
+        Some(spec) if spec.trim().eq_ignore_ascii_case("default") => Ok(Some(None)),
+        Some(spec) => Ok(Some(Some(spec.parse()?))),
+        None => Ok(None),
+    };
+}
+
+// Reads --priority, true for a reminder sent even during quiet hours and do not disturb
+pub fn parse_priority(flags: &Flags) -> Result<Option<bool>, String> {
+    return match flags.get("priority").map(|spec| spec.trim().to_lowercase()) {
+        Some(spec) if spec == "high" => Ok(Some(true)),
+        Some(spec) if spec == "normal" => Ok(Some(false)),
+        Some(spec) => Err(format!(
+            "Invalid priority \"{}\", expected high or normal",
+            spec
+        )),
+        None => Ok(None),
+    };
+}
+
+// Reads --until, the last date a recurring reminder is sent on, where "none" removes it
+pub fn parse_until(flags: &Flags) -> Result<Option<Option<NaiveDate>>, String> {
+    return match flags.get("until") {
+        Some(spec) if spec.trim().eq_ignore_ascii_case("none") => Ok(Some(None)),
+        Some(spec) => Ok(Some(Some(timespec::parse_time(spec)?.date()))),
+        None => Ok(None),
+    };
+}
+
+// Reads --count, how many more times a recurring reminder is sent, where "none" removes the limit
+pub fn parse_count(flags: &Flags) -> Result<Option<Option<u32>>, String> {
+    return match flags.get("count").map(|spec| spec.trim()) {
+        Some(spec) if spec.eq_ignore_ascii_case("none") => Ok(Some(None)),
+        Some(spec) => match spec.parse::<u32>() {
+            Ok(count) if count > 0 => Ok(Some(Some(count))),
+            _ => Err(format!(
+                "Invalid count \"{}\", expected a number of times of at least 1, or none",
+                spec
+            )),
+        },
+        None => Ok(None),
+    };
+}
+
+/*
+* Sets how the reminder's notification looks from --title, --urgency, --icon, --category and
+* --timeout, keeping whatever isn't given. "none", or "default" for --timeout, removes a setting.
+*/
+pub fn apply_presentation(flags: &Flags, reminder: &mut Reminder) -> Result<(), String> {
+    let text = |name: &str| {
+        flags.get(name).map(|value| {
+            Some(value.trim().to_string()).filter(|value| !value.eq_ignore_ascii_case("none"))
+        })
+    };
+    if let Some(title) = text("title") {
+        reminder.title = title;
+    }
+    if let Some(icon) = text("icon") {
+        reminder.icon = icon;
+    }
+    if let Some(category) = text("category") {
+        reminder.category = category;
+    }
+    if let Some(urgency) = text("urgency") {
+        reminder.urgency = urgency.map(|urgency| urgency.parse()).transpose()?;
+    }
+    if let Some(spec) = flags.get("timeout") {
+        reminder.timeout = parse_timeout(spec)?;
+    }
+    return Ok(());
+}
+
+// Reads how long a notification stays on screen, like "10s" or "2m", in milliseconds
+fn parse_timeout(spec: &str) -> Result<Option<u32>, String> {
+    let spec = spec.trim().to_lowercase();
+    let seconds = match spec.as_str() {
+        "default" => return Ok(None),
+        "never" => return Ok(Some(0)),
+        spec => spec
+            .strip_suffix('s')
+            .unwrap_or(spec)
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .or_else(|| Some(timespec::parse_duration(spec).ok()?.num_seconds())),
+    };
+    return match seconds.and_then(|seconds| seconds.checked_mul(1000)) {
+        Some(millis) if millis > 0 && millis <= i32::MAX as i64 => Ok(Some(millis as u32)),
+        _ => Err(format!(
+            "Invalid timeout \"{}\", expected e.g. 10s or 2m, never or default",
+            spec
+        )),
+    };
+}
+
+// Days an agenda covers when no window is given
+const DEFAULT_AGENDA_DAYS: i64 = 7;
+
+/*
+* Reads the window of an agenda from `now`: --today until midnight, --days N, or --from and --to,
+* either of which may be left out. Without any of them it covers the next week.
+*/
+pub fn parse_window(
+    flags: &Flags,
+    now: NaiveDateTime,
+) -> Result<(NaiveDateTime, NaiveDateTime), String> {
+    let ranged = flags.has("from") || flags.has("to");
+    if [flags.has("today"), flags.has("days"), ranged]
+        .iter()
+        .filter(|given| **given)
+        .count()
+        > 1
+    {
+        return Err("Give only one of --today, --days, or --from and --to".to_string());
+    }
+
+    if flags.has("today") {
+        let midnight = now.date().succ_opt().ok_or("No day after today")?;
+        return Ok((now, midnight.and_time(NaiveTime::MIN)));
+    }
+    if let Some(spec) = flags.get("days") {
+        return match spec.trim().parse::<i64>() {
+            Ok(days) if days > 0 => Ok((now, now + Duration::days(days))),
+            _ => Err(format!(
+                "Invalid number of days \"{}\", expected at least 1",
+                spec.trim()
+            )),
+        };
+    }
+
+    let from = match flags.get("from") {
+        Some(spec) => timespec::resolve(spec, now)?,
+        None => now,
+    };
+    let to = match flags.get("to") {
+        Some(spec) => timespec::resolve(spec, now)?,
+        None => from + Duration::days(DEFAULT_AGENDA_DAYS),
+    };
+    if to <= from {
+        return Err("The agenda has to end after it starts".to_string());
+    }
+    return Ok((from, to));
+}
+
+/*
+* Reads --alert, how far ahead of each occurrence to warn, such as "30m, 1d", where "none" removes
+* the warnings. Given in whole minutes, soonest first.
+*/
+pub fn parse_alerts(flags: &Flags) -> Result<Option<Vec<u32>>, String> {
+    let spec = match flags.get("alert").map(|spec| spec.trim()) {
+        Some(spec) if spec.eq_ignore_ascii_case("none") => return Ok(Some(Vec::new())),
+        Some(spec) => spec,
+        None => return Ok(None),
+    };
+    let mut leads = Vec::new();
+    for item in spec.split(',') {
+        let lead = timespec::parse_duration(item)
+            .ok()
+            .and_then(|lead| u32::try_from(lead.num_minutes()).ok())
+            .filter(|minutes| *minutes > 0);
+        match lead {
+            Some(minutes) => leads.push(minutes),
+            None => {
+                return Err(format!(
+                    "Invalid warning \"{}\", expected a time ahead such as 30m or 1d, or none",
+                    item.trim()
+                ))
+            }
+        }
+    }
+    leads.sort_unstable();
+    leads.dedup();
+    return Ok(Some(leads));
+}
+
+// Reads where to send a reminder, like "desktop, file", with default for the settings of Hermes
+pub fn parse_sinks(flags: &Flags) -> Result<Option<Vec<Sink>>, String> {
+    return match flags.get("notify").map(|spec| spec.trim()) {
+        Some(spec) if spec.eq_ignore_ascii_case("default") => Ok(Some(Vec::new())),
+        Some(spec) => reminder::parse_sinks(spec).map(Some),
+        None => Ok(None),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::args::{
+        apply_presentation, parse_alerts, parse_catch_up, parse_count, parse_every, parse_priority,
+        parse_renotify, parse_sinks, parse_until, parse_window, Flags,
+    };
+    use chrono::NaiveDate;
+    use hermes_proto::reminder::{CatchUp, Frequency, Reminder, Sink, Urgency};
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        return args.iter().map(|arg| arg.to_string()).collect();
+    }
+
+    #[test]
+    fn parses_both_flag_forms() {
+        let args = strings(&["--at", "2026-11-02 09:30", "--every=3d", "extra"]);
+        let flags = Flags::parse(&args, &["at", "every"], &[]).unwrap();
+        assert_eq!(flags.get("at"), Some("2026-11-02 09:30"));
+        assert_eq!(flags.get("every"), Some("3d"));
+        assert_eq!(flags.get("message"), None);
+        assert_eq!(flags.positional, vec!["extra".to_string()]);
+    }
+
+    #[test]
+    fn rejects_bad_flags() {
+        assert!(Flags::parse(&strings(&["--nope", "1"]), &["at"], &[]).is_err());
+        assert!(Flags::parse(&strings(&["--at"]), &["at"], &[]).is_err());
+        assert!(Flags::parse(&strings(&["--at", "1", "--at", "2"]), &["at"], &[]).is_err());
+        assert!(Flags::parse(&strings(&["--all=yes"]), &[], &["all"]).is_err());
+    }
+
+    #[test]
+    fn parses_switches() {
+        let args = strings(&["3", "--all", "4"]);
+        let flags = Flags::parse(&args, &[], &["all"]).unwrap();
+        assert!(flags.has("all"));
+        assert!(!flags.has("none"));
+        assert_eq!(flags.positional, vec!["3".to_string(), "4".to_string()]);
+    }
+
+    #[test]
+    fn recurrence_specs() {
+        assert_eq!(parse_every("once"), Ok((Frequency::ONCE, None, None)));
+        assert_eq!(parse_every("Daily"), Ok((Frequency::DAILY, None, None)));
+        assert_eq!(parse_every("1d"), Ok((Frequency::DAILY, None, None)));
+        assert_eq!(parse_every("1w"), Ok((Frequency::WEEKLY, None, None)));
+        assert_eq!(parse_every("3d"), Ok((Frequency::NDAYS, Some(3), None)));
+        assert_eq!(parse_every("2w"), Ok((Frequency::NDAYS, Some(14), None)));
+        assert_eq!(parse_every("Monthly"), Ok((Frequency::MONTHLY, None, None)));
+        assert_eq!(parse_every("annually"), Ok((Frequency::YEARLY, None, None)));
+
+        let (frequency, _, rule) = parse_every("weekdays").unwrap();
+        assert_eq!(frequency, Frequency::RULE);
+        assert_eq!(
+            rule.unwrap().to_string(),
+            "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR"
+        );
+        let (_, _, rule) = parse_every("FREQ=monthly;BYMONTHDAY=15").unwrap();
+        assert_eq!(rule.unwrap().to_string(), "FREQ=MONTHLY;BYMONTHDAY=15");
+        assert!(parse_every("FREQ=FORTNIGHTLY").is_err());
+        assert!(parse_every("0d").is_err());
+        assert!(parse_every("3x").is_err());
+        assert!(parse_every("").is_err());
+    }
+
+    #[test]
+    fn renotify_flags() {
+        let known = &["renotify"];
+        let switches = &["persistent"];
+        let parse =
+            |args: &[&str]| parse_renotify(&Flags::parse(&strings(args), known, switches).unwrap());
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(parse(&["--persistent"]), Ok(Some(Some(5))));
+        assert_eq!(parse(&["--renotify", "15m"]), Ok(Some(Some(15))));
+        assert_eq!(parse(&["--renotify=Off"]), Ok(Some(None)));
+        assert!(parse(&["--renotify", "later"]).is_err());
+    }
+
+    #[test]
+    fn catch_up_flag() {
+        let parse = |args: &[&str]| {
+            parse_catch_up(&Flags::parse(&strings(args), &["catch-up"], &[]).unwrap())
+        };
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(
+            parse(&["--catch-up", "summary"]),
+            Ok(Some(Some(CatchUp::Summary)))
+        );
+        assert_eq!(parse(&["--catch-up=Default"]), Ok(Some(None)));
+        assert!(parse(&["--catch-up", "never"]).is_err());
+    }
+
+    #[test]
+    fn priority_flag() {
+        let parse = |args: &[&str]| {
+            parse_priority(&Flags::parse(&strings(args), &["priority"], &[]).unwrap())
+        };
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(parse(&["--priority", "High"]), Ok(Some(true)));
+        assert_eq!(parse(&["--priority=normal"]), Ok(Some(false)));
+        assert!(parse(&["--priority", "urgent"]).is_err());
+    }
+
+    #[test]
+    fn end_flags() {
+        let flags = |args: &[&str]| Flags::parse(&strings(args), &["count", "until"], &[]).unwrap();
+        assert_eq!(parse_count(&flags(&[])), Ok(None));
+        assert_eq!(parse_count(&flags(&["--count", "30"])), Ok(Some(Some(30))));
+        assert_eq!(parse_count(&flags(&["--count=None"])), Ok(Some(None)));
+        assert!(parse_count(&flags(&["--count", "0"])).is_err());
+        assert!(parse_count(&flags(&["--count", "many"])).is_err());
+
+        assert_eq!(
+            parse_until(&flags(&["--until", "2026-11-30"])),
+            Ok(Some(NaiveDate::from_ymd_opt(2026, 11, 30)))
+        );
+        assert_eq!(parse_until(&flags(&["--until", "none"])), Ok(Some(None)));
+        assert!(parse_until(&flags(&["--until", "someday"])).is_err());
+    }
+
+    #[test]
+    fn agenda_windows() {
+        let flags = |args: &[&str]| {
+            Flags::parse(&strings(args), &["days", "from", "to"], &["today"]).unwrap()
+        };
+        let at = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2026, 10, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let now = at(14, 10);
+
+        assert_eq!(parse_window(&flags(&[]), now), Ok((now, at(21, 10))));
+        assert_eq!(
+            parse_window(&flags(&["--today"]), now),
+            Ok((now, at(15, 0)))
+        );
+        assert_eq!(
+            parse_window(&flags(&["--days", "2"]), now),
+            Ok((now, at(16, 10)))
+        );
+        assert_eq!(
+            parse_window(
+                &flags(&["--from", "2026-10-20 08:00", "--to", "2026-10-22 08:00"]),
+                now
+            ),
+            Ok((at(20, 8), at(22, 8)))
+        );
+        assert_eq!(
+            parse_window(&flags(&["--to", "2026-10-15 09:00"]), now),
+            Ok((now, at(15, 9)))
+        );
+        assert!(parse_window(&flags(&["--today", "--days", "2"]), now).is_err());
+        assert!(parse_window(&flags(&["--days", "0"]), now).is_err());
+        assert!(parse_window(&flags(&["--to", "2026-10-13 09:00"]), now).is_err());
+    }
+
+    #[test]
+    fn presentation_flags() {
+        let known = ["title", "urgency", "icon", "category", "timeout"];
+        let flags = |args: &[&str]| Flags::parse(&strings(args), &known, &[]).unwrap();
+        let mut reminder = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "x".to_string());
+
+        let given = flags(&[
+            "--title",
+            "Standup",
+            "--urgency",
+            "Critical",
+            "--icon=alarm-symbolic",
+            "--timeout",
+            "10s",
+        ]);
+        assert_eq!(apply_presentation(&given, &mut reminder), Ok(()));
+        assert_eq!(reminder.title.as_deref(), Some("Standup"));
+        assert_eq!(reminder.urgency, Some(Urgency::Critical));
+        assert_eq!(reminder.icon.as_deref(), Some("alarm-symbolic"));
+        assert_eq!(reminder.category, None);
+        assert_eq!(reminder.timeout, Some(10000));
+
+        let cleared = flags(&[
+            "--title",
+            "none",
+            "--urgency",
+            "none",
+            "--timeout",
+            "default",
+        ]);
+        assert_eq!(apply_presentation(&cleared, &mut reminder), Ok(()));
+        assert_eq!(reminder.title, None);
+        assert_eq!(reminder.urgency, None);
+        assert_eq!(reminder.icon.as_deref(), Some("alarm-symbolic"));
+        assert_eq!(reminder.timeout, None);
+
+        for (timeout, millis) in [("never", 0), ("2m", 120000), ("30", 30000)] {
+            let given = flags(&["--timeout", timeout]);
+            assert_eq!(apply_presentation(&given, &mut reminder), Ok(()));
+            assert_eq!(reminder.timeout, Some(millis));
+        }
+        assert!(apply_presentation(&flags(&["--timeout", "0s"]), &mut reminder).is_err());
+        assert!(apply_presentation(&flags(&["--urgency", "loud"]), &mut reminder).is_err());
+    }
+
+    #[test]
+    fn alert_flag() {
+        let flags = |args: &[&str]| Flags::parse(&strings(args), &["alert"], &[]).unwrap();
+        assert_eq!(parse_alerts(&flags(&[])), Ok(None));
+        assert_eq!(
+            parse_alerts(&flags(&["--alert", "1d, 30m, 2 hours, 30 minutes"])),
+            Ok(Some(vec![30, 120, 1440]))
+        );
+        assert_eq!(parse_alerts(&flags(&["--alert=none"])), Ok(Some(vec![])));
+        assert!(parse_alerts(&flags(&["--alert", "30m, soon"])).is_err());
+        assert!(parse_alerts(&flags(&["--alert", "0m"])).is_err());
+    }
+
+    #[test]
+    fn notify_flag() {
+        let flags = |args: &[&str]| Flags::parse(&strings(args), &["notify"], &[]).unwrap();
+        assert_eq!(parse_sinks(&flags(&[])), Ok(None));
+        assert_eq!(
+            parse_sinks(&flags(&["--notify", "file, Desktop, file"])),
+            Ok(Some(vec![Sink::File, Sink::Desktop]))
+        );
+        assert_eq!(parse_sinks(&flags(&["--notify=default"])), Ok(Some(vec![])));
+        assert!(parse_sinks(&flags(&["--notify", "pager"])).is_err());
+        assert!(parse_sinks(&flags(&["--notify", "file,"])).is_err());
+    }
+}
@@ -5,21 +5,213 @@ const HELP: &[(&str, &str)] = &[
     ("version", "version information"),
     ("-h", "usage information"),
     ("help", "usage information"),
-    ("add", "interactively add a reminder"),
+    (
+        "add",
+        "add a reminder, asking for anything not given as a flag",
+    ),
     ("list", "list existing reminders"),
-    ("remove", "interactively remove a reminder"),
+    (
+        "agenda",
+        "list what is due over the next week, or the window given, in order",
+    ),
+    (
+        "edit",
+        "change the time, recurrence or message of a reminder",
+    ),
+    (
+        "remove",
+        "remove reminders by id or filter, asking for an id if none are given",
+    ),
+    (
+        "ack",
+        "acknowledge a persistent reminder, so it is not sent again",
+    ),
+    (
+        "snooze",
+        "send a reminder again after a while, e.g. snooze 4 1h, 10 minutes by default",
+    ),
+    (
+        "pause",
+        "stop sending a reminder for now, until resumed or the time given",
+    ),
+    ("resume", "send a paused reminder again"),
+    (
+        "dnd",
+        "show do not disturb, or set it: dnd on, dnd on 2h, dnd on until 7am, dnd off",
+    ),
+    (
+        "status",
+        "show whether notifications are getting through, and how many wait to be sent",
+    ),
 ];
 
+const ADD_FLAGS: &[(&str, &str)] = &[
+    (
+        "--at TIME",
+        "when to send the reminder, e.g. \"tomorrow 9am\" or \"2026-11-02 09:30\"",
+    ),
+    (
+        "--every SPEC",
+        "once, daily, weekly, monthly, yearly, a count of days or weeks, e.g. 3d or 2w, weekdays, or a rule",
+    ),
+    ("--message TEXT", "message body of the reminder"),
+    (
+        "--persistent",
+        "send the reminder again every 5 minutes until it is dismissed",
+    ),
+    (
+        "--renotify SPEC",
+        "as --persistent, every SPEC instead, e.g. 15m, or off to send it once",
+    ),
+    (
+        "--catch-up POLICY",
+        "occurrences missed while Hermes was down: once, all, summary or skip",
+    ),
+    (
+        "--priority LEVEL",
+        "high to send it even during quiet hours and do not disturb, or normal",
+    ),
+    (
+        "--until DATE",
+        "last day a recurring reminder is sent on, e.g. 2026-11-30 or \"in 10 days\"",
+    ),
+    (
+        "--count N",
+        "number of times a recurring reminder is sent before it is removed",
+    ),
+    (
+        "--alert LEADS",
+        "also warn this far ahead of each time, e.g. \"30m, 1d\"",
+    ),
+    ("--title TEXT", "summary shown above the message, Hermes by default"),
+    ("--urgency LEVEL", "low, normal or critical"),
+    ("--icon NAME", "icon name from the desktop's theme, or path to an image"),
+    ("--category NAME", "kind of notification, such as calendar"),
+    (
+        "--timeout TIME",
+        "how long the notification stays on screen, e.g. 10s or 2m, or never",
+    ),
+    (
+        "--notify SINKS",
+        "where to send it: desktop, file, wall, command or http, e.g. \"desktop, file\"",
+    ),
+];
+
+const EDIT_FLAGS: &[(&str, &str)] = &[
+    ("ID", "id of the reminder to change, as shown by list"),
+    ("--at TIME", "new time, as for add"),
+    ("--every SPEC", "new recurrence, as for add"),
+    ("--message TEXT", "new message body"),
+    ("--persistent", "as for add"),
+    ("--renotify SPEC", "as for add"),
+    (
+        "--catch-up POLICY",
+        "as for add, or default to follow the setting of Hermes",
+    ),
+    ("--priority LEVEL", "as for add"),
+    ("--until DATE", "as for add, or none to keep going"),
+    (
+        "--count N",
+        "number of times left to send it, or none to keep going",
+    ),
+    ("--alert LEADS", "as for add, or none to stop warning"),
+    ("--title TEXT", "as for add, or none for Hermes"),
+    ("--urgency LEVEL", "as for add, or none"),
+    ("--icon NAME", "as for add, or none"),
+    ("--category NAME", "as for add, or none"),
+    ("--timeout TIME", "as for add, or default"),
+    (
+        "--notify SINKS",
+        "as for add, or default to follow the setting of Hermes",
+    ),
+];
+
+pub const ADD_FLAGS_KNOWN: &[&str] = &[
+    "alert", "at", "catch-up", "category", "count", "every", "icon", "message", "notify",
+    "priority", "renotify", "timeout", "title", "until", "urgency",
+];
+pub const ADD_SWITCHES_KNOWN: &[&str] = &["persistent"];
+
+const REMOVE_FLAGS: &[(&str, &str)] = &[
+    ("ID...", "ids of the reminders to remove, as shown by list"),
+    (
+        "--all-once-expired",
+        "every one-time reminder whose time has passed",
+    ),
+    (
+        "--matching TEXT",
+        "every reminder whose message contains TEXT",
+    ),
+];
+
+const PAUSE_FLAGS: &[(&str, &str)] = &[
+    ("ID", "id of the reminder to pause, as shown by list"),
+    (
+        "--until TIME",
+        "resume it by itself at TIME, as for add, e.g. \"in 2 weeks\"",
+    ),
+    (
+        "--hold",
+        "send the occurrences due meanwhile as missed on resuming, instead of skipping them",
+    ),
+];
+
+const AGENDA_FLAGS: &[(&str, &str)] = &[
+    ("--today", "from now until midnight"),
+    ("--days N", "from now for N days"),
+    ("--from TIME", "from TIME, as for add, now by default"),
+    (
+        "--to TIME",
+        "until TIME, as for add, a week after --from by default",
+    ),
+];
+
+pub const AGENDA_FLAGS_KNOWN: &[&str] = &["days", "from", "to"];
+pub const AGENDA_SWITCHES_KNOWN: &[&str] = &["today"];
+
+pub const PAUSE_FLAGS_KNOWN: &[&str] = &["until"];
+pub const PAUSE_SWITCHES_KNOWN: &[&str] = &["hold"];
+
+pub const REMOVE_FLAGS_KNOWN: &[&str] = &["matching"];
+pub const REMOVE_SWITCHES_KNOWN: &[&str] = &["all-once-expired"];
+
 pub fn help_info() {
     println!(
         "Caduceus, a client for Hermes\n\
-	 Usage: caduceus [OPTION]\n\n\
+	 Usage: caduceus [OPTION] [FLAGS]\n\n\
 	 Options:"
     );
 
     for (opt, long) in HELP.iter() {
         println!("\t{}\t\t{}", opt, long);
     }
+
+    println!("\nFlags for add:");
+    for (flag, long) in ADD_FLAGS.iter() {
+        println!("\t{:<20}{}", flag, long);
+    }
+
+    println!(
+        "\nFlags for edit (anything not given is kept, without any flags each part is asked for):"
+    );
+    for (flag, long) in EDIT_FLAGS.iter() {
+        println!("\t{:<20}{}", flag, long);
+    }
+
+    println!("\nFlags for remove:");
+    for (flag, long) in REMOVE_FLAGS.iter() {
+        println!("\t{:<20}{}", flag, long);
+    }
+
+    println!("\nFlags for agenda:");
+    for (flag, long) in AGENDA_FLAGS.iter() {
+        println!("\t{:<20}{}", flag, long);
+    }
+
+    println!("\nFlags for pause:");
+    for (flag, long) in PAUSE_FLAGS.iter() {
+        println!("\t{:<20}{}", flag, long);
+    }
 }
 
 pub fn version_info() {
@@ -1,141 +1,655 @@
-use crate::reminder;
+use crate::args::{self, Flags};
+use crate::timespec;
+use chrono::{Datelike, Local, NaiveDateTime, Timelike, Utc};
+use hermes_proto::protocol::{
+    self, Command, DeliveryStatus, DoNotDisturb, Request, Response, Selector, Status,
+};
+use hermes_proto::recurrence::Rule;
+use hermes_proto::reminder::{self, Pause};
+use hermes_proto::zone::{self, Tz};
+use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::{io, str::FromStr};
-use zmq::{self, Message};
+use zmq;
 
 /*
 * This module contains methods used in communication with Hermes, the background daemon
 */
 
-pub fn add_reminder() {
-    let reminder = build_message_interactive();
-    if let None = reminder {
-        return;
+const HERMES_ADDRESS: &str = "ipc:///tmp/hermesd";
+
+// Request ids only need to be unique per connection, so a counter is enough
+static NEXT_REQUEST_ID: AtomicU32 = AtomicU32::new(1);
+
+const DEFAULT_SNOOZE_MINUTES: u32 = 10;
+const AGENDA_DAY_FORMAT: &str = "%A %Y-%m-%d";
+
+pub fn add_reminder(flags: &Flags) -> bool {
+    let reminder = build_message(flags);
+    if reminder.is_none() {
+        return false;
     }
     let reminder = reminder.unwrap();
 
-    let zmq_conn = construct_socket();
-    if let None = zmq_conn {
-        return;
+    let response = send_request(Command::Add, vec![reminder.serialize()]);
+    if response.is_none() {
+        return false;
+    }
+    match response
+        .unwrap()
+        .payload
+        .first()
+        .and_then(|id| protocol::decode_u32(id))
+    {
+        Some(id) => println!("Added reminder with id {}", id),
+        None => println!("Added reminder"),
     }
-    let (_, socket) = zmq_conn.unwrap();
+    return true;
+}
 
-    let success = socket.connect("ipc:///tmp/hermesd");
-    if let Err(err) = success {
-        println!("Error connecting: {}", err);
-        return;
+pub fn list_reminders() -> bool {
+    let response = send_request(Command::List, vec![]);
+    if response.is_none() {
+        return false;
+    }
+
+    for entry in response.unwrap().payload {
+        match protocol::deserialize_entry(&entry) {
+            Ok((id, reminder)) => println!("ID: {} |{}", id, reminder),
+            Err(err) => println!("Malformed reminder received: {}", err),
+        }
+    }
+    return true;
+}
+
+// Lists what is due over a window, as Hermes will send it, a day at a time
+pub fn show_agenda(flags: &Flags) -> bool {
+    let now = Local::now().naive_local();
+    let window = args::parse_window(flags, now).and_then(|(from, to)| {
+        match (zone::resolve(None, from), zone::resolve(None, to)) {
+            (Some(from), Some(to)) => Ok((from, to)),
+            _ => Err("That time does not exist".to_string()),
+        }
+    });
+    let (from, to) = match window {
+        Ok(window) => window,
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    };
+
+    let payload = vec![protocol::encode_time(from), protocol::encode_time(to)];
+    let response = send_request(Command::Agenda, payload);
+    if response.is_none() {
+        return false;
+    }
+    let occurrences = response.unwrap().payload;
+    if occurrences.is_empty() {
+        println!(
+            "Nothing due until {}",
+            to.with_timezone(&Local).format(AGENDA_DAY_FORMAT)
+        );
+        return true;
+    }
+
+    let mut day = None;
+    for occurrence in occurrences {
+        let (at, id, reminder) = match protocol::deserialize_occurrence(&occurrence) {
+            Ok(occurrence) => occurrence,
+            Err(err) => {
+                println!("Malformed occurrence received: {}", err);
+                continue;
+            }
+        };
+        let at = zone::wall_time(None, at);
+        if day != Some(at.date()) {
+            day = Some(at.date());
+            println!("{}", at.format(AGENDA_DAY_FORMAT));
+        }
+        println!(
+            "  {}  {:<10}  ID: {} | {}",
+            at.format("%H:%M"),
+            timespec::describe_relative(now, at),
+            id,
+            reminder.message.lines().next().unwrap_or("")
+        );
+    }
+    return true;
+}
+
+// Deletes the reminders picked by ids and filters, or asks for one when none are given
+pub fn delete_reminder(flags: &Flags) -> bool {
+    let mut selectors = Vec::new();
+    for id in flags.positional.iter() {
+        match id.parse::<u32>() {
+            Ok(id) => selectors.push(Selector::Id(id)),
+            Err(_) => {
+                println!("Invalid id: {}", id);
+                return false;
+            }
+        }
+    }
+    if flags.has("all-once-expired") {
+        selectors.push(Selector::OnceExpired);
+    }
+    if let Some(text) = flags.get("matching") {
+        if text.trim().is_empty() {
+            println!("--matching needs some text to look for");
+            return false;
+        }
+        selectors.push(Selector::Matching(text.to_string()));
+    }
+
+    if selectors.is_empty() {
+        match select_interactive() {
+            Some(id) => selectors.push(Selector::Id(id)),
+            None => return false,
+        }
     }
 
-    let _ = socket.send_multipart(
-        vec![
-            "HERMES".as_bytes().to_vec(),
-            vec![1_u8],
-            reminder.serialize(),
-        ],
-        0,
+    let response = send_request(
+        Command::Delete,
+        selectors
+            .iter()
+            .map(|selector| selector.serialize())
+            .collect(),
     );
-    let mut msg = Message::new();
-    let result = socket.recv(&mut msg, 0);
-    if let Err(err) = result {
-        println!("Error in receiving response from Hermes: {}", err);
+    if response.is_none() {
+        return false;
+    }
+    let results = response.unwrap().payload;
+    if results.is_empty() {
+        println!("No reminders matched");
+        return true;
     }
+
+    let mut success = true;
+    for result in results {
+        match protocol::deserialize_result(&result) {
+            Some((id, Status::Ok)) => println!("Deleted reminder {}", id),
+            Some((id, status)) => {
+                println!("Failed to delete reminder {}: {}", id, status);
+                success = false;
+            }
+            None => {
+                println!("Malformed result received");
+                success = false;
+            }
+        }
+    }
+    return success;
 }
 
-pub fn list_reminders() {
-    let comm = construct_socket();
-    if comm.is_none() {
-        return;
+// Changes the recurrence, time or message of a stored reminder, keeping its id
+pub fn edit_reminder(flags: &Flags) -> bool {
+    let id = match flags.positional.as_slice() {
+        [id] => id.parse::<u32>().ok(),
+        _ => None,
+    };
+    if id.is_none() {
+        println!("Edit expects the id of one reminder, as shown by list");
+        return false;
     }
-    let (_, socket) = comm.unwrap();
-    let success = socket.connect("ipc:///tmp/hermesd");
-    if let Err(err) = success {
-        println!("Error connecting: {}", err);
-        return;
+    let id = id.unwrap();
+
+    let current = find_reminder(id);
+    if current.is_none() {
+        return false;
     }
+    let current = current.unwrap();
+    let mut reminder = current.clone();
 
-    let _ = socket.send_multipart(vec!["HERMES".as_bytes().to_vec(), vec![2_u8]], 0);
-    let data = socket.recv_multipart(0);
-    if let Err(err) = data {
-        println!("Error while receiving data: {}\n", err);
-        return;
+    match args::parse_renotify(flags) {
+        Ok(Some(renotify)) => reminder.renotify = renotify,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    match args::parse_catch_up(flags) {
+        Ok(Some(catch_up)) => reminder.catch_up = catch_up,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    match args::parse_priority(flags) {
+        Ok(Some(high_priority)) => reminder.high_priority = high_priority,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    match args::parse_until(flags) {
+        Ok(Some(until)) => reminder.until = until,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    match args::parse_count(flags) {
+        Ok(Some(count)) => reminder.remaining = count,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    match args::parse_alerts(flags) {
+        Ok(Some(alerts)) => reminder.alerts = alerts,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+    if let Err(err) = args::apply_presentation(flags, &mut reminder) {
+        println!("{}", err);
+        return false;
+    }
+    match args::parse_sinks(flags) {
+        Ok(Some(sinks)) => reminder.sinks = sinks,
+        Ok(None) => {}
+        Err(err) => {
+            println!("{}", err);
+            return false;
+        }
+    }
+
+    // Without flags, ask for each part, where a blank answer keeps it as it is
+    let interactive = [
+        "every",
+        "at",
+        "message",
+        "renotify",
+        "persistent",
+        "catch-up",
+        "priority",
+        "until",
+        "count",
+        "alert",
+        "title",
+        "urgency",
+        "icon",
+        "category",
+        "timeout",
+        "notify",
+    ]
+    .iter()
+    .all(|flag| !flags.has(flag));
+    let ask = |flag: &str, prompt: &str| -> String {
+        match flags.get(flag) {
+            Some(value) => value.to_string(),
+            None if interactive => read_line(prompt),
+            None => String::new(),
+        }
+    };
+    if interactive {
+        println!("ID: {} |{}", id, current);
+    }
+
+    let every = ask(
+        "every",
+        "New recurrence (once, daily, weekly, monthly, yearly, 3d, 2w), blank to keep:",
+    );
+    if !every.is_empty() {
+        match args::parse_every(&every) {
+            Ok((freq, n, rule)) => {
+                reminder.frequency = freq;
+                reminder.n = n;
+                reminder.rule = rule;
+            }
+            Err(err) => {
+                println!("{}", err);
+                return false;
+            }
+        }
+    }
+
+    let time = ask("at", "New time (e.g. \"tomorrow 9am\"), blank to keep:");
+    if !time.is_empty() {
+        match resolve_time(&time) {
+            Some((datetime, zone)) => {
+                set_time(&mut reminder, datetime);
+                reminder.zone = zone;
+            }
+            None => return false,
+        }
+    }
+
+    let message = ask("message", "New message body, blank to keep:");
+    if !message.is_empty() {
+        reminder.message = message;
+    }
+
+    if !align_to_rule(&mut reminder) {
+        return false;
+    }
+    // Hermes counts a changed schedule from its new time
+    if reminder.frequency != current.frequency
+        || reminder.n != current.n
+        || reminder.rule != current.rule
+        || reminder.to_naive() != current.to_naive()
+        || reminder.zone != current.zone
+    {
+        reminder.start = None;
+    }
+    if reminder == current {
+        println!("Nothing to change");
+        return true;
+    }
+    if let Err(err) = reminder.validate() {
+        println!("Invalid reminder: {}", err);
+        return false;
+    }
+
+    let payload = vec![protocol::encode_u32(id), reminder.serialize()];
+    if send_request(Command::Update, payload).is_none() {
+        return false;
+    }
+    println!("Updated reminder {}", id);
+    println!("ID: {} |{}", id, reminder);
+    return true;
+}
+
+fn find_reminder(id: u32) -> Option<reminder::Reminder> {
+    let response = send_request(Command::List, vec![])?;
+    for entry in response.payload {
+        if let Ok((entry_id, reminder)) = protocol::deserialize_entry(&entry) {
+            if entry_id == id {
+                return Some(reminder);
+            }
+        }
+    }
+    println!("No reminder with id {}", id);
+    return None;
+}
+
+// Stops the repeated notifications of a persistent reminder that has been sent
+pub fn ack_reminder(flags: &Flags) -> bool {
+    let id = match flags.positional.as_slice() {
+        [id] => id.parse::<u32>().ok(),
+        _ => None,
+    };
+    if id.is_none() {
+        println!("Ack expects the id of one reminder, as shown by list");
+        return false;
+    }
+    let id = id.unwrap();
+
+    if send_request(Command::Ack, vec![protocol::encode_u32(id)]).is_none() {
+        return false;
+    }
+    println!("Acknowledged reminder {}", id);
+    return true;
+}
+
+// Has Hermes send a reminder again after a while, 10 minutes unless a length of time is given
+pub fn snooze_reminder(flags: &Flags) -> bool {
+    let (id, duration) = match flags.positional.split_first() {
+        Some((id, rest)) => (id.parse::<u32>().ok(), rest.join(" ")),
+        None => (None, String::new()),
+    };
+    if id.is_none() {
+        println!("Snooze expects the id of one reminder, as shown by list");
+        return false;
+    }
+    let id = id.unwrap();
+
+    let minutes = if duration.is_empty() {
+        DEFAULT_SNOOZE_MINUTES
+    } else {
+        let minutes = timespec::parse_duration(&duration).and_then(|duration| {
+            u32::try_from(duration.num_minutes()).map_err(|_| "Too long to snooze".to_string())
+        });
+        match minutes {
+            Ok(minutes) => minutes,
+            Err(err) => {
+                println!("{}", err);
+                return false;
+            }
+        }
+    };
+
+    let payload = vec![protocol::encode_u32(id), protocol::encode_u32(minutes)];
+    if send_request(Command::Snooze, payload).is_none() {
+        return false;
+    }
+    let until = Local::now().naive_local() + chrono::Duration::minutes(minutes as i64);
+    println!(
+        "Snoozed reminder {} until {}",
+        id,
+        until.format("%A %Y-%m-%d %H:%M")
+    );
+    return true;
+}
+
+/*
+* Pauses a reminder until resumed, or until the time given with --until. Its occurrences meanwhile
+* are skipped, or with --hold sent as missed once it resumes.
+*/
+pub fn pause_reminder(flags: &Flags) -> bool {
+    let id = match flags.positional.as_slice() {
+        [id] => id.parse::<u32>().ok(),
+        _ => None,
+    };
+    if id.is_none() {
+        println!("Pause expects the id of one reminder, as shown by list");
+        return false;
+    }
+    let id = id.unwrap();
+
+    let until = match flags.get("until") {
+        Some(time) => match timespec::parse_zoned_time(time) {
+            Ok((time, zone)) => match zone::resolve(zone, time) {
+                Some(until) => Some(until),
+                None => {
+                    println!("That time does not exist");
+                    return false;
+                }
+            },
+            Err(err) => {
+                println!("{}", err);
+                return false;
+            }
+        },
+        None => None,
+    };
+    let pause = Pause {
+        until,
+        hold: flags.has("hold"),
+    };
+
+    let payload = vec![protocol::encode_u32(id), pause.serialize()];
+    if send_request(Command::Pause, payload).is_none() {
+        return false;
     }
-    let mut data = data.unwrap();
+    match until {
+        Some(until) => println!(
+            "Paused reminder {} until {}",
+            id,
+            until.with_timezone(&Local).format("%A %Y-%m-%d %H:%M")
+        ),
+        None => println!("Paused reminder {} until resumed", id),
+    }
+    return true;
+}
 
-    // Let server know data is sent
-    let mut res = socket.send("RECEIVED", 0);
-    while res.is_err() {
-        res = socket.send("RECEIVED", 0);
+pub fn resume_reminder(flags: &Flags) -> bool {
+    let id = match flags.positional.as_slice() {
+        [id] => id.parse::<u32>().ok(),
+        _ => None,
+    };
+    if id.is_none() {
+        println!("Resume expects the id of one reminder, as shown by list");
+        return false;
     }
+    let id = id.unwrap();
 
-    if data.is_empty() || std::str::from_utf8(&data[0]).unwrap() != "HERMES" {
-        println!("Malformed message received");
-        return;
+    if send_request(Command::Resume, vec![protocol::encode_u32(id)]).is_none() {
+        return false;
     }
-    data.remove(0); // TODO: Better way?
-    for reminder in data {
-        let rem = reminder::Reminder::deserialize_reminder(reminder);
-        if let Some(value) = rem {
-            value.print();
+    println!("Resumed reminder {}", id);
+    return true;
+}
+
+/*
+* Shows do not disturb, or sets it with "on", "on DURATION", "on until TIME" or "off". Reminders
+* due meanwhile are held back, as during quiet hours, unless they have a high priority.
+*/
+pub fn do_not_disturb(flags: &Flags) -> bool {
+    let words: Vec<&str> = flags.positional.iter().map(|word| word.as_str()).collect();
+    let dnd = match words.split_first() {
+        None => None,
+        Some((&"off", [])) => Some(DoNotDisturb::Off),
+        Some((&"on", [])) => Some(DoNotDisturb::On),
+        Some((&"on", ["until", time @ ..])) => {
+            let until = timespec::resolve_zoned(&time.join(" "), Utc::now());
+            match until.map(|(time, zone)| zone::resolve(zone, time)) {
+                Ok(Some(until)) => Some(DoNotDisturb::Until(until)),
+                Ok(None) => {
+                    println!("That time does not exist");
+                    return false;
+                }
+                Err(err) => {
+                    println!("{}", err);
+                    return false;
+                }
+            }
+        }
+        Some((&"on", duration)) => match timespec::parse_duration(&duration.join(" ")) {
+            Ok(duration) => Some(DoNotDisturb::Until(Utc::now() + duration)),
+            Err(err) => {
+                println!("{}", err);
+                return false;
+            }
+        },
+        Some(_) => {
+            println!("Expected dnd, dnd off, dnd on, dnd on DURATION or dnd on until TIME");
+            return false;
         }
+    };
+
+    let payload = dnd.iter().map(|dnd| dnd.serialize()).collect();
+    let response = send_request(Command::DoNotDisturb, payload);
+    if response.is_none() {
+        return false;
     }
+    let current = response
+        .unwrap()
+        .payload
+        .first()
+        .and_then(|dnd| DoNotDisturb::deserialize(dnd));
+    match current {
+        Some(DoNotDisturb::Off) => println!("Do not disturb is off"),
+        Some(DoNotDisturb::On) => println!("Do not disturb is on until turned off"),
+        Some(DoNotDisturb::Until(until)) => println!(
+            "Do not disturb is on until {}",
+            until.with_timezone(&Local).format("%A %Y-%m-%d %H:%M")
+        ),
+        None => {
+            println!("Malformed response received");
+            return false;
+        }
+    }
+    return true;
 }
 
-pub fn delete_reminder() {
-    let comm = construct_socket();
-    if comm.is_none() {
-        return;
+// Shows whether Hermes can reach the desktop, and how many notifications wait to be sent again
+pub fn show_status() -> bool {
+    let response = send_request(Command::Status, vec![]);
+    if response.is_none() {
+        return false;
+    }
+    let status = response
+        .unwrap()
+        .payload
+        .first()
+        .and_then(|status| DeliveryStatus::deserialize(status));
+    if status.is_none() {
+        println!("Malformed response received");
+        return false;
     }
-    let (_, socket) = comm.unwrap();
-    let success = socket.connect("ipc:///tmp/hermesd");
+    let status = status.unwrap();
+
+    match &status.degraded {
+        None => println!("Delivery: ok"),
+        Some((since, reason)) => println!(
+            "Delivery: degraded since {}, {}",
+            since.with_timezone(&Local).format("%A %Y-%m-%d %H:%M"),
+            reason
+        ),
+    }
+    match status.queued {
+        0 => {}
+        1 => println!("1 notification waiting to be sent again"),
+        queued => println!("{} notifications waiting to be sent again", queued),
+    }
+    return true;
+}
+
+// Lists reminders with their ids, and reads the id of one to act on
+fn select_interactive() -> Option<u32> {
+    let response = send_request(Command::List, vec![])?;
+
+    for entry in response.payload {
+        match protocol::deserialize_entry(&entry) {
+            Ok((id, reminder)) => println!("ID: {} |{}", id, reminder),
+            Err(err) => println!("Malformed Message received: {}", err),
+        }
+    }
+
+    println!("Enter the id of a reminder to delete:");
+    return read_in_integer::<u32>();
+}
+
+// Sends a single request to Hermes, returning its response if the request succeeded
+fn send_request(command: Command, payload: Vec<Vec<u8>>) -> Option<Response> {
+    let (_ctx, socket) = construct_socket()?;
+    let success = socket.connect(HERMES_ADDRESS);
     if let Err(err) = success {
         println!("Error connecting: {}", err);
-        return;
+        return None;
+    }
+
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let request = Request::new(request_id, command, payload);
+    if let Err(err) = socket.send_multipart(request.to_frames(), 0) {
+        println!("Error sending request to Hermes: {}", err);
+        return None;
     }
 
-    let _ = socket.send_multipart(vec!["HERMES".as_bytes().to_vec(), vec![3_u8]], 0);
     let data = socket.recv_multipart(0);
     if let Err(err) = data {
-        println!("Error while receiving data: {}\n", err);
-        return;
-    }
-    let mut data = data.unwrap();
-    if data.is_empty() || std::str::from_utf8(&data[0]).unwrap() != "HERMES" {
-        println!("Malformed message received");
-        return;
-    }
-    data.remove(0);
-    for msg in data {
-        // Should be (u32, Reminder)
-        if msg.len() < 18 {
-            println!("Malformed Message received");
-        } else {
-            let id = u32::from_be_bytes([msg[0], msg[1], msg[2], msg[3]]);
-            let (_, rem) = msg.split_at(4);
-            let reminder = reminder::Reminder::deserialize_reminder(rem.to_vec());
-            if reminder.is_none() {
-                println!("Malformed Message received");
-            } else {
-                print!("ID: {} |", id);
-                let val = reminder.unwrap();
-                val.print();
-            }
-        }
+        println!("Error in receiving response from Hermes: {}", err);
+        return None;
     }
+    let response = match Response::from_frames(&data.unwrap()) {
+        Ok(response) => response,
+        Err(err) => {
+            println!("Malformed response received: {}", err);
+            return None;
+        }
+    };
 
-    println!("Enter the id of a reminder to delete:");
-    //TODO: get ID, send, get response from server
-    let std_in = io::stdin();
-    let mut buffer = String::new();
-    let _ = std_in.read_line(&mut buffer);
-    let id = buffer.trim().parse::<u32>();
-    if id.is_err() {
-        println!("Invalid id entered");
-    } else {
-        let _ = socket.send(buffer.trim(), 0);
-        let mut buff = zmq::Message::new();
-        let _ = socket.recv(&mut buff, 0);
-        println!("{}", buff.as_str().unwrap());
+    if response.request_id != request_id {
+        println!(
+            "Response was for request {}, expected {}",
+            response.request_id, request_id
+        );
+        return None;
     }
+    if response.status != Status::Ok {
+        println!("Hermes reported {}: {}", response.status, response.detail());
+        return None;
+    }
+    return Some(response);
 }
 
 fn construct_socket() -> Option<(zmq::Context, zmq::Socket)> {
@@ -169,94 +683,208 @@ where
     }
 }
 
-fn build_message_interactive() -> Option<reminder::Reminder> {
-    println!("Enter D, O, W, or N, for DAILY, ONCE, WEEKLY, or every N DAYS,  respectively");
-    let std_in = io::stdin();
-    let mut buffer = String::new();
-    let _ = std_in.read_line(&mut buffer);
-
-    let mut freq: Option<reminder::Frequency> = None;
-    match buffer.trim() {
-        "D" => {
-            freq = Some(reminder::Frequency::DAILY);
+// Builds a reminder from the flags given to add, asking for anything missing
+fn build_message(flags: &Flags) -> Option<reminder::Reminder> {
+    let renotify = match args::parse_renotify(flags) {
+        Ok(renotify) => renotify.flatten(),
+        Err(err) => {
+            println!("{}", err);
+            return None;
         }
-        "O" => {
-            freq = Some(reminder::Frequency::ONCE);
+    };
+    let catch_up = match args::parse_catch_up(flags) {
+        Ok(catch_up) => catch_up.flatten(),
+        Err(err) => {
+            println!("{}", err);
+            return None;
         }
-        "W" => {
-            freq = Some(reminder::Frequency::WEEKLY);
+    };
+    let high_priority = match args::parse_priority(flags) {
+        Ok(high_priority) => high_priority.unwrap_or(false),
+        Err(err) => {
+            println!("{}", err);
+            return None;
         }
-        "N" => {
-            freq = Some(reminder::Frequency::NDAYS);
+    };
+    let until = match args::parse_until(flags) {
+        Ok(until) => until.flatten(),
+        Err(err) => {
+            println!("{}", err);
+            return None;
         }
-        _ => {
-            println!("Invalid input received");
+    };
+    let count = match args::parse_count(flags) {
+        Ok(count) => count.flatten(),
+        Err(err) => {
+            println!("{}", err);
+            return None;
         }
-    }
-
-    let freq = freq?;
-
-    let mut n: Option<u32> = None;
-    if freq == reminder::Frequency::NDAYS {
-        println!("Enter a number for how many days between notifications");
-        buffer.clear();
-        let _ = std_in.read_line(&mut buffer);
-        let input = buffer.trim().parse::<u32>();
-        if let Err(_) = input {
-            println!("Error, invalid input received");
+    };
+    let alerts = match args::parse_alerts(flags) {
+        Ok(alerts) => alerts.unwrap_or_default(),
+        Err(err) => {
+            println!("{}", err);
             return None;
-        } else {
-            n = Some(input.unwrap());
         }
-    }
-
-    println!("Enter a month (1 - 12)");
-    let month = read_in_integer::<u8>();
-    month?;
-    let month = month.unwrap();
-    if !(1..=12).contains(&month) {
-        println!("Input not in range");
-        return None;
-    }
-
-    println!("Enter a day (numeric)");
-    let day = read_in_integer::<u8>();
-    day?;
+    };
+    let sinks = match args::parse_sinks(flags) {
+        Ok(sinks) => sinks.unwrap_or_default(),
+        Err(err) => {
+            println!("{}", err);
+            return None;
+        }
+    };
 
-    let day = day.unwrap();
+    let (freq, n, rule) = match flags.get("every") {
+        Some(spec) => match args::parse_every(spec) {
+            Ok(value) => value,
+            Err(err) => {
+                println!("{}", err);
+                return None;
+            }
+        },
+        None => read_frequency_interactive()?,
+    };
 
-    println!("Enter a year");
-    let year = read_in_integer::<u32>();
-    year?;
-    let year = year.unwrap();
+    let time = match flags.get("at") {
+        Some(time) => time.to_string(),
+        None => read_line(
+            "When should the reminder be sent? (e.g. \"tomorrow 9am\", \"in 20 minutes\")",
+        ),
+    };
+    let (datetime, zone) = resolve_time(&time)?;
 
-    println!("Enter an hour (0 - 24)");
-    let hour = read_in_integer::<u8>();
-    hour?;
+    let message = match flags.get("message") {
+        Some(message) => message.to_string(),
+        None => read_line("Enter a message body for the reminder:"),
+    };
 
-    let hour = hour.unwrap();
-    if hour > 24 {
-        // as a u8 it cant be under 0
-        println!("Input not in range");
+    // Can construct Message object
+    let mut message: reminder::Reminder =
+        reminder::Reminder::new(freq, 1, 1, 1970, 0, 0, n, message);
+    set_time(&mut message, datetime);
+    message.zone = zone;
+    message.renotify = renotify;
+    message.rule = rule;
+    message.catch_up = catch_up;
+    message.high_priority = high_priority;
+    message.until = until;
+    message.remaining = count;
+    message.alerts = alerts;
+    message.sinks = sinks;
+    if let Err(err) = args::apply_presentation(flags, &mut message) {
+        println!("{}", err);
+        return None;
+    }
+    if !align_to_rule(&mut message) {
         return None;
     }
 
-    println!("Enter a minute (0-60)");
-    let minute = read_in_integer::<u8>();
-    minute?;
-    let minute = minute.unwrap();
-    if minute > 60 {
-        println!("Input not in range");
+    // Same checks Hermes applies, so mistakes are caught before sending
+    if let Err(err) = message.validate() {
+        println!("Invalid reminder: {}", err);
         return None;
     }
+    return Some(message);
+}
 
-    println!("Enter a message body for the reminder:");
-    buffer.clear();
+// Parses a time given by the user, echoing back the absolute time it resolved to
+fn resolve_time(input: &str) -> Option<(NaiveDateTime, Option<Tz>)> {
+    let (datetime, zone) = match timespec::parse_zoned_time(input) {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            println!("{}", err);
+            return None;
+        }
+    };
+    let zone_name = zone.map_or(String::new(), |zone| format!(" {}", zone.name()));
+    println!(
+        "Reminder will be sent {}{} ({})",
+        datetime.format("%A %Y-%m-%d %H:%M"),
+        zone_name,
+        timespec::describe_relative(zone::wall_time(zone, Utc::now()), datetime)
+    );
+    return Some((datetime, zone));
+}
+
+// Moves a custom reminder to the first occurrence of its rule, from the time it was given
+fn align_to_rule(reminder: &mut reminder::Reminder) -> bool {
+    let (rule, start) = match (&reminder.rule, reminder.to_naive()) {
+        (Some(rule), Some(start)) if reminder.frequency == reminder::Frequency::RULE => {
+            (rule, start)
+        }
+        _ => return true,
+    };
+    return match rule.next_after(start, start - chrono::Duration::minutes(1)) {
+        Some(first) if first == start => true,
+        Some(first) => {
+            println!(
+                "The rule first applies {} ({})",
+                first.format("%A %Y-%m-%d %H:%M"),
+                timespec::describe_relative(reminder.wall_time(Utc::now()), first)
+            );
+            set_time(reminder, first);
+            true
+        }
+        None => {
+            println!("The rule has no occurrences after that time");
+            false
+        }
+    };
+}
+
+fn set_time(reminder: &mut reminder::Reminder, datetime: NaiveDateTime) {
+    reminder.month = datetime.month() as u8;
+    reminder.day = datetime.day() as u8;
+    reminder.year = datetime.year() as u32;
+    reminder.hour = datetime.hour() as u8;
+    reminder.minute = datetime.minute() as u8;
+}
+
+fn read_line(prompt: &str) -> String {
+    println!("{}", prompt);
+    let mut buffer = String::new();
+    let _ = io::stdin().read_line(&mut buffer);
+    return buffer.trim().to_string();
+}
+
+fn read_frequency_interactive() -> Option<(reminder::Frequency, Option<u32>, Option<Rule>)> {
+    println!(
+        "Enter D, O, W, M, Y, N, or R, for DAILY, ONCE, WEEKLY, MONTHLY, YEARLY, every N DAYS, or a RULE, respectively"
+    );
+    let std_in = io::stdin();
+    let mut buffer = String::new();
     let _ = std_in.read_line(&mut buffer);
 
-    // Can construct Message object
-    let message: reminder::Reminder =
-        reminder::Reminder::new(freq, buffer, month, day, year, hour, minute, n);
+    let freq = match buffer.trim() {
+        "D" => reminder::Frequency::DAILY,
+        "O" => reminder::Frequency::ONCE,
+        "W" => reminder::Frequency::WEEKLY,
+        "M" => reminder::Frequency::MONTHLY,
+        "Y" => reminder::Frequency::YEARLY,
+        "N" => reminder::Frequency::NDAYS,
+        "R" => reminder::Frequency::RULE,
+        _ => {
+            println!("Invalid input received");
+            return None;
+        }
+    };
 
-    return Some(message);
+    let mut n: Option<u32> = None;
+    if freq == reminder::Frequency::NDAYS {
+        println!("Enter a number for how many days between notifications");
+        n = Some(read_in_integer::<u32>()?);
+    }
+    let mut rule = None;
+    if freq == reminder::Frequency::RULE {
+        let line = read_line("Enter a rule, e.g. FREQ=MONTHLY;BYDAY=1MO for the first Monday");
+        match line.parse::<Rule>() {
+            Ok(parsed) => rule = Some(parsed),
+            Err(err) => {
+                println!("{}", err);
+                return None;
+            }
+        }
+    }
+    return Some((freq, n, rule));
 }
@@ -1,38 +1,97 @@
+// Explicit returns are the house style
+#![allow(clippy::needless_return)]
+
 use std::env;
+use std::process;
+pub mod args;
 pub mod info;
 pub mod ipc;
-pub mod reminder;
+pub mod timespec;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
+    if args.len() < 2 {
         println!("Invalid Argument count. Run with -h to see help");
-        return;
+        process::exit(1);
     }
-    match args[1].as_str() {
-        "-h" => {
-            info::help_info();
-        }
-        "help" => {
+    let (command, rest) = (args[1].as_str(), &args[2..]);
+    let takes_flags = [
+        "add", "edit", "remove", "snooze", "ack", "dnd", "pause", "resume", "agenda",
+    ]
+    .contains(&command);
+    if !takes_flags && !rest.is_empty() {
+        println!("Invalid Argument count. Run with -h to see help");
+        process::exit(1);
+    }
+
+    let success = match command {
+        "-h" | "help" => {
             info::help_info();
+            true
         }
-        "-v" => {
-            info::version_info();
-        }
-        "version" => {
+        "-v" | "version" => {
             info::version_info();
+            true
         }
-        "add" => {
-            ipc::add_reminder();
-        }
-        "list" => {
-            ipc::list_reminders();
-        }
-        "remove" => {
-            ipc::delete_reminder();
-        }
+        "add" => with_flags(
+            rest,
+            info::ADD_FLAGS_KNOWN,
+            info::ADD_SWITCHES_KNOWN,
+            ipc::add_reminder,
+        ),
+        "list" => ipc::list_reminders(),
+        "status" => ipc::show_status(),
+        "agenda" => with_flags(
+            rest,
+            info::AGENDA_FLAGS_KNOWN,
+            info::AGENDA_SWITCHES_KNOWN,
+            ipc::show_agenda,
+        ),
+        "edit" => with_flags(
+            rest,
+            info::ADD_FLAGS_KNOWN,
+            info::ADD_SWITCHES_KNOWN,
+            ipc::edit_reminder,
+        ),
+        "remove" => with_flags(
+            rest,
+            info::REMOVE_FLAGS_KNOWN,
+            info::REMOVE_SWITCHES_KNOWN,
+            ipc::delete_reminder,
+        ),
+        "snooze" => with_flags(rest, &[], &[], ipc::snooze_reminder),
+        "ack" => with_flags(rest, &[], &[], ipc::ack_reminder),
+        "dnd" => with_flags(rest, &[], &[], ipc::do_not_disturb),
+        "pause" => with_flags(
+            rest,
+            info::PAUSE_FLAGS_KNOWN,
+            info::PAUSE_SWITCHES_KNOWN,
+            ipc::pause_reminder,
+        ),
+        "resume" => with_flags(rest, &[], &[], ipc::resume_reminder),
         _ => {
             println!("Argument not recognized");
+            false
         }
+    };
+
+    if !success {
+        process::exit(1);
     }
 }
+
+// Parses the flags of a command and runs it, returning whether it succeeded
+fn with_flags(
+    rest: &[String],
+    known: &[&str],
+    switches: &[&str],
+    command: fn(&args::Flags) -> bool,
+) -> bool {
+    return match args::Flags::parse(rest, known, switches) {
+        Ok(flags) => command(&flags),
+        Err(err) => {
+            println!("{}", err);
+            false
+        }
+    };
+}
@@ -0,0 +1,418 @@
+// Module to turn the time given to `add --at`, or typed when asked, into a date and time
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc,
+    Weekday,
+};
+use hermes_proto::zone::{self, Tz};
+
+const FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+];
+const EXAMPLES: &str =
+    "e.g. \"in 20 minutes\", \"tomorrow 9am\", \"next friday 14:00\" or \"2026-11-02 09:30\"";
+
+// Hour used when only a day is given, like "tomorrow"
+const DEFAULT_HOUR: u32 = 9;
+
+enum Day {
+    Date(NaiveDate),
+    // Weekday, and whether "next" was given, which excludes today
+    Weekday(Weekday, bool),
+}
+
+pub fn parse_time(input: &str) -> Result<NaiveDateTime, String> {
+    return resolve(input, Local::now().naive_local());
+}
+
+/*
+* As parse_time, for a time that may end with a zone like "9:00 America/New_York", which is then
+* resolved against the time in that zone. Returns the zone, if one was given.
+*/
+pub fn parse_zoned_time(input: &str) -> Result<(NaiveDateTime, Option<Tz>), String> {
+    return resolve_zoned(input, Utc::now());
+}
+
+pub fn resolve_zoned(
+    input: &str,
+    now: DateTime<Utc>,
+) -> Result<(NaiveDateTime, Option<Tz>), String> {
+    if let Some((time, name)) = input.trim().rsplit_once(char::is_whitespace) {
+        if let Ok(zone) = zone::parse_zone(name) {
+            let now = now.with_timezone(&zone).naive_local();
+            return Ok((resolve(time, now)?, Some(zone)));
+        }
+    }
+    return Ok((resolve(input, zone::wall_time(None, now))?, None));
+}
+
+/*
+* Resolves an expression against the given local time. Accepted forms are:
+*   - timestamps, "2026-11-02 09:30", "2026-11-02T09:30:00+01:00"
+*   - "now", or an offset, "in 20 minutes", "in 1h 30m", "in a week"
+*   - a day, a time, or both: "tomorrow 9am", "friday at 14:00", "next mon noon", "2026-11-02 9pm"
+* A day without a time means 09:00, and a time without a day means the next time the clock reads it.
+*/
+pub fn resolve(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let input = input.trim();
+    let now = truncate(now);
+    let invalid = || format!("Could not understand the time \"{}\", {}", input, EXAMPLES);
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Ok(truncate(datetime.with_timezone(&Local).naive_local()));
+    }
+    for format in FORMATS.iter() {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input, format) {
+            return Ok(truncate(datetime));
+        }
+    }
+
+    let lowered = input.to_lowercase();
+    let tokens: Vec<&str> = lowered
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    match tokens.as_slice() {
+        [] => return Err(invalid()),
+        ["now"] => return Ok(now),
+        ["in", rest @ ..] => {
+            let offset = parse_offset(rest).ok_or_else(invalid)?;
+            return now.checked_add_signed(offset).ok_or_else(invalid);
+        }
+        _ => {}
+    }
+
+    let (day, rest) = parse_day(&tokens, now.date());
+    let rest = match rest {
+        ["at", rest @ ..] if !rest.is_empty() => rest,
+        _ => rest,
+    };
+    let time = match rest {
+        [] => None,
+        _ => Some(parse_clock(&rest.concat()).ok_or_else(invalid)?),
+    };
+
+    let today = now.date();
+    return match (day, time) {
+        (None, None) => Err(invalid()),
+        (Some(Day::Date(date)), time) => Ok(date.and_time(time.unwrap_or_else(default_time))),
+        (Some(Day::Weekday(weekday, next)), time) => {
+            let time = time.unwrap_or_else(default_time);
+            let mut ahead = days_until(today.weekday(), weekday);
+            if ahead == 0 && (next || today.and_time(time) <= now) {
+                ahead = 7;
+            }
+            Ok((today + Duration::days(ahead)).and_time(time))
+        }
+        (None, Some(time)) => {
+            let candidate = today.and_time(time);
+            if candidate > now {
+                Ok(candidate)
+            } else {
+                Ok(candidate + Duration::days(1))
+            }
+        }
+    };
+}
+
+// Reads a length of time such as "10m", "1h 30m" or "2 hours", as for "in ..." offsets
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let lowered = input.trim().to_lowercase();
+    let tokens: Vec<&str> = lowered.split_whitespace().collect();
+    return parse_offset(&tokens)
+        .filter(|duration| *duration > Duration::zero())
+        .ok_or_else(|| {
+            format!(
+                "Could not understand the length of time \"{}\", e.g. \"10m\" or \"1h 30m\"",
+                input.trim()
+            )
+        });
+}
+
+// Describes how far `then` is from `now`, like "in 3h 10m" or "2d 4h ago"
+pub fn describe_relative(now: NaiveDateTime, then: NaiveDateTime) -> String {
+    let minutes = (truncate(then) - truncate(now)).num_minutes();
+    if minutes == 0 {
+        return "now".to_string();
+    }
+
+    let total = minutes.abs();
+    let (days, hours, mins) = (total / (24 * 60), total / 60 % 24, total % 60);
+    let parts: Vec<String> = [(days, "d"), (hours, "h"), (mins, "m")]
+        .iter()
+        .skip_while(|(value, _)| *value == 0)
+        .take(2)
+        .filter(|(value, _)| *value != 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+
+    if minutes > 0 {
+        return format!("in {}", parts.join(" "));
+    }
+    return format!("{} ago", parts.join(" "));
+}
+
+// Reminders are only precise to the minute
+fn truncate(datetime: NaiveDateTime) -> NaiveDateTime {
+    return datetime.with_second(0).unwrap().with_nanosecond(0).unwrap();
+}
+
+fn default_time() -> NaiveTime {
+    return NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0).unwrap();
+}
+
+fn days_until(from: Weekday, to: Weekday) -> i64 {
+    let from = from.num_days_from_monday() as i64;
+    let to = to.num_days_from_monday() as i64;
+    return (to - from).rem_euclid(7);
+}
+
+// Reads an optional day from the start of the tokens, returning it and the tokens left over
+fn parse_day<'a, 'b>(tokens: &'a [&'b str], today: NaiveDate) -> (Option<Day>, &'a [&'b str]) {
+    let (next, rest) = match tokens {
+        ["next", rest @ ..] => (true, rest),
+        ["this", rest @ ..] => (false, rest),
+        _ => (false, tokens),
+    };
+    if let Some((first, rest)) = rest.split_first() {
+        if let Some(weekday) = parse_weekday(first) {
+            return (Some(Day::Weekday(weekday, next)), rest);
+        }
+    }
+    if next {
+        return (None, tokens);
+    }
+
+    let day = match tokens.first() {
+        Some(&"today") | Some(&"tonight") => Some(today),
+        Some(&"tomorrow") => Some(today + Duration::days(1)),
+        Some(token) => NaiveDate::parse_from_str(token, "%Y-%m-%d").ok(),
+        None => None,
+    };
+    return match day {
+        Some(date) => (Some(Day::Date(date)), &tokens[1..]),
+        None => (None, tokens),
+    };
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    let weekday = match token {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    return Some(weekday);
+}
+
+// Reads a time of day such as "9am", "9:30pm", "14:00", "noon" or "midnight"
+fn parse_clock(text: &str) -> Option<NaiveTime> {
+    match text {
+        "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (clock, meridiem) = if let Some(clock) = text.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = text.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (text, None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour, minute),
+        // A bare number is only a time with am or pm after it
+        None if meridiem.is_some() => (clock, "0"),
+        None => return None,
+    };
+    let mut hour = hour.parse::<u32>().ok()?;
+    let minute = minute.parse::<u32>().ok()?;
+
+    if let Some(offset) = meridiem {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour = hour % 12 + offset;
+    }
+    return NaiveTime::from_hms_opt(hour, minute, 0);
+}
+
+// Reads an offset such as "20 minutes", "1h 30m", "an hour and 15 minutes" or "2 weeks"
+fn parse_offset(tokens: &[&str]) -> Option<Duration> {
+    // Split "1h30m" into "1", "h", "30", "m", so compact and spaced forms read the same
+    let mut words: Vec<String> = Vec::new();
+    for token in tokens {
+        let mut chars = token.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut word = c.to_string();
+            while let Some(&next) = chars.peek() {
+                if next.is_ascii_digit() != c.is_ascii_digit() {
+                    break;
+                }
+                word.push(next);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+
+    let mut total = Duration::zero();
+    let mut words = words.iter().filter(|word| word.as_str() != "and");
+    let mut seen = false;
+    while let Some(count) = words.next() {
+        let count = match count.as_str() {
+            "a" | "an" => 1,
+            count => count.parse::<i64>().ok()?,
+        };
+        let minutes = match words.next()?.as_str() {
+            "m" | "min" | "mins" | "minute" | "minutes" => 1,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+            "d" | "day" | "days" => 24 * 60,
+            "w" | "wk" | "wks" | "week" | "weeks" => 7 * 24 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(&Duration::try_minutes(count.checked_mul(minutes)?)?)?;
+        seen = true;
+    }
+
+    if !seen {
+        return None;
+    }
+    return Some(total);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timespec::{describe_relative, parse_duration, resolve, resolve_zoned};
+    use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+    // Wednesday
+    fn now() -> NaiveDateTime {
+        return at(2026, 10, 14, 10, 5);
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        return NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            resolve("2026-11-02 09:30", now()),
+            Ok(at(2026, 11, 2, 9, 30))
+        );
+        assert_eq!(
+            resolve("2026-11-02T09:30:45", now()),
+            Ok(at(2026, 11, 2, 9, 30))
+        );
+        assert!(resolve("2026-11-02T09:30:00+01:00", now()).is_ok());
+        assert_eq!(resolve("2026-11-02 9pm", now()), Ok(at(2026, 11, 2, 21, 0)));
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(resolve("now", now()), Ok(now()));
+        assert_eq!(
+            resolve("in 20 minutes", now()),
+            Ok(at(2026, 10, 14, 10, 25))
+        );
+        assert_eq!(resolve("in an hour", now()), Ok(at(2026, 10, 14, 11, 5)));
+        assert_eq!(resolve("in 1h30m", now()), Ok(at(2026, 10, 14, 11, 35)));
+        assert_eq!(
+            resolve("In 2 days and 3 hours", now()),
+            Ok(at(2026, 10, 16, 13, 5))
+        );
+        assert_eq!(resolve("in 1 week", now()), Ok(at(2026, 10, 21, 10, 5)));
+        assert!(resolve("in 20", now()).is_err());
+        assert!(resolve("in 20 parsecs", now()).is_err());
+        assert!(resolve("in 9999999999999 weeks", now()).is_err());
+        assert!(resolve("in 99999999 weeks", now()).is_err());
+        assert!(parse_duration("100000000000000m 100000000000000m").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("10m"), Ok(Duration::minutes(10)));
+        assert_eq!(parse_duration("1h 30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("2 Hours"), Ok(Duration::hours(2)));
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn days_and_times() {
+        assert_eq!(resolve("tomorrow 9am", now()), Ok(at(2026, 10, 15, 9, 0)));
+        assert_eq!(resolve("tomorrow", now()), Ok(at(2026, 10, 15, 9, 0)));
+        assert_eq!(
+            resolve("today at 5:45 pm", now()),
+            Ok(at(2026, 10, 14, 17, 45))
+        );
+        assert_eq!(resolve("12am", now()), Ok(at(2026, 10, 15, 0, 0)));
+        assert_eq!(resolve("noon", now()), Ok(at(2026, 10, 14, 12, 0)));
+        assert_eq!(resolve("9:00", now()), Ok(at(2026, 10, 15, 9, 0)));
+        assert!(resolve("13pm", now()).is_err());
+        assert!(resolve("tomorrow at", now()).is_err());
+        assert!(resolve("whenever", now()).is_err());
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(resolve("friday 14:00", now()), Ok(at(2026, 10, 16, 14, 0)));
+        assert_eq!(
+            resolve("next friday 14:00", now()),
+            Ok(at(2026, 10, 16, 14, 0))
+        );
+        assert_eq!(resolve("monday", now()), Ok(at(2026, 10, 19, 9, 0)));
+        // Today, while the time is still ahead, unless "next" is given
+        assert_eq!(resolve("wed 11am", now()), Ok(at(2026, 10, 14, 11, 0)));
+        assert_eq!(resolve("wed 8am", now()), Ok(at(2026, 10, 21, 8, 0)));
+        assert_eq!(
+            resolve("next wednesday 11am", now()),
+            Ok(at(2026, 10, 21, 11, 0))
+        );
+    }
+
+    #[test]
+    fn relative_descriptions() {
+        assert_eq!(
+            describe_relative(now(), at(2026, 10, 14, 13, 15)),
+            "in 3h 10m"
+        );
+        assert_eq!(
+            describe_relative(now(), at(2026, 10, 16, 14, 5)),
+            "in 2d 4h"
+        );
+        assert_eq!(describe_relative(now(), at(2026, 10, 15, 10, 5)), "in 1d");
+        assert_eq!(describe_relative(now(), at(2026, 10, 14, 10, 0)), "5m ago");
+        assert_eq!(describe_relative(now(), now()), "now");
+    }
+
+    #[test]
+    fn zones() {
+        // 14:05 in UTC is 10:05 in New York, and 23:05 in Tokyo
+        let now = Utc.from_utc_datetime(&at(2026, 10, 14, 14, 5));
+        let (time, zone) = resolve_zoned("9:00 America/New_York", now).unwrap();
+        assert_eq!(time, at(2026, 10, 15, 9, 0));
+        assert_eq!(zone.unwrap().name(), "America/New_York");
+
+        let (time, zone) = resolve_zoned("tomorrow 8am asia/tokyo", now).unwrap();
+        assert_eq!(time, at(2026, 10, 15, 8, 0));
+        assert_eq!(zone.unwrap().name(), "Asia/Tokyo");
+
+        assert_eq!(resolve_zoned("now", now).unwrap().1, None);
+        assert!(resolve_zoned("9:00 Mars/Olympus_Mons", now).is_err());
+    }
+}
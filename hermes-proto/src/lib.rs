@@ -0,0 +1,12 @@
+// Explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+/*
+* Shared protocol definitions for Hermes and Caduceus. Anything that crosses the socket
+* between the daemon and a client is defined here, so both sides encode it the same way.
+*/
+
+pub mod protocol;
+pub mod recurrence;
+pub mod reminder;
+pub mod zone;
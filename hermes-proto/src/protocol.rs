@@ -0,0 +1,620 @@
+use crate::reminder::{DecodeError, Reminder};
+use chrono::{DateTime, TimeZone, Utc};
+use std::convert::TryFrom;
+use std::fmt;
+
+/*
+* Envelope for every message exchanged between Hermes and a client. Each message is a
+* ZeroMQ multipart message laid out as:
+*
+*   frame 0: the magic bytes "HERMES"
+*   frame 1: header, version (1 byte), request id (4 bytes), command or status (1 byte)
+*   frame 2..: payload, specific to the command or response
+*
+* Every request receives exactly one response, carrying the same request id. Failures are
+* reported with a non-zero status, and a single payload frame describing the error.
+*/
+
+pub const MAGIC: &[u8] = b"HERMES";
+pub const PROTOCOL_VERSION: u8 = 6;
+
+const HEADER_LENGTH_BYTES: usize = 6;
+// Before versioning, the header frame only carried a one byte command
+const LEGACY_HEADER_LENGTH_BYTES: usize = 1;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Command {
+    Add,
+    List,
+    Delete,
+    Update,
+    Snooze,
+    Ack,
+    DoNotDisturb,
+    Pause,
+    Resume,
+    Agenda,
+    Status,
+}
+
+impl Command {
+    pub fn from_code(code: u8) -> Option<Command> {
+        return match code {
+            1 => Some(Command::Add),
+            2 => Some(Command::List),
+            3 => Some(Command::Delete),
+            4 => Some(Command::Update),
+            5 => Some(Command::Snooze),
+            6 => Some(Command::Ack),
+            7 => Some(Command::DoNotDisturb),
+            8 => Some(Command::Pause),
+            9 => Some(Command::Resume),
+            10 => Some(Command::Agenda),
+            11 => Some(Command::Status),
+            _ => None,
+        };
+    }
+
+    pub fn code(self) -> u8 {
+        return match self {
+            Command::Add => 1,
+            Command::List => 2,
+            Command::Delete => 3,
+            Command::Update => 4,
+            Command::Snooze => 5,
+            Command::Ack => 6,
+            Command::DoNotDisturb => 7,
+            Command::Pause => 8,
+            Command::Resume => 9,
+            Command::Agenda => 10,
+            Command::Status => 11,
+        };
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Status {
+    Ok,
+    UnsupportedVersion,
+    UnknownCommand,
+    MalformedRequest,
+    InvalidReminder,
+    NotFound,
+    StorageFailure,
+}
+
+impl Status {
+    pub fn from_code(code: u8) -> Option<Status> {
+        return match code {
+            0 => Some(Status::Ok),
+            1 => Some(Status::UnsupportedVersion),
+            2 => Some(Status::UnknownCommand),
+            3 => Some(Status::MalformedRequest),
+            4 => Some(Status::InvalidReminder),
+            5 => Some(Status::NotFound),
+            6 => Some(Status::StorageFailure),
+            _ => None,
+        };
+    }
+
+    pub fn code(self) -> u8 {
+        return match self {
+            Status::Ok => 0,
+            Status::UnsupportedVersion => 1,
+            Status::UnknownCommand => 2,
+            Status::MalformedRequest => 3,
+            Status::InvalidReminder => 4,
+            Status::NotFound => 5,
+            Status::StorageFailure => 6,
+        };
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Ok => "success",
+            Status::UnsupportedVersion => "unsupported protocol version",
+            Status::UnknownCommand => "unknown command",
+            Status::MalformedRequest => "malformed request",
+            Status::InvalidReminder => "invalid reminder",
+            Status::NotFound => "no such reminder",
+            Status::StorageFailure => "database failure",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum FrameError {
+    MissingFrames(usize),
+    InvalidMagic,
+    InvalidHeader(usize),
+    UnsupportedVersion { version: u8, request_id: u32 },
+    UnknownCommand { code: u8, request_id: u32 },
+    UnknownStatus { code: u8, request_id: u32 },
+}
+
+impl FrameError {
+    // Request id to answer with, 0 when the header could not be read
+    pub fn request_id(&self) -> u32 {
+        return match self {
+            FrameError::UnsupportedVersion { request_id, .. }
+            | FrameError::UnknownCommand { request_id, .. }
+            | FrameError::UnknownStatus { request_id, .. } => *request_id,
+            _ => 0,
+        };
+    }
+
+    pub fn status(&self) -> Status {
+        return match self {
+            FrameError::UnsupportedVersion { .. } => Status::UnsupportedVersion,
+            FrameError::UnknownCommand { .. } => Status::UnknownCommand,
+            _ => Status::MalformedRequest,
+        };
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::MissingFrames(count) => {
+                write!(f, "Received Message of invalid part count: {}", count)
+            }
+            FrameError::InvalidMagic => write!(f, "Invalid Header, expected: HERMES"),
+            FrameError::InvalidHeader(len) => write!(f, "Invalid header length: {}", len),
+            FrameError::UnsupportedVersion { version, .. } => write!(
+                f,
+                "Protocol version {} is not supported, expected version {}",
+                version, PROTOCOL_VERSION
+            ),
+            FrameError::UnknownCommand { code, .. } => {
+                write!(f, "Invalid Command code received: {}", code)
+            }
+            FrameError::UnknownStatus { code, .. } => {
+                write!(f, "Invalid Status code received: {}", code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+#[derive(PartialEq, Debug)]
+pub struct Request {
+    pub request_id: u32,
+    pub command: Command,
+    pub payload: Vec<Vec<u8>>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Response {
+    pub request_id: u32,
+    pub status: Status,
+    pub payload: Vec<Vec<u8>>,
+}
+
+// Splits off magic and header, returning (request id, code, payload)
+fn split_frames(frames: &[Vec<u8>]) -> Result<(u32, u8, &[Vec<u8>]), FrameError> {
+    if frames.len() < 2 {
+        return Err(FrameError::MissingFrames(frames.len()));
+    }
+    if frames[0] != MAGIC {
+        return Err(FrameError::InvalidMagic);
+    }
+
+    let header = &frames[1];
+    if header.len() == LEGACY_HEADER_LENGTH_BYTES {
+        return Err(FrameError::UnsupportedVersion {
+            version: 1,
+            request_id: 0,
+        });
+    }
+    if header.len() != HEADER_LENGTH_BYTES {
+        return Err(FrameError::InvalidHeader(header.len()));
+    }
+
+    let version = header[0];
+    let request_id = decode_u32(&header[1..5]).unwrap();
+    if version != PROTOCOL_VERSION {
+        return Err(FrameError::UnsupportedVersion {
+            version,
+            request_id,
+        });
+    }
+    return Ok((request_id, header[5], &frames[2..]));
+}
+
+fn join_frames(request_id: u32, code: u8, payload: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut header = vec![PROTOCOL_VERSION];
+    header.extend_from_slice(&request_id.to_be_bytes());
+    header.push(code);
+
+    let mut frames = vec![MAGIC.to_vec(), header];
+    frames.extend(payload.iter().cloned());
+    return frames;
+}
+
+impl Request {
+    pub fn new(request_id: u32, command: Command, payload: Vec<Vec<u8>>) -> Self {
+        return Request {
+            request_id,
+            command,
+            payload,
+        };
+    }
+
+    pub fn from_frames(frames: &[Vec<u8>]) -> Result<Request, FrameError> {
+        let (request_id, code, payload) = split_frames(frames)?;
+        let command =
+            Command::from_code(code).ok_or(FrameError::UnknownCommand { code, request_id })?;
+        return Ok(Request::new(request_id, command, payload.to_vec()));
+    }
+
+    pub fn to_frames(&self) -> Vec<Vec<u8>> {
+        return join_frames(self.request_id, self.command.code(), &self.payload);
+    }
+}
+
+impl Response {
+    pub fn ok(request_id: u32, payload: Vec<Vec<u8>>) -> Self {
+        return Response {
+            request_id,
+            status: Status::Ok,
+            payload,
+        };
+    }
+
+    pub fn error(request_id: u32, status: Status, detail: &str) -> Self {
+        return Response {
+            request_id,
+            status,
+            payload: vec![detail.as_bytes().to_vec()],
+        };
+    }
+
+    pub fn from_frames(frames: &[Vec<u8>]) -> Result<Response, FrameError> {
+        let (request_id, code, payload) = split_frames(frames)?;
+        let status =
+            Status::from_code(code).ok_or(FrameError::UnknownStatus { code, request_id })?;
+        return Ok(Response {
+            request_id,
+            status,
+            payload: payload.to_vec(),
+        });
+    }
+
+    pub fn to_frames(&self) -> Vec<Vec<u8>> {
+        return join_frames(self.request_id, self.status.code(), &self.payload);
+    }
+
+    // Human readable description of a failed response
+    pub fn detail(&self) -> String {
+        return match self.payload.first() {
+            Some(frame) if self.status != Status::Ok => String::from_utf8_lossy(frame).into_owned(),
+            _ => self.status.to_string(),
+        };
+    }
+}
+
+pub fn encode_u32(value: u32) -> Vec<u8> {
+    return value.to_be_bytes().to_vec();
+}
+
+pub fn decode_u32(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() != 4 {
+        return None;
+    }
+    return Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+}
+
+// An instant is sent as a Unix timestamp, 8 bytes
+pub fn encode_time(time: DateTime<Utc>) -> Vec<u8> {
+    return time.timestamp().to_be_bytes().to_vec();
+}
+
+pub fn decode_time(bytes: &[u8]) -> Option<DateTime<Utc>> {
+    let seconds = i64::from_be_bytes(<[u8; 8]>::try_from(bytes).ok()?);
+    return Utc.timestamp_opt(seconds, 0).single();
+}
+
+// Chooses which reminders a delete applies to, each sent as a kind byte followed by its value
+#[derive(PartialEq, Debug, Clone)]
+pub enum Selector {
+    Id(u32),
+    // ONCE reminders whose time has already passed
+    OnceExpired,
+    // Reminders whose message contains the text, ignoring case
+    Matching(String),
+}
+
+impl Selector {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut vec = Vec::new();
+        match self {
+            Selector::Id(id) => {
+                vec.push(1);
+                vec.extend(encode_u32(*id));
+            }
+            Selector::OnceExpired => vec.push(2),
+            Selector::Matching(text) => {
+                vec.push(3);
+                vec.extend_from_slice(text.as_bytes());
+            }
+        }
+        return vec;
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Option<Selector> {
+        let (kind, value) = bytes.split_first()?;
+        return match kind {
+            1 => Some(Selector::Id(decode_u32(value)?)),
+            2 if value.is_empty() => Some(Selector::OnceExpired),
+            3 => Some(Selector::Matching(String::from_utf8(value.to_vec()).ok()?)),
+            _ => None,
+        };
+    }
+}
+
+/*
+* Whether Hermes holds back notifications outside of its quiet hours, sent as a kind byte followed
+* by its value. A DoNotDisturb request carries one to set, or nothing to only ask, and is answered
+* with the one in effect.
+*/
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DoNotDisturb {
+    Off,
+    // Until turned off
+    On,
+    Until(DateTime<Utc>),
+}
+
+impl DoNotDisturb {
+    pub fn serialize(&self) -> Vec<u8> {
+        return match self {
+            DoNotDisturb::Off => vec![0],
+            DoNotDisturb::On => vec![1],
+            DoNotDisturb::Until(until) => {
+                let mut vec = vec![2];
+                vec.extend(encode_time(*until));
+                vec
+            }
+        };
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Option<DoNotDisturb> {
+        let (kind, value) = bytes.split_first()?;
+        return match kind {
+            0 if value.is_empty() => Some(DoNotDisturb::Off),
+            1 if value.is_empty() => Some(DoNotDisturb::On),
+            2 => Some(DoNotDisturb::Until(decode_time(value)?)),
+            _ => None,
+        };
+    }
+}
+
+/*
+* How notifications are getting through, as answered to Status: the number waiting to be sent
+* again (4 bytes), then, while Hermes can't reach the desktop, when it lost it as a Unix timestamp
+* (8 bytes) and why, as UTF-8.
+*/
+#[derive(PartialEq, Debug, Clone)]
+pub struct DeliveryStatus {
+    pub queued: u32,
+    pub degraded: Option<(DateTime<Utc>, String)>,
+}
+
+impl DeliveryStatus {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut vec = encode_u32(self.queued);
+        if let Some((since, reason)) = &self.degraded {
+            vec.extend(encode_time(*since));
+            vec.extend(reason.as_bytes());
+        }
+        return vec;
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Option<DeliveryStatus> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let (queued, rest) = bytes.split_at(4);
+        let degraded = match rest.len() {
+            0 => None,
+            1..=7 => return None,
+            _ => {
+                let (since, reason) = rest.split_at(8);
+                let reason = String::from_utf8(reason.to_vec()).ok()?;
+                Some((decode_time(since)?, reason))
+            }
+        };
+        return Some(DeliveryStatus {
+            queued: decode_u32(queued)?,
+            degraded,
+        });
+    }
+}
+
+// Outcome for one reminder of a request affecting several, as its id followed by a status byte
+pub fn serialize_result(id: u32, status: Status) -> Vec<u8> {
+    let mut vec = encode_u32(id);
+    vec.push(status.code());
+    return vec;
+}
+
+pub fn deserialize_result(bytes: &[u8]) -> Option<(u32, Status)> {
+    if bytes.len() != 5 {
+        return None;
+    }
+    return Some((decode_u32(&bytes[..4])?, Status::from_code(bytes[4])?));
+}
+
+// A listed reminder is sent as its id followed by the reminder itself
+pub fn serialize_entry(id: u32, reminder: &Reminder) -> Vec<u8> {
+    let mut vec = encode_u32(id);
+    vec.extend(reminder.serialize());
+    return vec;
+}
+
+pub fn deserialize_entry(bytes: &[u8]) -> Result<(u32, Reminder), DecodeError> {
+    if bytes.len() < 4 {
+        return Err(DecodeError::InvalidLength(bytes.len()));
+    }
+    let (id, reminder) = bytes.split_at(4);
+    let id = decode_u32(id).unwrap();
+    return Ok((id, Reminder::deserialize_reminder(reminder)?));
+}
+
+/*
+* An occurrence in an agenda is sent as the instant it is due, followed by the id and reminder it
+* belongs to as for a listed one
+*/
+pub fn serialize_occurrence(at: DateTime<Utc>, id: u32, reminder: &Reminder) -> Vec<u8> {
+    let mut vec = encode_time(at);
+    vec.extend(serialize_entry(id, reminder));
+    return vec;
+}
+
+pub fn deserialize_occurrence(bytes: &[u8]) -> Result<(DateTime<Utc>, u32, Reminder), DecodeError> {
+    if bytes.len() < 8 {
+        return Err(DecodeError::InvalidLength(bytes.len()));
+    }
+    let (at, entry) = bytes.split_at(8);
+    let at = decode_time(at).ok_or(DecodeError::InvalidLength(bytes.len()))?;
+    let (id, reminder) = deserialize_entry(entry)?;
+    return Ok((at, id, reminder));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::*;
+    use crate::reminder::{Frequency, Reminder};
+
+    #[test]
+    fn request_round_trip() {
+        let reminder = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "Hi".to_string());
+        let request = Request::new(77, Command::Add, vec![reminder.serialize()]);
+        let frames = request.to_frames();
+        assert_eq!(frames[0], b"HERMES");
+        assert_eq!(frames[1], vec![PROTOCOL_VERSION, 0, 0, 0, 77, 1]);
+        assert_eq!(Request::from_frames(&frames), Ok(request));
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let response = Response::error(9, Status::NotFound, "No reminder with id 4");
+        let parsed = Response::from_frames(&response.to_frames()).unwrap();
+        assert_eq!(parsed.status, Status::NotFound);
+        assert_eq!(parsed.request_id, 9);
+        assert_eq!(parsed.detail(), "No reminder with id 4");
+    }
+
+    #[test]
+    fn legacy_request_is_unsupported() {
+        let frames = vec![b"HERMES".to_vec(), vec![2]];
+        let err = Request::from_frames(&frames).unwrap_err();
+        assert_eq!(err.status(), Status::UnsupportedVersion);
+    }
+
+    #[test]
+    fn future_version_keeps_request_id() {
+        let frames = vec![
+            b"HERMES".to_vec(),
+            vec![PROTOCOL_VERSION + 1, 0, 0, 1, 0, 2],
+        ];
+        let err = Request::from_frames(&frames).unwrap_err();
+        assert_eq!(err.status(), Status::UnsupportedVersion);
+        assert_eq!(err.request_id(), 256);
+    }
+
+    #[test]
+    fn unknown_command_rejected() {
+        let frames = vec![b"HERMES".to_vec(), vec![PROTOCOL_VERSION, 0, 0, 0, 5, 200]];
+        let err = Request::from_frames(&frames).unwrap_err();
+        assert_eq!(err.status(), Status::UnknownCommand);
+        assert_eq!(err.request_id(), 5);
+    }
+
+    #[test]
+    fn bad_magic_rejected() {
+        let frames = vec![b"HERMIT".to_vec(), vec![PROTOCOL_VERSION, 0, 0, 0, 1, 2]];
+        assert_eq!(Request::from_frames(&frames), Err(FrameError::InvalidMagic));
+    }
+
+    #[test]
+    fn do_not_disturb_round_trip() {
+        let until = Utc.with_ymd_and_hms(2030, 1, 2, 3, 4, 0).unwrap();
+        for dnd in [
+            DoNotDisturb::Off,
+            DoNotDisturb::On,
+            DoNotDisturb::Until(until),
+        ] {
+            assert_eq!(DoNotDisturb::deserialize(&dnd.serialize()), Some(dnd));
+        }
+        assert_eq!(DoNotDisturb::deserialize(&[2, 0, 0]), None);
+        assert_eq!(DoNotDisturb::deserialize(&[]), None);
+    }
+
+    #[test]
+    fn delivery_status_round_trip() {
+        let since = Utc.timestamp_opt(1_900_000_000, 0).unwrap();
+        for status in [
+            DeliveryStatus {
+                queued: 0,
+                degraded: None,
+            },
+            DeliveryStatus {
+                queued: 3,
+                degraded: Some((since, "Connection refused".to_string())),
+            },
+        ] {
+            assert_eq!(
+                DeliveryStatus::deserialize(&status.serialize()),
+                Some(status)
+            );
+        }
+        assert_eq!(DeliveryStatus::deserialize(&[0, 0, 1]), None);
+        assert_eq!(DeliveryStatus::deserialize(&[0, 0, 0, 1, 0, 0]), None);
+    }
+
+    #[test]
+    fn selector_round_trip() {
+        let selectors = [
+            Selector::Id(7),
+            Selector::OnceExpired,
+            Selector::Matching("Dentist".to_string()),
+        ];
+        for selector in selectors.iter() {
+            assert_eq!(
+                Selector::deserialize(&selector.serialize()).as_ref(),
+                Some(selector)
+            );
+        }
+        assert_eq!(Selector::deserialize(&[1, 0, 0]), None);
+        assert_eq!(Selector::deserialize(&[2, 0]), None);
+        assert_eq!(Selector::deserialize(&[]), None);
+    }
+
+    #[test]
+    fn result_round_trip() {
+        let bytes = serialize_result(12, Status::NotFound);
+        assert_eq!(deserialize_result(&bytes), Some((12, Status::NotFound)));
+        assert_eq!(deserialize_result(&bytes[..4]), None);
+    }
+
+    #[test]
+    fn entry_round_trip() {
+        let reminder = Reminder::new(Frequency::NDAYS, 5, 6, 2027, 7, 8, Some(3), "x".to_string());
+        let bytes = serialize_entry(42, &reminder);
+        assert_eq!(deserialize_entry(&bytes), Ok((42, reminder)));
+    }
+
+    #[test]
+    fn occurrence_round_trip() {
+        let reminder = Reminder::new(Frequency::DAILY, 5, 6, 2027, 7, 8, None, "x".to_string());
+        let at = Utc.with_ymd_and_hms(2027, 5, 7, 7, 8, 0).unwrap();
+        let bytes = serialize_occurrence(at, 3, &reminder);
+        assert_eq!(deserialize_occurrence(&bytes), Ok((at, 3, reminder)));
+        assert!(deserialize_occurrence(&bytes[..6]).is_err());
+    }
+}
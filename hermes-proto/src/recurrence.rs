@@ -0,0 +1,671 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
+/*
+* Recurrence rules, written as a subset of the RRULE format of RFC 5545, like
+* "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR" or "FREQ=MONTHLY;BYDAY=1MO". The supported parts are
+* FREQ (HOURLY, DAILY, WEEKLY, MONTHLY or YEARLY), INTERVAL, BYMONTH, BYMONTHDAY, BYDAY,
+* BYHOUR and BYMINUTE.
+*
+* Occurrences are counted from a start, the first time of the reminder. Anything a rule
+* leaves out is taken from the start, so "FREQ=MONTHLY" repeats on the start's day of the
+* month, at the start's time. Like RFC 5545, a month without that day is skipped.
+*/
+
+// The calendar, weekdays and leap years included, repeats every 400 years
+const CYCLE_YEARS: i64 = 400;
+const CYCLE_DAYS: i64 = 146097;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum RuleFrequency {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Rule {
+    pub frequency: RuleFrequency,
+    pub interval: u32,
+    pub by_month: Vec<u32>,
+    // Negative days count back from the end of the month, -1 is the last day
+    pub by_month_day: Vec<i32>,
+    // Weekdays, with an ordinal within the month (or year) such as 1 for the first, -1 for the last
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_hour: Vec<u32>,
+    pub by_minute: Vec<u32>,
+}
+
+const WEEKDAYS: &[(&str, Weekday)] = &[
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+fn parse_list<T: FromStr>(
+    name: &str,
+    value: &str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, String> {
+    return value
+        .split(',')
+        .map(|item| match item.trim().parse::<T>() {
+            Ok(parsed) if valid(&parsed) => Ok(parsed),
+            _ => Err(format!("Invalid value \"{}\" in {}", item, name)),
+        })
+        .collect();
+}
+
+fn parse_weekday(item: &str) -> Result<(Option<i32>, Weekday), String> {
+    let item = item.trim();
+    let invalid = || format!("Invalid value \"{}\" in BYDAY", item);
+    if item.len() < 2 || !item.is_char_boundary(item.len() - 2) {
+        return Err(invalid());
+    }
+    let (ordinal, day) = item.split_at(item.len() - 2);
+    let day = WEEKDAYS
+        .iter()
+        .find(|(name, _)| *name == day)
+        .map(|(_, weekday)| *weekday)
+        .ok_or_else(invalid)?;
+    if ordinal.is_empty() {
+        return Ok((None, day));
+    }
+    return match ordinal.parse::<i32>() {
+        Ok(ordinal) if ordinal != 0 && ordinal.abs() <= 53 => Ok((Some(ordinal), day)),
+        _ => Err(invalid()),
+    };
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Rule, String> {
+        let upper = input.trim().to_uppercase();
+        let text = upper.strip_prefix("RRULE:").unwrap_or(&upper);
+
+        let mut frequency = None;
+        let mut rule = Rule {
+            frequency: RuleFrequency::Daily,
+            interval: 1,
+            by_month: Vec::new(),
+            by_month_day: Vec::new(),
+            by_day: Vec::new(),
+            by_hour: Vec::new(),
+            by_minute: Vec::new(),
+        };
+        for part in text.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected NAME=VALUE, found \"{}\"", part))?;
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "HOURLY" => RuleFrequency::Hourly,
+                        "DAILY" => RuleFrequency::Daily,
+                        "WEEKLY" => RuleFrequency::Weekly,
+                        "MONTHLY" => RuleFrequency::Monthly,
+                        "YEARLY" => RuleFrequency::Yearly,
+                        _ => return Err(format!("Unsupported FREQ \"{}\"", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = match value.parse::<u32>() {
+                        Ok(interval) if interval > 0 => interval,
+                        _ => return Err(format!("Invalid INTERVAL \"{}\"", value)),
+                    }
+                }
+                "BYMONTH" => rule.by_month = parse_list(name, value, |m| (1..=12).contains(m))?,
+                "BYMONTHDAY" => {
+                    rule.by_month_day = parse_list(name, value, |d: &i32| *d != 0 && d.abs() <= 31)?
+                }
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYHOUR" => rule.by_hour = parse_list(name, value, |h| *h < 24)?,
+                "BYMINUTE" => rule.by_minute = parse_list(name, value, |m| *m < 60)?,
+                "WKST" if value == "MO" => {} // Weeks always start on Monday
+                _ => return Err(format!("Unsupported rule part \"{}\"", name)),
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(|| "A rule needs a FREQ".to_string())?;
+        let ordinals = rule.by_day.iter().any(|(ordinal, _)| ordinal.is_some());
+        if ordinals
+            && rule.frequency != RuleFrequency::Monthly
+            && rule.frequency != RuleFrequency::Yearly
+        {
+            return Err("Numbered weekdays like 1MO need FREQ=MONTHLY or YEARLY".to_string());
+        }
+        return Ok(rule);
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, name: &str, values: &[T]) -> fmt::Result {
+    if values.is_empty() {
+        return Ok(());
+    }
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    write!(f, ";{}={}", name, values.join(","))
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frequency = match self.frequency {
+            RuleFrequency::Hourly => "HOURLY",
+            RuleFrequency::Daily => "DAILY",
+            RuleFrequency::Weekly => "WEEKLY",
+            RuleFrequency::Monthly => "MONTHLY",
+            RuleFrequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        write_list(f, "BYMONTH", &self.by_month)?;
+        write_list(f, "BYMONTHDAY", &self.by_month_day)?;
+        let days: Vec<String> = self
+            .by_day
+            .iter()
+            .map(|(ordinal, day)| {
+                let name = WEEKDAYS
+                    .iter()
+                    .find(|(_, weekday)| weekday == day)
+                    .unwrap()
+                    .0;
+                match ordinal {
+                    Some(ordinal) => format!("{}{}", ordinal, name),
+                    None => name.to_string(),
+                }
+            })
+            .collect();
+        write_list(f, "BYDAY", &days)?;
+        write_list(f, "BYHOUR", &self.by_hour)?;
+        write_list(f, "BYMINUTE", &self.by_minute)
+    }
+}
+
+pub(crate) fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    return NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .unwrap()
+        .pred_opt()
+        .unwrap()
+        .day();
+}
+
+fn monday_of(date: NaiveDate) -> NaiveDate {
+    return date - Duration::days(date.weekday().num_days_from_monday() as i64);
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+fn months_since_zero(date: NaiveDate) -> i64 {
+    return date.year() as i64 * 12 + date.month0() as i64;
+}
+
+impl Rule {
+    /*
+     * The first occurrence at or after `start` and strictly after `after`, if there is one. A rule
+     * without one in a whole turn of the calendar, counted in periods of its frequency, has none.
+     */
+    pub fn next_after(&self, start: NaiveDateTime, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let interval = self.interval as i64;
+        let first = self.period_index(start, after).max(0);
+        let mut index = first - first % interval;
+
+        let last = index.checked_add(self.cycle_periods().checked_mul(interval)?)?;
+        while index <= last {
+            let period = self.period_start(start, index)?;
+            // Hours of a day the rule leaves out are passed over all at once
+            if self.frequency == RuleFrequency::Hourly && !self.day_allowed(period.date()) {
+                let next_day = period.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                let skipped = self.period_index(start, next_day);
+                index = skipped + (interval - skipped % interval) % interval;
+                continue;
+            }
+            let found = self
+                .occurrences_in(start, index)
+                .into_iter()
+                .find(|occurrence| *occurrence >= start && *occurrence > after);
+            if found.is_some() {
+                return found;
+            }
+            index += interval;
+        }
+        return None;
+    }
+
+    // Periods of the rule's frequency in one turn of the calendar
+    fn cycle_periods(&self) -> i64 {
+        return match self.frequency {
+            RuleFrequency::Yearly => CYCLE_YEARS,
+            RuleFrequency::Monthly => CYCLE_YEARS * 12,
+            RuleFrequency::Weekly => CYCLE_DAYS / 7,
+            RuleFrequency::Daily => CYCLE_DAYS,
+            RuleFrequency::Hourly => CYCLE_DAYS * 24,
+        };
+    }
+
+    // Why the rule can't be used, for parts that parse but mean nothing together
+    pub fn unsupported(&self) -> Option<&'static str> {
+        if self.frequency == RuleFrequency::Weekly && !self.by_month_day.is_empty() {
+            return Some("BYMONTHDAY can't be used with FREQ=WEEKLY");
+        }
+        return None;
+    }
+
+    /*
+     * Whether the rule plainly never occurs from `start`, with days of the month no month it allows
+     * has, or hours or weekdays its interval never comes to, so that isn't searched for
+     */
+    pub fn never_occurs(&self, start: NaiveDateTime) -> bool {
+        // February may have 29 days
+        let longest = |month: u32| days_in_month(2000, month) as i32;
+        let months: Vec<u32> = if self.by_month.is_empty() {
+            (1..=12).collect()
+        } else {
+            self.by_month.clone()
+        };
+        let has_day = months.iter().any(|month| {
+            self.by_month_day
+                .iter()
+                .any(|day| day.abs() <= longest(*month))
+        });
+        if !self.by_month_day.is_empty() && !has_day {
+            return true;
+        }
+
+        let interval = self.interval as i64;
+        return match self.frequency {
+            RuleFrequency::Hourly if !self.by_hour.is_empty() => {
+                let step = gcd(interval, 24);
+                let reached = start.hour() as i64 % step;
+                !self
+                    .by_hour
+                    .iter()
+                    .any(|hour| *hour as i64 % step == reached)
+            }
+            RuleFrequency::Daily if interval % 7 == 0 && !self.by_day.is_empty() => {
+                !self.by_day.iter().any(|(_, day)| *day == start.weekday())
+            }
+            _ => false,
+        };
+    }
+
+    // Number of periods of the rule's frequency from the period holding `start` to the one holding `time`
+    fn period_index(&self, start: NaiveDateTime, time: NaiveDateTime) -> i64 {
+        return match self.frequency {
+            RuleFrequency::Yearly => (time.year() - start.year()) as i64,
+            RuleFrequency::Monthly => {
+                months_since_zero(time.date()) - months_since_zero(start.date())
+            }
+            RuleFrequency::Weekly => {
+                (monday_of(time.date()) - monday_of(start.date())).num_days() / 7
+            }
+            RuleFrequency::Daily => (time.date() - start.date()).num_days(),
+            RuleFrequency::Hourly => {
+                let hour_of = |t: NaiveDateTime| t.date().and_hms_opt(t.hour(), 0, 0).unwrap();
+                (hour_of(time) - hour_of(start)).num_hours()
+            }
+        };
+    }
+
+    fn period_start(&self, start: NaiveDateTime, index: i64) -> Option<NaiveDateTime> {
+        let date = start.date();
+        return match self.frequency {
+            RuleFrequency::Yearly => {
+                NaiveDate::from_ymd_opt(date.year().checked_add(index as i32)?, 1, 1)?
+                    .and_hms_opt(0, 0, 0)
+            }
+            RuleFrequency::Monthly => {
+                let months = months_since_zero(date) + index;
+                NaiveDate::from_ymd_opt((months / 12) as i32, (months % 12) as u32 + 1, 1)?
+                    .and_hms_opt(0, 0, 0)
+            }
+            RuleFrequency::Weekly => {
+                (monday_of(date) + Duration::try_weeks(index)?).and_hms_opt(0, 0, 0)
+            }
+            RuleFrequency::Daily => (date + Duration::try_days(index)?).and_hms_opt(0, 0, 0),
+            RuleFrequency::Hourly => {
+                Some(date.and_hms_opt(start.hour(), 0, 0)? + Duration::try_hours(index)?)
+            }
+        };
+    }
+
+    // Every occurrence within a period, in order, before comparing them with the start
+    fn occurrences_in(&self, start: NaiveDateTime, index: i64) -> Vec<NaiveDateTime> {
+        let period = match self.period_start(start, index) {
+            Some(period) => period,
+            None => return Vec::new(),
+        };
+        let (year, month) = (period.year(), period.month());
+
+        let dates: Vec<NaiveDate> = match self.frequency {
+            RuleFrequency::Yearly => self.dates_in_year(year, start.date()),
+            RuleFrequency::Monthly if self.month_allowed(month) => {
+                self.dates_in_month(year, month, start.date())
+            }
+            RuleFrequency::Monthly => Vec::new(),
+            RuleFrequency::Weekly => {
+                let days: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![start.weekday()]
+                } else {
+                    self.by_day.iter().map(|(_, day)| *day).collect()
+                };
+                (0..7)
+                    .map(|offset| period.date() + Duration::days(offset))
+                    .filter(|date| {
+                        days.contains(&date.weekday()) && self.month_allowed(date.month())
+                    })
+                    .collect()
+            }
+            RuleFrequency::Daily | RuleFrequency::Hourly => {
+                if self.day_allowed(period.date()) {
+                    vec![period.date()]
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+
+        let minutes: Vec<u32> = if self.by_minute.is_empty() {
+            vec![start.minute()]
+        } else {
+            self.by_minute.clone()
+        };
+        let hours: Vec<u32> = match self.frequency {
+            RuleFrequency::Hourly
+                if self.by_hour.is_empty() || self.by_hour.contains(&period.hour()) =>
+            {
+                vec![period.hour()]
+            }
+            RuleFrequency::Hourly => Vec::new(),
+            _ if self.by_hour.is_empty() => vec![start.hour()],
+            _ => self.by_hour.clone(),
+        };
+
+        let mut occurrences = Vec::new();
+        for date in dates.iter() {
+            for hour in hours.iter() {
+                for minute in minutes.iter() {
+                    if let Some(time) = NaiveTime::from_hms_opt(*hour, *minute, 0) {
+                        occurrences.push(date.and_time(time));
+                    }
+                }
+            }
+        }
+        occurrences.sort();
+        return occurrences;
+    }
+
+    fn month_allowed(&self, month: u32) -> bool {
+        return self.by_month.is_empty() || self.by_month.contains(&month);
+    }
+
+    // Filters for the daily and hourly frequencies, where every part narrows the days down
+    fn day_allowed(&self, date: NaiveDate) -> bool {
+        let length = days_in_month(date.year(), date.month());
+        return self.month_allowed(date.month())
+            && self.month_day_allowed(date)
+            && self.weekday_allowed(date, date.day(), length);
+    }
+
+    fn month_day_allowed(&self, date: NaiveDate) -> bool {
+        let length = days_in_month(date.year(), date.month()) as i32;
+        let day = date.day() as i32;
+        return self.by_month_day.is_empty()
+            || self
+                .by_month_day
+                .iter()
+                .any(|d| *d == day || *d == day - length - 1);
+    }
+
+    // Numbered weekdays count by the date's `position` among the `length` days of its month or year
+    fn weekday_allowed(&self, date: NaiveDate, position: u32, length: u32) -> bool {
+        let nth = ((position - 1) / 7) as i32 + 1;
+        let from_end = -(((length - position) / 7) as i32) - 1;
+        return self.by_day.is_empty()
+            || self.by_day.iter().any(|(ordinal, weekday)| {
+                *weekday == date.weekday() && ordinal.is_none_or(|o| o == nth || o == from_end)
+            });
+    }
+
+    fn dates_in_month(&self, year: i32, month: u32, start: NaiveDate) -> Vec<NaiveDate> {
+        if self.by_month_day.is_empty() && self.by_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, month, start.day())
+                .into_iter()
+                .collect();
+        }
+
+        let length = days_in_month(year, month);
+        return (1..=length)
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .filter(|date| {
+                self.month_day_allowed(*date) && self.weekday_allowed(*date, date.day(), length)
+            })
+            .collect();
+    }
+
+    fn dates_in_year(&self, year: i32, start: NaiveDate) -> Vec<NaiveDate> {
+        if !self.by_month.is_empty() || !self.by_month_day.is_empty() {
+            let months: Vec<u32> = if self.by_month.is_empty() {
+                (1..=12).collect()
+            } else {
+                self.by_month.clone()
+            };
+            let mut dates: Vec<NaiveDate> = months
+                .iter()
+                .flat_map(|month| self.dates_in_month(year, *month, start))
+                .collect();
+            dates.sort();
+            return dates;
+        }
+
+        if self.by_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                .into_iter()
+                .collect();
+        }
+
+        // Numbered weekdays without BYMONTH count within the whole year
+        let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+        let length = NaiveDate::from_ymd_opt(year, 12, 31).unwrap().ordinal();
+        return (0..length as i64)
+            .map(|offset| first + Duration::days(offset))
+            .filter(|date| self.weekday_allowed(*date, date.ordinal(), length))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::recurrence::Rule;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        return NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+    }
+
+    // The first `count` occurrences, starting from `start`
+    fn expand(rule: &str, start: NaiveDateTime, count: usize) -> Vec<NaiveDateTime> {
+        let rule: Rule = rule.parse().unwrap();
+        let mut occurrences = Vec::new();
+        let mut after = start - chrono::Duration::minutes(1);
+        while occurrences.len() < count {
+            after = rule.next_after(start, after).unwrap();
+            occurrences.push(after);
+        }
+        return occurrences;
+    }
+
+    #[test]
+    fn weekdays() {
+        // 2026-10-16 is a Friday
+        let start = at(2026, 10, 16, 9, 0);
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR", start, 3),
+            vec![start, at(2026, 10, 19, 9, 0), at(2026, 10, 20, 9, 0)]
+        );
+    }
+
+    #[test]
+    fn monthly_days() {
+        let start = at(2026, 10, 1, 8, 30);
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=1MO", start, 3),
+            vec![
+                at(2026, 10, 5, 8, 30),
+                at(2026, 11, 2, 8, 30),
+                at(2026, 12, 7, 8, 30)
+            ]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=15", start, 2),
+            vec![at(2026, 10, 15, 8, 30), at(2026, 11, 15, 8, 30)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=-1FR", start, 2),
+            vec![at(2026, 10, 30, 8, 30), at(2026, 11, 27, 8, 30)]
+        );
+        // Months without a 31st are skipped
+        assert_eq!(
+            expand("FREQ=MONTHLY", at(2027, 1, 31, 9, 0), 2),
+            vec![at(2027, 1, 31, 9, 0), at(2027, 3, 31, 9, 0)]
+        );
+    }
+
+    #[test]
+    fn yearly() {
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;BYMONTH=3;BYMONTHDAY=3",
+                at(2026, 10, 18, 7, 0),
+                2
+            ),
+            vec![at(2027, 3, 3, 7, 0), at(2028, 3, 3, 7, 0)]
+        );
+        assert_eq!(
+            expand("FREQ=YEARLY", at(2028, 2, 29, 7, 0), 2),
+            vec![at(2028, 2, 29, 7, 0), at(2032, 2, 29, 7, 0)]
+        );
+    }
+
+    #[test]
+    fn hours_within_a_day() {
+        let start = at(2026, 10, 19, 9, 0);
+        let expected = [
+            at(2026, 10, 19, 15, 0),
+            at(2026, 10, 19, 17, 0),
+            at(2026, 10, 20, 9, 0),
+        ];
+        let rule: Rule = "FREQ=HOURLY;INTERVAL=2;BYHOUR=9,10,11,12,13,14,15,16,17"
+            .parse()
+            .unwrap();
+        let mut after = at(2026, 10, 19, 14, 0);
+        for occurrence in expected.iter() {
+            after = rule.next_after(start, after).unwrap();
+            assert_eq!(after, *occurrence);
+        }
+        assert_eq!(
+            expand("FREQ=DAILY;BYHOUR=9,11,13,15,17;BYMINUTE=0", start, 6)[5],
+            at(2026, 10, 20, 9, 0)
+        );
+    }
+
+    #[test]
+    fn intervals_keep_to_the_start() {
+        let start = at(2026, 10, 19, 9, 0); // A Monday
+        let rule: Rule = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE".parse().unwrap();
+        // Asking from any later occurrence, or long after, stays on the same fortnights
+        assert_eq!(
+            rule.next_after(at(2026, 10, 21, 9, 0), at(2026, 10, 21, 9, 0)),
+            Some(at(2026, 11, 2, 9, 0))
+        );
+        assert_eq!(
+            rule.next_after(start, at(2027, 1, 1, 0, 0)),
+            Some(at(2027, 1, 11, 9, 0))
+        );
+    }
+
+    #[test]
+    fn impossible_rule_ends() {
+        let rule: Rule = "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30".parse().unwrap();
+        let start = at(2026, 1, 1, 0, 0);
+        assert_eq!(rule.next_after(start, start), None);
+        assert!(rule.never_occurs(start));
+
+        // 2026-10-19 is a Monday
+        let start = at(2026, 10, 19, 9, 0);
+        for never in [
+            "FREQ=HOURLY;BYMONTH=4,6;BYMONTHDAY=31,-31",
+            "FREQ=HOURLY;INTERVAL=2;BYHOUR=10,12",
+            "FREQ=DAILY;INTERVAL=14;BYDAY=TU",
+        ] {
+            assert!(
+                never.parse::<Rule>().unwrap().never_occurs(start),
+                "{}",
+                never
+            );
+        }
+        for occurs in [
+            "FREQ=HOURLY;BYMONTH=2;BYMONTHDAY=29",
+            "FREQ=HOURLY;INTERVAL=2;BYHOUR=10,11",
+            "FREQ=DAILY;INTERVAL=14;BYDAY=MO",
+        ] {
+            assert!(
+                !occurs.parse::<Rule>().unwrap().never_occurs(start),
+                "{}",
+                occurs
+            );
+        }
+    }
+
+    // Days an hourly rule leaves out are passed over a day at a time, to a leap day years away
+    #[test]
+    fn hourly_passes_over_days() {
+        let rule: Rule = "FREQ=HOURLY;INTERVAL=5;BYMONTH=2;BYMONTHDAY=29"
+            .parse()
+            .unwrap();
+        let start = at(2097, 3, 1, 9, 0);
+        assert_eq!(rule.next_after(start, start), Some(at(2104, 2, 29, 4, 0)));
+    }
+
+    #[test]
+    fn parsing() {
+        let rule: Rule = "rrule:freq=monthly;interval=2;byday=1mo,-1fr;byhour=9"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=MONTHLY;INTERVAL=2;BYDAY=1MO,-1FR;BYHOUR=9"
+        );
+        assert_eq!(rule.to_string().parse::<Rule>(), Ok(rule));
+
+        assert!("BYDAY=MO".parse::<Rule>().is_err());
+        assert!("FREQ=SECONDLY".parse::<Rule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=1MO".parse::<Rule>().is_err());
+        assert!("FREQ=DAILY;BYHOUR=24".parse::<Rule>().is_err());
+        assert!("FREQ=DAILY;COUNT=3".parse::<Rule>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=0".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,1659 @@
+use crate::recurrence::{self, Rule, RuleFrequency};
+use crate::zone::{self, Tz};
+use chrono::prelude::*;
+use chrono::Duration;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Frequency {
+    DAILY,
+    ONCE,
+    WEEKLY,
+    NDAYS,
+    // On the same day each month or year, or the last day of a month that is too short
+    MONTHLY,
+    YEARLY,
+    // Follows the reminder's recurrence rule
+    RULE,
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::DAILY => "Daily",
+            Self::ONCE => "Once",
+            Self::WEEKLY => "Weekly",
+            Self::NDAYS => "Every N Days",
+            Self::MONTHLY => "Monthly",
+            Self::YEARLY => "Yearly",
+            Self::RULE => "Custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// What Hermes sends for occurrences of a reminder that passed while it wasn't running
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum CatchUp {
+    // A single notification, however many were missed
+    Once,
+    // A notification for each missed occurrence
+    All,
+    // A single notification saying how many were missed
+    Summary,
+    // Nothing, waiting for the next occurrence instead
+    Skip,
+}
+
+impl fmt::Display for CatchUp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Once => "once",
+            Self::All => "all",
+            Self::Summary => "summary",
+            Self::Skip => "skip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<CatchUp, String> {
+        return match input.trim().to_lowercase().as_str() {
+            "once" => Ok(Self::Once),
+            "all" => Ok(Self::All),
+            "summary" => Ok(Self::Summary),
+            "skip" => Ok(Self::Skip),
+            _ => Err(format!(
+                "Invalid catch-up policy \"{}\", expected once, all, summary or skip",
+                input
+            )),
+        };
+    }
+}
+
+pub fn deserialize_catch_up(byte: u8) -> Option<CatchUp> {
+    return match byte {
+        1 => Some(CatchUp::Once),
+        2 => Some(CatchUp::All),
+        3 => Some(CatchUp::Summary),
+        4 => Some(CatchUp::Skip),
+        _ => None,
+    };
+}
+
+pub fn serialize_catch_up(catch_up: CatchUp) -> u8 {
+    return match catch_up {
+        CatchUp::Once => 1,
+        CatchUp::All => 2,
+        CatchUp::Summary => 3,
+        CatchUp::Skip => 4,
+    };
+}
+
+// How urgent a notification says it is, which notification servers may show or sort it by
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Urgency {
+    Low,
+    Normal,
+    // Servers usually keep these on screen until dismissed
+    Critical,
+}
+
+impl fmt::Display for Urgency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::Critical => "critical",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Urgency {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Urgency, String> {
+        return match input.trim().to_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "normal" => Ok(Self::Normal),
+            "critical" => Ok(Self::Critical),
+            _ => Err(format!(
+                "Invalid urgency \"{}\", expected low, normal or critical",
+                input.trim()
+            )),
+        };
+    }
+}
+
+// The same bytes as the urgency hint of the freedesktop notification spec
+pub fn deserialize_urgency(byte: u8) -> Option<Urgency> {
+    return match byte {
+        0 => Some(Urgency::Low),
+        1 => Some(Urgency::Normal),
+        2 => Some(Urgency::Critical),
+        _ => None,
+    };
+}
+
+pub fn serialize_urgency(urgency: Urgency) -> u8 {
+    return match urgency {
+        Urgency::Low => 0,
+        Urgency::Normal => 1,
+        Urgency::Critical => 2,
+    };
+}
+
+// Where a reminder is sent, by the notifier of Hermes with the same name
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Sink {
+    // A desktop notification, over D-Bus
+    Desktop,
+    // A line appended to a file
+    File,
+    // The terminals of the user, as wall does
+    Wall,
+    // A command of the user's choosing
+    Command,
+    // JSON posted to a local HTTP endpoint
+    Http,
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Desktop => "desktop",
+            Self::File => "file",
+            Self::Wall => "wall",
+            Self::Command => "command",
+            Self::Http => "http",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Sink {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Sink, String> {
+        return match input.trim().to_lowercase().as_str() {
+            "desktop" => Ok(Self::Desktop),
+            "file" => Ok(Self::File),
+            "wall" => Ok(Self::Wall),
+            "command" => Ok(Self::Command),
+            "http" => Ok(Self::Http),
+            _ => Err(format!(
+                "Invalid notifier \"{}\", expected desktop, file, wall, command or http",
+                input.trim()
+            )),
+        };
+    }
+}
+
+// Reads a list of sinks such as "desktop, file", each named once
+pub fn parse_sinks(input: &str) -> Result<Vec<Sink>, String> {
+    let mut sinks = Vec::new();
+    for name in input.split(',') {
+        let sink = name.parse()?;
+        if !sinks.contains(&sink) {
+            sinks.push(sink);
+        }
+    }
+    return Ok(sinks);
+}
+
+pub fn deserialize_sink(byte: u8) -> Option<Sink> {
+    return match byte {
+        1 => Some(Sink::Desktop),
+        2 => Some(Sink::File),
+        3 => Some(Sink::Wall),
+        4 => Some(Sink::Command),
+        5 => Some(Sink::Http),
+        _ => None,
+    };
+}
+
+pub fn serialize_sink(sink: Sink) -> u8 {
+    return match sink {
+        Sink::Desktop => 1,
+        Sink::File => 2,
+        Sink::Wall => 3,
+        Sink::Command => 4,
+        Sink::Http => 5,
+    };
+}
+
+/*
+* A reminder paused until a time, or until resumed. Occurrences due meanwhile are skipped, moving
+* the reminder on, unless held, when they are sent as missed on resuming.
+*/
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Pause {
+    pub until: Option<DateTime<Utc>>,
+    pub hold: bool,
+}
+
+impl Pause {
+    // Mode byte, 1 to skip and 2 to hold, then the end as a Unix timestamp (8 bytes) if it has one
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut vec = vec![if self.hold { 2 } else { 1 }];
+        if let Some(until) = self.until {
+            vec.extend_from_slice(&until.timestamp().to_be_bytes());
+        }
+        return vec;
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Option<Pause> {
+        let (mode, until) = bytes.split_first()?;
+        let hold = match mode {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let until = match until.len() {
+            0 => None,
+            8 => {
+                let seconds = i64::from_be_bytes(<[u8; 8]>::try_from(until).ok()?);
+                Some(Utc.timestamp_opt(seconds, 0).single()?)
+            }
+            _ => return None,
+        };
+        return Some(Pause { until, hold });
+    }
+}
+
+// Most occurrences walked through looking for those in a window
+const MAX_STEPS: usize = 100_000;
+// Occurrences looked at for the shortest time between two, enough to see every month of a few years
+const GAP_SAMPLE: usize = 60;
+
+// Frequency byte, month, day, year (4 bytes), hour, minute, n (4 bytes)
+const HEADER_LENGTH_BYTES: usize = 13;
+const MIN_REMINDER_LENGTH_BYTES: usize = HEADER_LENGTH_BYTES + 1;
+
+/*
+* Optional fields follow the body, after a zero byte. Each is a tag (1 byte), the length of
+* its value (2 bytes), then the value. Tags a decoder doesn't know are skipped, and a reminder
+* without optional fields is encoded exactly as before they existed.
+*/
+const EXTENSION_MARKER: u8 = 0;
+const EXTENSION_HEADER_BYTES: usize = 3;
+// The most a value's 2 byte length can say
+const MAX_EXTENSION_BYTES: usize = u16::MAX as usize;
+const TAG_RENOTIFY: u8 = 1;
+const TAG_RULE: u8 = 2;
+const TAG_START: u8 = 3;
+const TAG_CATCH_UP: u8 = 4;
+const TAG_ZONE: u8 = 5;
+const TAG_PRIORITY: u8 = 6;
+const TAG_UNTIL: u8 = 7;
+const TAG_REMAINING: u8 = 8;
+const TAG_PAUSED: u8 = 9;
+const TAG_ALERTS: u8 = 10;
+const TAG_TITLE: u8 = 11;
+const TAG_URGENCY: u8 = 12;
+const TAG_ICON: u8 = 13;
+const TAG_CATEGORY: u8 = 14;
+const TAG_TIMEOUT: u8 = 15;
+const TAG_SINKS: u8 = 16;
+
+// Note: Byte Order is expected to be Big Endian, as is standard in networking
+#[derive(PartialEq, Debug, Clone)]
+pub struct Reminder {
+    pub frequency: Frequency,
+    pub month: u8,
+    pub day: u8,
+    pub year: u32,
+    pub hour: u8,
+    pub minute: u8,
+    pub n: Option<u32>,
+    pub message: String,
+    // Minutes between notifications until the user dismisses one, None if sent only once
+    pub renotify: Option<u32>,
+    // Recurrence of a RULE reminder, counted from its date and time
+    pub rule: Option<Rule>,
+    // Date of the first occurrence, which a MONTHLY or YEARLY reminder keeps the day of
+    pub start: Option<NaiveDate>,
+    // Handling of missed occurrences, None to follow the setting of Hermes
+    pub catch_up: Option<CatchUp>,
+    // Time zone the date and time are in, None for the local time of the computer wherever it is
+    pub zone: Option<Tz>,
+    // Sent even during quiet hours and do not disturb
+    pub high_priority: bool,
+    // Last date an occurrence may fall on, in the reminder's zone
+    pub until: Option<NaiveDate>,
+    // Occurrences left to send, counting the one at the reminder's date and time
+    pub remaining: Option<u32>,
+    // Set by pausing the reminder, rather than when adding or updating it
+    pub paused: Option<Pause>,
+    // Minutes ahead of each occurrence to send a warning, such as 30 for "In 30 min: ..."
+    pub alerts: Vec<u32>,
+    // Summary shown above the message, None for "Hermes"
+    pub title: Option<String>,
+    pub urgency: Option<Urgency>,
+    // Icon name from the desktop's theme, or path to an image
+    pub icon: Option<String>,
+    // Kind of notification, such as "calendar", for servers that style by it
+    pub category: Option<String>,
+    // Milliseconds the notification stays on screen, 0 until dismissed, None to leave it to Hermes
+    pub timeout: Option<u32>,
+    // Where it is sent, empty for wherever the settings of Hermes say
+    pub sinks: Vec<Sink>,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum DecodeError {
+    InvalidLength(usize),
+    InvalidFrequency(u8),
+    MissingInterval,
+    InvalidBody(std::str::Utf8Error),
+    InvalidExtension(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "Invalid Message Length: {}", len),
+            Self::InvalidFrequency(byte) => write!(f, "Invalid Frequency byte: {}", byte),
+            Self::MissingInterval => write!(f, "N not specified, but N days is the frequency"),
+            Self::InvalidBody(e) => write!(f, "Error decoding message body: {}", e),
+            Self::InvalidExtension(tag) => write!(f, "Invalid optional field with tag {}", tag),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// Rules a reminder must follow before Hermes will store it
+#[derive(PartialEq, Debug)]
+pub enum ValidationError {
+    InvalidDate,
+    InvalidTime,
+    MissingInterval,
+    EmptyMessage,
+    NulInMessage,
+    InvalidRenotify,
+    MissingRule,
+    RuleNeverOccurs,
+    // Parts of a recurrence rule that can't be used together, and why
+    UnsupportedRule(&'static str),
+    EndsBeforeStart,
+    NoOccurrencesLeft,
+    InvalidAlert,
+    // A warning, in minutes ahead, coming before the occurrence ahead of it, in minutes apart
+    AlertBeyondInterval { lead: u32, interval: u32 },
+    EmptyTitle,
+    InvalidTimeout,
+    // An optional field, named, too long for its length to be encoded
+    FieldTooLong(&'static str),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidDate => write!(f, "Date is not a valid calendar date"),
+            Self::InvalidTime => write!(f, "Time must be between 00:00 and 23:59"),
+            Self::MissingInterval => write!(f, "Every N days needs an N of at least 1"),
+            Self::EmptyMessage => write!(f, "Message body is empty"),
+            Self::NulInMessage => write!(f, "Message body contains a NUL character"),
+            Self::MissingRule => write!(f, "Custom recurrence needs a rule"),
+            Self::RuleNeverOccurs => write!(f, "Recurrence rule has no occurrences"),
+            Self::UnsupportedRule(reason) => write!(f, "Unsupported recurrence rule: {}", reason),
+            Self::EndsBeforeStart => write!(f, "Last date is before the reminder's date"),
+            Self::NoOccurrencesLeft => write!(f, "Number of occurrences must be at least 1"),
+            Self::InvalidAlert => write!(f, "Warnings must come at least a minute ahead"),
+            Self::AlertBeyondInterval { lead, interval } => write!(
+                f,
+                "A warning {} ahead comes before the occurrence before it, as it recurs every {}",
+                describe_lead(*lead),
+                describe_lead(*interval)
+            ),
+            Self::EmptyTitle => write!(f, "Title is empty"),
+            Self::InvalidTimeout => write!(f, "Notifications can stay on screen at most 24 days"),
+            Self::FieldTooLong(field) => {
+                write!(f, "{} is longer than {} bytes", field, MAX_EXTENSION_BYTES)
+            }
+            Self::InvalidRenotify => write!(
+                f,
+                "Repeated notifications need an interval of at least a minute"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn deserialize_frequency(byte: u8) -> Option<Frequency> {
+    return match byte {
+        1 => Some(Frequency::DAILY),
+        2 => Some(Frequency::ONCE),
+        3 => Some(Frequency::WEEKLY),
+        4 => Some(Frequency::NDAYS),
+        5 => Some(Frequency::RULE),
+        6 => Some(Frequency::MONTHLY),
+        7 => Some(Frequency::YEARLY),
+        _ => None,
+    };
+}
+
+pub fn serialize_frequency(freq: Frequency) -> u8 {
+    return match freq {
+        Frequency::DAILY => 1,
+        Frequency::ONCE => 2,
+        Frequency::WEEKLY => 3,
+        Frequency::NDAYS => 4,
+        Frequency::RULE => 5,
+        Frequency::MONTHLY => 6,
+        Frequency::YEARLY => 7,
+    };
+}
+
+// Bytes are specified to come as BIG ENDIAN
+fn deserialize_u32(bytes: &[u8]) -> u32 {
+    return u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+}
+
+impl Reminder {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        frequency: Frequency,
+        month: u8,
+        day: u8,
+        year: u32,
+        hour: u8,
+        minute: u8,
+        n: Option<u32>,
+        message: String,
+    ) -> Self {
+        return Reminder {
+            frequency,
+            month,
+            day,
+            year,
+            hour,
+            minute,
+            n,
+            message,
+            renotify: None,
+            rule: None,
+            start: None,
+            catch_up: None,
+            zone: None,
+            high_priority: false,
+            until: None,
+            remaining: None,
+            paused: None,
+            alerts: Vec::new(),
+            title: None,
+            urgency: None,
+            icon: None,
+            category: None,
+            timeout: None,
+            sinks: Vec::new(),
+        };
+    }
+
+    pub fn deserialize_reminder(vec: &[u8]) -> Result<Reminder, DecodeError> {
+        if vec.len() < MIN_REMINDER_LENGTH_BYTES {
+            return Err(DecodeError::InvalidLength(vec.len()));
+        }
+
+        let frequency =
+            deserialize_frequency(vec[0]).ok_or(DecodeError::InvalidFrequency(vec[0]))?;
+
+        let month = vec[1];
+        let day = vec[2];
+        let year = deserialize_u32(&vec[3..7]);
+        let hour = vec[7];
+        let minute = vec[8];
+
+        // An n of 0 is how an absent interval is sent
+        let n = match deserialize_u32(&vec[9..13]) {
+            0 => None,
+            value => Some(value),
+        };
+        if n.is_none() && frequency == Frequency::NDAYS {
+            return Err(DecodeError::MissingInterval);
+        }
+
+        // Only body remains, possibly followed by optional fields
+        let rest = &vec[HEADER_LENGTH_BYTES..];
+        let (body, extensions) = match rest.iter().position(|&byte| byte == EXTENSION_MARKER) {
+            Some(end) => (&rest[..end], &rest[end + 1..]),
+            None => (rest, &rest[rest.len()..]),
+        };
+        let message = std::str::from_utf8(body)
+            .map_err(DecodeError::InvalidBody)?
+            .to_string();
+
+        let mut reminder = Reminder::new(frequency, month, day, year, hour, minute, n, message);
+        reminder.read_extensions(extensions)?;
+        return Ok(reminder);
+    }
+
+    fn read_extensions(&mut self, mut bytes: &[u8]) -> Result<(), DecodeError> {
+        while !bytes.is_empty() {
+            let tag = bytes[0];
+            if bytes.len() < EXTENSION_HEADER_BYTES {
+                return Err(DecodeError::InvalidExtension(tag));
+            }
+            let length = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+            let value = bytes
+                .get(EXTENSION_HEADER_BYTES..EXTENSION_HEADER_BYTES + length)
+                .ok_or(DecodeError::InvalidExtension(tag))?;
+
+            match tag {
+                TAG_RENOTIFY if length == 4 => self.renotify = Some(deserialize_u32(value)),
+                TAG_RENOTIFY => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_RULE => {
+                    let rule = std::str::from_utf8(value)
+                        .ok()
+                        .and_then(|rule| rule.parse().ok());
+                    self.rule = Some(rule.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_START if length == 6 => {
+                    let year = deserialize_u32(&value[..4]) as i32;
+                    let start = NaiveDate::from_ymd_opt(year, value[4] as u32, value[5] as u32);
+                    self.start = Some(start.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_START => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_CATCH_UP => {
+                    let catch_up = value.first().and_then(|&byte| deserialize_catch_up(byte));
+                    self.catch_up = Some(catch_up.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_ZONE => {
+                    let zone = std::str::from_utf8(value)
+                        .ok()
+                        .and_then(|name| zone::parse_zone(name).ok());
+                    self.zone = Some(zone.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_PRIORITY if length == 1 => self.high_priority = value[0] == 1,
+                TAG_PRIORITY => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_UNTIL if length == 6 => {
+                    let year = deserialize_u32(&value[..4]) as i32;
+                    let until = NaiveDate::from_ymd_opt(year, value[4] as u32, value[5] as u32);
+                    self.until = Some(until.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_UNTIL => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_REMAINING if length == 4 => self.remaining = Some(deserialize_u32(value)),
+                TAG_REMAINING => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_ALERTS if length.is_multiple_of(4) => {
+                    self.alerts = value.chunks(4).map(deserialize_u32).collect();
+                }
+                TAG_ALERTS => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_TITLE | TAG_ICON | TAG_CATEGORY => {
+                    let text = std::str::from_utf8(value)
+                        .map_err(|_| DecodeError::InvalidExtension(tag))?;
+                    let text = Some(text.to_string());
+                    match tag {
+                        TAG_TITLE => self.title = text,
+                        TAG_ICON => self.icon = text,
+                        _ => self.category = text,
+                    }
+                }
+                TAG_URGENCY => {
+                    let urgency = value.first().and_then(|&byte| deserialize_urgency(byte));
+                    self.urgency = Some(urgency.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                TAG_TIMEOUT if length == 4 => self.timeout = Some(deserialize_u32(value)),
+                TAG_TIMEOUT => return Err(DecodeError::InvalidExtension(tag)),
+                TAG_SINKS => {
+                    let sinks: Option<Vec<Sink>> =
+                        value.iter().map(|&byte| deserialize_sink(byte)).collect();
+                    self.sinks = sinks.ok_or(DecodeError::InvalidExtension(tag))?;
+                }
+                TAG_PAUSED => {
+                    let paused = Pause::deserialize(value);
+                    self.paused = Some(paused.ok_or(DecodeError::InvalidExtension(tag))?);
+                }
+                _ => {} // Written by a newer version, safe to ignore
+            }
+            bytes = &bytes[EXTENSION_HEADER_BYTES + length..];
+        }
+        return Ok(());
+    }
+
+    fn write_extensions(&self) -> Vec<u8> {
+        let mut vec = Vec::new();
+        // validate() keeps values short enough, and one that isn't is left out rather than cut short
+        let mut push = |tag: u8, value: &[u8]| {
+            if let Ok(length) = u16::try_from(value.len()) {
+                vec.push(tag);
+                vec.extend_from_slice(&length.to_be_bytes());
+                vec.extend_from_slice(value);
+            }
+        };
+        if let Some(minutes) = self.renotify {
+            push(TAG_RENOTIFY, &minutes.to_be_bytes());
+        }
+        if let Some(rule) = &self.rule {
+            push(TAG_RULE, rule.to_string().as_bytes());
+        }
+        if let Some(start) = self.start {
+            let mut value = (start.year() as u32).to_be_bytes().to_vec();
+            value.extend_from_slice(&[start.month() as u8, start.day() as u8]);
+            push(TAG_START, &value);
+        }
+        if let Some(catch_up) = self.catch_up {
+            push(TAG_CATCH_UP, &[serialize_catch_up(catch_up)]);
+        }
+        if let Some(zone) = self.zone {
+            push(TAG_ZONE, zone.name().as_bytes());
+        }
+        if self.high_priority {
+            push(TAG_PRIORITY, &[1]);
+        }
+        if let Some(until) = self.until {
+            let mut value = (until.year() as u32).to_be_bytes().to_vec();
+            value.extend_from_slice(&[until.month() as u8, until.day() as u8]);
+            push(TAG_UNTIL, &value);
+        }
+        if let Some(remaining) = self.remaining {
+            push(TAG_REMAINING, &remaining.to_be_bytes());
+        }
+        if let Some(paused) = self.paused {
+            push(TAG_PAUSED, &paused.serialize());
+        }
+        if !self.alerts.is_empty() {
+            let value: Vec<u8> = self
+                .alerts
+                .iter()
+                .flat_map(|minutes| minutes.to_be_bytes())
+                .collect();
+            push(TAG_ALERTS, &value);
+        }
+        if let Some(title) = &self.title {
+            push(TAG_TITLE, title.as_bytes());
+        }
+        if let Some(urgency) = self.urgency {
+            push(TAG_URGENCY, &[serialize_urgency(urgency)]);
+        }
+        if let Some(icon) = &self.icon {
+            push(TAG_ICON, icon.as_bytes());
+        }
+        if let Some(category) = &self.category {
+            push(TAG_CATEGORY, category.as_bytes());
+        }
+        if let Some(timeout) = self.timeout {
+            push(TAG_TIMEOUT, &timeout.to_be_bytes());
+        }
+        if !self.sinks.is_empty() {
+            let value: Vec<u8> = self
+                .sinks
+                .iter()
+                .map(|&sink| serialize_sink(sink))
+                .collect();
+            push(TAG_SINKS, &value);
+        }
+        return vec;
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut vec: Vec<u8> = Vec::with_capacity(HEADER_LENGTH_BYTES + self.message.len());
+        vec.push(serialize_frequency(self.frequency));
+        vec.push(self.month);
+        vec.push(self.day);
+        vec.extend_from_slice(&self.year.to_be_bytes());
+        vec.push(self.hour);
+        vec.push(self.minute);
+        vec.extend_from_slice(&self.n.unwrap_or(0).to_be_bytes());
+        vec.extend_from_slice(self.message.as_bytes());
+
+        let extensions = self.write_extensions();
+        if !extensions.is_empty() {
+            vec.push(EXTENSION_MARKER);
+            vec.extend(extensions);
+        }
+        return vec;
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.year > i32::MAX as u32
+            || NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+                .is_none()
+        {
+            return Err(ValidationError::InvalidDate);
+        }
+        if self.hour > 23 || self.minute > 59 {
+            return Err(ValidationError::InvalidTime);
+        }
+        if self.frequency == Frequency::NDAYS && self.n.unwrap_or(0) == 0 {
+            return Err(ValidationError::MissingInterval);
+        }
+        if self.message.trim().is_empty() {
+            return Err(ValidationError::EmptyMessage);
+        }
+        // The zero byte would end the body early
+        if self.message.contains('\0') {
+            return Err(ValidationError::NulInMessage);
+        }
+        if self.renotify == Some(0) {
+            return Err(ValidationError::InvalidRenotify);
+        }
+        if self.frequency == Frequency::RULE {
+            let rule = self.rule.as_ref().ok_or(ValidationError::MissingRule)?;
+            let start = self.to_naive().unwrap();
+            if let Some(reason) = rule.unsupported() {
+                return Err(ValidationError::UnsupportedRule(reason));
+            }
+            if rule.never_occurs(start)
+                || rule
+                    .next_after(start, start - chrono::Duration::minutes(1))
+                    .is_none()
+            {
+                return Err(ValidationError::RuleNeverOccurs);
+            }
+        }
+        if self
+            .until
+            .is_some_and(|until| until < self.to_naive().unwrap().date())
+        {
+            return Err(ValidationError::EndsBeforeStart);
+        }
+        if self.remaining == Some(0) {
+            return Err(ValidationError::NoOccurrencesLeft);
+        }
+        if self.alerts.contains(&0) {
+            return Err(ValidationError::InvalidAlert);
+        }
+        // A warning for one occurrence would otherwise be due before the one ahead of it was sent
+        let longest = self.alerts.iter().max().copied();
+        if let (Some(lead), Some(interval)) = (longest, self.shortest_interval()) {
+            if lead as i64 > interval.num_minutes() {
+                return Err(ValidationError::AlertBeyondInterval {
+                    lead,
+                    interval: interval.num_minutes() as u32,
+                });
+            }
+        }
+        if self
+            .title
+            .as_ref()
+            .is_some_and(|title| title.trim().is_empty())
+        {
+            return Err(ValidationError::EmptyTitle);
+        }
+        // Sent to the notification server as a signed number
+        if self
+            .timeout
+            .is_some_and(|timeout| timeout > i32::MAX as u32)
+        {
+            return Err(ValidationError::InvalidTimeout);
+        }
+        let lengths = [
+            (
+                "Rule",
+                self.rule.as_ref().map_or(0, |rule| rule.to_string().len()),
+            ),
+            ("Warnings", self.alerts.len() * 4),
+            ("Title", self.title.as_ref().map_or(0, String::len)),
+            ("Icon", self.icon.as_ref().map_or(0, String::len)),
+            ("Category", self.category.as_ref().map_or(0, String::len)),
+            ("Notifiers", self.sinks.len()),
+        ];
+        if let Some((field, _)) = lengths
+            .iter()
+            .find(|(_, length)| *length > MAX_EXTENSION_BYTES)
+        {
+            return Err(ValidationError::FieldTooLong(field));
+        }
+        return Ok(());
+    }
+
+    // The least time between two of the first occurrences, None when there is at most one
+    fn shortest_interval(&self) -> Option<Duration> {
+        let mut previous = self.to_naive()?;
+        let mut shortest = None;
+        let sample = self
+            .remaining
+            .map_or(GAP_SAMPLE, |remaining| GAP_SAMPLE.min(remaining as usize));
+        for _ in 1..sample {
+            let next = match self.next_occurrence(previous) {
+                Some(next) => next,
+                None => break,
+            };
+            let gap = next - previous;
+            shortest = Some(shortest.map_or(gap, |shortest: Duration| shortest.min(gap)));
+            previous = next;
+        }
+        return shortest;
+    }
+
+    pub fn to_naive(&self) -> Option<NaiveDateTime> {
+        return NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)?
+            .and_hms_opt(self.hour as u32, self.minute as u32, 0);
+    }
+
+    /*
+     * The first occurrence strictly after `after`, counted in whole intervals from the start of
+     * the schedule rather than from when a notification was actually sent, so a reminder sent
+     * late keeps its day. None once a reminder has no occurrences left before its last date.
+     */
+    pub fn next_occurrence(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        return self
+            .next_scheduled(after)
+            .filter(|next| self.until.is_none_or(|until| next.date() <= until));
+    }
+
+    fn next_scheduled(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let first = self.to_naive()?;
+        if after < first {
+            return Some(first);
+        }
+        let start = self.start.unwrap_or_else(|| first.date());
+        let days = match self.frequency {
+            Frequency::ONCE => return None,
+            Frequency::DAILY => 1,
+            Frequency::WEEKLY => 7,
+            Frequency::NDAYS => self.n? as i64,
+            Frequency::RULE => {
+                let rule = self.rule.as_ref()?;
+                // Counted from the start, so intervals keep their phase however late one was sent.
+                // Hours move the time of day, so an hourly rule counts from its last occurrence.
+                let anchor = match rule.frequency {
+                    RuleFrequency::Hourly => first,
+                    _ => start.and_time(first.time()),
+                };
+                return rule.next_after(anchor, after);
+            }
+            Frequency::MONTHLY => return next_month(start, first.time(), 1, after),
+            Frequency::YEARLY => return next_month(start, first.time(), 12, after),
+        };
+
+        // Whole days keep the time of day, also across a change of the clocks
+        let anchor = start.and_time(first.time());
+        let elapsed = (after.date() - anchor.date()).num_days();
+        let mut next = anchor + Duration::try_days(elapsed.div_euclid(days) * days)?;
+        while next <= after {
+            next = next.checked_add_signed(Duration::try_days(days)?)?;
+        }
+        return Some(next);
+    }
+
+    /*
+     * The reminder's own time and each occurrence after it up to `until`, at most `limit` of them,
+     * and no more than it has remaining.
+     */
+    pub fn occurrences_until(&self, until: NaiveDateTime, limit: usize) -> Vec<NaiveDateTime> {
+        let limit = self
+            .remaining
+            .map_or(limit, |remaining| limit.min(remaining as usize));
+        let mut occurrences = Vec::new();
+        let mut next = self.to_naive().filter(|first| *first <= until);
+        while let Some(time) = next {
+            if occurrences.len() == limit {
+                break;
+            }
+            occurrences.push(time);
+            next = self.next_occurrence(time).filter(|next| *next <= until);
+        }
+        return occurrences;
+    }
+
+    /*
+     * The instants of the occurrences between `from` and `to`, from the reminder's own time on,
+     * at most `limit` of them. Occurrences before `from` still use up those remaining, so are only
+     * skipped over when there is no count, and no more than MAX_STEPS are ever walked through.
+     */
+    pub fn occurrences_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let until = self.wall_time(to);
+        let mut left = self
+            .remaining
+            .map_or(usize::MAX, |remaining| remaining as usize)
+            .min(MAX_STEPS);
+        let mut occurrences = Vec::new();
+        let mut next = match self.remaining {
+            Some(_) => self.to_naive(),
+            None => self.next_occurrence(self.wall_time(from) - Duration::minutes(1)),
+        }
+        .filter(|first| *first <= until);
+        while let Some(time) = next {
+            if left == 0 || occurrences.len() == limit {
+                break;
+            }
+            left -= 1;
+            if let Some(at) = zone::resolve(self.zone, time).filter(|at| *at >= from && *at <= to) {
+                occurrences.push(at);
+            }
+            next = self.next_occurrence(time).filter(|next| *next <= until);
+        }
+        return occurrences;
+    }
+
+    /*
+     * When the next warning for the reminder's date and time is due after `after`, with how many
+     * minutes ahead it is. Warnings for a time that has already come are never due.
+     */
+    pub fn next_alert(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, u32)> {
+        let at = self.to_instant()?;
+        return self
+            .alerts
+            .iter()
+            .filter_map(|&lead| {
+                Some((at.checked_sub_signed(Duration::minutes(lead as i64))?, lead))
+            })
+            .filter(|(time, _)| *time > after && *time < at)
+            .min();
+    }
+
+    /*
+     * The warning to send at `now`, as the minutes ahead it was meant to come, once one or more
+     * are due and the reminder's time hasn't come. The latest one stands for any before it.
+     */
+    pub fn alert_due(&self, now: DateTime<Utc>) -> Option<u32> {
+        let at = self.to_instant()?;
+        if at <= now {
+            return None;
+        }
+        return self
+            .alerts
+            .iter()
+            .filter(|&&lead| at - Duration::minutes(lead as i64) <= now)
+            .min()
+            .copied();
+    }
+
+    // The instant the reminder's date and time happen in its zone
+    pub fn to_instant(&self) -> Option<DateTime<Utc>> {
+        return zone::resolve(self.zone, self.to_naive()?);
+    }
+
+    // What the clocks of the reminder's zone read at an instant
+    pub fn wall_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        return zone::wall_time(self.zone, instant);
+    }
+}
+
+/*
+* The first occurrence after `after` of a reminder every `step` months from `start`. A day the
+* month doesn't have, like the 31st or Feb 29th, falls on the last day of that month instead,
+* and the next month goes back to the start's day.
+*/
+fn next_month(
+    start: NaiveDate,
+    time: NaiveTime,
+    step: i64,
+    after: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let start_month = start.year() as i64 * 12 + start.month0() as i64;
+    let after_month = after.year() as i64 * 12 + after.month0() as i64;
+
+    let mut month = start_month + (after_month - start_month).max(0) / step * step;
+    loop {
+        let year = i32::try_from(month.div_euclid(12)).ok()?;
+        let month0 = month.rem_euclid(12) as u32;
+        let day = start.day().min(recurrence::days_in_month(year, month0 + 1));
+        let next = NaiveDate::from_ymd_opt(year, month0 + 1, day)?.and_time(time);
+        if next > after {
+            return Some(next);
+        }
+        month += step;
+    }
+}
+
+// A length of warning such as "30 min", "2 h" or "1 day", in the largest unit it is whole in
+pub fn describe_lead(minutes: u32) -> String {
+    return match minutes {
+        1440 => "1 day".to_string(),
+        m if m % 1440 == 0 => format!("{} days", m / 1440),
+        m if m % 60 == 0 => format!("{} h", m / 60),
+        m => format!("{} min", m),
+    };
+}
+
+impl fmt::Display for Reminder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "REMINDER: {}{} | {}/{}/{} {:02}:{:02}",
+            self.title
+                .as_ref()
+                .map_or(String::new(), |title| format!("{}: ", title)),
+            self.message.trim_end(),
+            self.month,
+            self.day,
+            self.year,
+            self.hour,
+            self.minute,
+        )?;
+        if let Some(zone) = self.zone {
+            write!(f, " {}", zone)?;
+        }
+        write!(f, " | Frequency: {}", self.frequency)?;
+        if let Some(n) = self.n {
+            write!(f, ": {}", n)?;
+        }
+        if let Some(rule) = &self.rule {
+            write!(f, ": {}", rule)?;
+        }
+        if let Some(minutes) = self.renotify {
+            write!(f, " | Repeats every {} min until dismissed", minutes)?;
+        }
+        if let Some(catch_up) = self.catch_up {
+            write!(f, " | Missed: {}", catch_up)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, " | Until {}", until)?;
+        }
+        if let Some(remaining) = self.remaining {
+            write!(f, " | {} left", remaining)?;
+        }
+        if !self.alerts.is_empty() {
+            let leads: Vec<String> = self
+                .alerts
+                .iter()
+                .map(|&lead| describe_lead(lead))
+                .collect();
+            write!(f, " | Warns {} ahead", leads.join(", "))?;
+        }
+        if self.high_priority {
+            write!(f, " | High priority")?;
+        }
+        if let Some(urgency) = self.urgency {
+            write!(f, " | Urgency: {}", urgency)?;
+        }
+        if let Some(icon) = &self.icon {
+            write!(f, " | Icon: {}", icon)?;
+        }
+        if let Some(category) = &self.category {
+            write!(f, " | Category: {}", category)?;
+        }
+        if !self.sinks.is_empty() {
+            let sinks: Vec<String> = self.sinks.iter().map(|sink| sink.to_string()).collect();
+            write!(f, " | Sent to {}", sinks.join(", "))?;
+        }
+        match self.timeout {
+            Some(0) => write!(f, " | Stays until dismissed")?,
+            Some(timeout) => write!(f, " | Shown for {}s", timeout / 1000)?,
+            None => {}
+        }
+        match self.paused {
+            Some(Pause {
+                until: Some(until), ..
+            }) => write!(
+                f,
+                " | Paused until {}",
+                self.wall_time(until).format("%Y-%m-%d %H:%M")
+            )?,
+            Some(_) => write!(f, " | Paused")?,
+            None => {}
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reminder::{
+        self, CatchUp, DecodeError, Frequency, Pause, Reminder, Sink, Urgency, ValidationError,
+    };
+    use chrono::{Datelike, NaiveDate};
+    use chrono::{TimeZone, Utc};
+
+    // Bytes as produced by the serializers of Hermes and Caduceus 0.1.0, before the shared crate
+    const GOLDEN_DAILY: &[u8] = &[1, 11, 2, 0, 0, 7, 234, 9, 30, 0, 0, 0, 0, 72, 73];
+    const GOLDEN_NDAYS: &[u8] = &[4, 2, 29, 0, 0, 7, 236, 23, 5, 0, 0, 0, 3, 72, 73];
+
+    #[test]
+    fn fails_deserialize_if_vec_short() {
+        let vec: Vec<u8> = vec![3, 4, 5, 6];
+        assert_eq!(
+            reminder::Reminder::deserialize_reminder(&vec),
+            Err(DecodeError::InvalidLength(4))
+        );
+    }
+
+    #[test]
+    fn successful_deserialize() {
+        let vec: Vec<u8> = vec![1, 1, 1, 2, 2, 2, 2, 1, 1, 2, 2, 2, 2, 72, 69, 76, 76, 79];
+        let reminder = reminder::Reminder::deserialize_reminder(&vec);
+        assert!(reminder.is_ok());
+        assert_eq!(reminder.unwrap().message, "HELLO");
+    }
+
+    #[test]
+    fn rejects_unknown_frequency() {
+        let mut vec = GOLDEN_DAILY.to_vec();
+        vec[0] = 9;
+        assert_eq!(
+            Reminder::deserialize_reminder(&vec),
+            Err(DecodeError::InvalidFrequency(9))
+        );
+    }
+
+    #[test]
+    fn rejects_ndays_without_interval() {
+        let mut vec = GOLDEN_NDAYS.to_vec();
+        vec[12] = 0;
+        assert_eq!(
+            Reminder::deserialize_reminder(&vec),
+            Err(DecodeError::MissingInterval)
+        );
+    }
+
+    #[test]
+    fn golden_bytes_decode() {
+        let daily = Reminder::deserialize_reminder(GOLDEN_DAILY).unwrap();
+        assert_eq!(
+            daily,
+            Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 30, None, "HI".to_string())
+        );
+
+        // Multi-byte fields are big endian; Caduceus 0.1.0 read these byte-reversed
+        let ndays = Reminder::deserialize_reminder(GOLDEN_NDAYS).unwrap();
+        assert_eq!(ndays.year, 2028);
+        assert_eq!(ndays.n, Some(3));
+    }
+
+    #[test]
+    fn golden_bytes_encode() {
+        let daily = Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 30, None, "HI".to_string());
+        assert_eq!(daily.serialize(), GOLDEN_DAILY);
+
+        let ndays = Reminder::new(
+            Frequency::NDAYS,
+            2,
+            29,
+            2028,
+            23,
+            5,
+            Some(3),
+            "HI".to_string(),
+        );
+        assert_eq!(ndays.serialize(), GOLDEN_NDAYS);
+    }
+
+    #[test]
+    fn validation() {
+        let valid = Reminder::new(Frequency::ONCE, 2, 29, 2028, 0, 0, None, "x".to_string());
+        assert_eq!(valid.validate(), Ok(()));
+
+        let mut leap = valid.clone();
+        leap.year = 2027;
+        assert_eq!(leap.validate(), Err(ValidationError::InvalidDate));
+
+        let mut late = valid.clone();
+        late.hour = 24;
+        assert_eq!(late.validate(), Err(ValidationError::InvalidTime));
+
+        let mut ndays = valid.clone();
+        ndays.frequency = Frequency::NDAYS;
+        assert_eq!(ndays.validate(), Err(ValidationError::MissingInterval));
+
+        let mut blank = valid.clone();
+        blank.message = " \n".to_string();
+        assert_eq!(blank.validate(), Err(ValidationError::EmptyMessage));
+
+        let mut nul = valid.clone();
+        nul.message = "a\0b".to_string();
+        assert_eq!(nul.validate(), Err(ValidationError::NulInMessage));
+
+        let mut renotify = valid.clone();
+        renotify.renotify = Some(0);
+        assert_eq!(renotify.validate(), Err(ValidationError::InvalidRenotify));
+
+        let mut custom = valid.clone();
+        custom.frequency = Frequency::RULE;
+        assert_eq!(custom.validate(), Err(ValidationError::MissingRule));
+        custom.rule = Some("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30".parse().unwrap());
+        assert_eq!(custom.validate(), Err(ValidationError::RuleNeverOccurs));
+        custom.rule = Some("FREQ=HOURLY;BYMONTH=2;BYMONTHDAY=30".parse().unwrap());
+        assert_eq!(custom.validate(), Err(ValidationError::RuleNeverOccurs));
+        custom.rule = Some("FREQ=WEEKLY;BYMONTHDAY=1".parse().unwrap());
+        assert!(matches!(
+            custom.validate(),
+            Err(ValidationError::UnsupportedRule(_))
+        ));
+
+        let mut ending = valid;
+        ending.until = NaiveDate::from_ymd_opt(2020, 1, 1);
+        assert_eq!(ending.validate(), Err(ValidationError::EndsBeforeStart));
+        ending.until = None;
+        ending.remaining = Some(0);
+        assert_eq!(ending.validate(), Err(ValidationError::NoOccurrencesLeft));
+
+        let mut styled = ending;
+        styled.remaining = None;
+        styled.title = Some(" ".to_string());
+        assert_eq!(styled.validate(), Err(ValidationError::EmptyTitle));
+        styled.title = None;
+        styled.timeout = Some(u32::MAX);
+        assert_eq!(styled.validate(), Err(ValidationError::InvalidTimeout));
+        styled.timeout = None;
+
+        styled.category = Some("x".repeat(65535));
+        assert_eq!(styled.validate(), Ok(()));
+        styled.category = Some("x".repeat(65536));
+        assert_eq!(
+            styled.validate(),
+            Err(ValidationError::FieldTooLong("Category"))
+        );
+        // Never written with a length that wrapped around
+        let decoded = Reminder::deserialize_reminder(&styled.serialize()).unwrap();
+        assert_eq!(decoded.category, None);
+        assert_eq!(decoded.message, styled.message);
+    }
+
+    #[test]
+    fn optional_fields() {
+        let mut persistent =
+            Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        persistent.renotify = Some(5);
+        let bytes = persistent.serialize();
+        assert_eq!(&bytes[13..], &[72, 73, 0, 1, 0, 4, 0, 0, 0, 5]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(persistent));
+
+        let mut custom = Reminder::new(Frequency::RULE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        custom.rule = Some("FREQ=MONTHLY;BYDAY=1MO".parse().unwrap());
+        let bytes = custom.serialize();
+        assert_eq!(bytes[0], 5);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(custom));
+
+        let mut monthly = Reminder::new(
+            Frequency::MONTHLY,
+            2,
+            28,
+            2030,
+            3,
+            4,
+            None,
+            "HI".to_string(),
+        );
+        monthly.start = NaiveDate::from_ymd_opt(2030, 1, 31);
+        let bytes = monthly.serialize();
+        assert_eq!(&bytes[15..], &[0, 3, 0, 6, 0, 0, 7, 238, 1, 31]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(monthly));
+
+        let mut catch_up =
+            Reminder::new(Frequency::DAILY, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        catch_up.catch_up = Some(CatchUp::Summary);
+        let bytes = catch_up.serialize();
+        assert_eq!(&bytes[15..], &[0, 4, 0, 1, 3]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(catch_up));
+
+        let mut zoned = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        zoned.zone = Some(crate::zone::parse_zone("Asia/Tokyo").unwrap());
+        let bytes = zoned.serialize();
+        assert_eq!(&bytes[15..19], &[0, 5, 0, 10]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(zoned));
+
+        let mut urgent = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        urgent.high_priority = true;
+        let bytes = urgent.serialize();
+        assert_eq!(&bytes[15..], &[0, 6, 0, 1, 1]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(urgent));
+
+        let mut ending = Reminder::new(Frequency::DAILY, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        ending.until = NaiveDate::from_ymd_opt(2030, 2, 1);
+        ending.remaining = Some(10);
+        let bytes = ending.serialize();
+        assert_eq!(
+            &bytes[15..],
+            &[0, 7, 0, 6, 0, 0, 7, 238, 2, 1, 8, 0, 4, 0, 0, 0, 10]
+        );
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(ending));
+
+        let mut paused = Reminder::new(Frequency::DAILY, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        paused.paused = Some(Pause {
+            until: None,
+            hold: true,
+        });
+        let bytes = paused.serialize();
+        assert_eq!(&bytes[15..], &[0, 9, 0, 1, 2]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(paused.clone()));
+        paused.paused = Some(Pause {
+            until: Utc.timestamp_opt(1_900_000_000, 0).single(),
+            hold: false,
+        });
+        let bytes = paused.serialize();
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(paused));
+        assert_eq!(Pause::deserialize(&[1, 0, 0]), None);
+
+        let mut warned = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        warned.alerts = vec![30, 1440];
+        let bytes = warned.serialize();
+        assert_eq!(&bytes[15..], &[0, 10, 0, 8, 0, 0, 0, 30, 0, 0, 5, 160]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(warned));
+
+        let mut styled = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        styled.title = Some("Yo".to_string());
+        styled.urgency = Some(Urgency::Critical);
+        styled.icon = Some("alarm".to_string());
+        styled.category = Some("x".to_string());
+        styled.timeout = Some(0);
+        let bytes = styled.serialize();
+        assert_eq!(
+            &bytes[15..],
+            &[
+                0, 11, 0, 2, 89, 111, 12, 0, 1, 2, 13, 0, 5, 97, 108, 97, 114, 109, 14, 0, 1, 120,
+                15, 0, 4, 0, 0, 0, 0
+            ]
+        );
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(styled));
+
+        let mut routed = Reminder::new(Frequency::ONCE, 1, 2, 2030, 3, 4, None, "HI".to_string());
+        routed.sinks = reminder::parse_sinks("File, wall,file").unwrap();
+        assert_eq!(routed.sinks, vec![Sink::File, Sink::Wall]);
+        let bytes = routed.serialize();
+        assert_eq!(&bytes[15..], &[0, 16, 0, 2, 2, 3]);
+        assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(routed));
+        assert!(reminder::parse_sinks("desktop, pager").is_err());
+        assert_eq!(
+            Reminder::deserialize_reminder(&[&bytes[..16], &[12, 0, 1, 3]].concat()),
+            Err(DecodeError::InvalidExtension(12))
+        );
+
+        // Unknown tags are skipped, truncated values are not
+        let mut unknown = GOLDEN_DAILY.to_vec();
+        unknown.extend_from_slice(&[0, 200, 0, 2, 9, 9]);
+        assert_eq!(
+            Reminder::deserialize_reminder(&unknown),
+            Reminder::deserialize_reminder(GOLDEN_DAILY)
+        );
+        unknown.pop();
+        assert_eq!(
+            Reminder::deserialize_reminder(&unknown),
+            Err(DecodeError::InvalidExtension(200))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let frequencies = [
+            (Frequency::DAILY, None),
+            (Frequency::ONCE, None),
+            (Frequency::WEEKLY, None),
+            (Frequency::NDAYS, Some(u32::MAX)),
+            (Frequency::MONTHLY, None),
+            (Frequency::YEARLY, None),
+        ];
+        for (frequency, n) in frequencies.iter() {
+            let original = Reminder::new(
+                *frequency,
+                12,
+                31,
+                u32::MAX,
+                23,
+                59,
+                *n,
+                "Water the plants \u{1F331}\n".to_string(),
+            );
+            let bytes = original.serialize();
+            assert_eq!(bytes[0], reminder::serialize_frequency(*frequency));
+            assert_eq!(Reminder::deserialize_reminder(&bytes), Ok(original));
+        }
+    }
+
+    fn next_dates(reminder: &Reminder, count: usize) -> Vec<String> {
+        let mut dates = Vec::new();
+        let mut after = reminder.to_naive().unwrap() - chrono::Duration::minutes(1);
+        for _ in 0..count {
+            after = reminder.next_occurrence(after).unwrap();
+            dates.push(after.format("%Y-%m-%d %H:%M").to_string());
+        }
+        return dates;
+    }
+
+    #[test]
+    fn monthly_keeps_its_day() {
+        let mut bill = Reminder::new(Frequency::MONTHLY, 1, 31, 2027, 9, 0, None, "x".to_string());
+        assert_eq!(
+            next_dates(&bill, 4),
+            [
+                "2027-01-31 09:00",
+                "2027-02-28 09:00",
+                "2027-03-31 09:00",
+                "2027-04-30 09:00"
+            ]
+        );
+
+        // Stored after sending on a short month, the start still says the 31st
+        bill.month = 2;
+        bill.day = 28;
+        bill.start = NaiveDate::from_ymd_opt(2027, 1, 31);
+        assert_eq!(
+            next_dates(&bill, 2),
+            ["2027-02-28 09:00", "2027-03-31 09:00"]
+        );
+        let late = NaiveDate::from_ymd_opt(2027, 6, 15)
+            .unwrap()
+            .and_hms_opt(0, 0, 0);
+        assert_eq!(
+            bill.next_occurrence(late.unwrap()),
+            NaiveDate::from_ymd_opt(2027, 6, 30)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+        );
+    }
+
+    #[test]
+    fn yearly_leap_day() {
+        let birthday = Reminder::new(Frequency::YEARLY, 2, 29, 2028, 8, 0, None, "x".to_string());
+        assert_eq!(
+            next_dates(&birthday, 5),
+            [
+                "2028-02-29 08:00",
+                "2029-02-28 08:00",
+                "2030-02-28 08:00",
+                "2031-02-28 08:00",
+                "2032-02-29 08:00"
+            ]
+        );
+        let once = Reminder::new(Frequency::ONCE, 2, 29, 2028, 8, 0, None, "x".to_string());
+        assert_eq!(once.next_occurrence(once.to_naive().unwrap()), None);
+    }
+
+    #[test]
+    fn late_sends_keep_the_schedule() {
+        let at = |day: u32, hour: u32, minute: u32| {
+            NaiveDate::from_ymd_opt(2026, 11, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        // Monday the 2nd at 09:00, sent on Wednesday after the machine woke up
+        let weekly = Reminder::new(Frequency::WEEKLY, 11, 2, 2026, 9, 0, None, "x".to_string());
+        assert_eq!(weekly.next_occurrence(at(4, 13, 7)), Some(at(9, 9, 0)));
+        assert_eq!(weekly.next_occurrence(at(9, 9, 0)), Some(at(16, 9, 0)));
+
+        let daily = Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 0, None, "x".to_string());
+        assert_eq!(daily.next_occurrence(at(2, 9, 1)), Some(at(3, 9, 0)));
+        assert_eq!(daily.next_occurrence(at(2, 8, 0)), Some(at(2, 9, 0)));
+
+        let mut ndays = Reminder::new(
+            Frequency::NDAYS,
+            11,
+            8,
+            2026,
+            9,
+            0,
+            Some(3),
+            "x".to_string(),
+        );
+        ndays.start = NaiveDate::from_ymd_opt(2026, 11, 2);
+        assert_eq!(ndays.next_occurrence(at(9, 0, 0)), Some(at(11, 9, 0)));
+        assert_eq!(ndays.next_occurrence(at(11, 9, 0)), Some(at(14, 9, 0)));
+    }
+
+    #[test]
+    fn missed_occurrences() {
+        let daily = Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 0, None, "x".to_string());
+        let until = |day: u32| {
+            NaiveDate::from_ymd_opt(2026, 11, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        assert_eq!(daily.occurrences_until(until(1), 10), []);
+        assert_eq!(daily.occurrences_until(until(2), 10).len(), 1);
+        let missed = daily.occurrences_until(until(5), 10);
+        assert_eq!(missed.len(), 4);
+        assert_eq!(missed[3], until(5).date().and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(daily.occurrences_until(until(30), 10).len(), 10);
+
+        let mut counted = daily.clone();
+        counted.remaining = Some(3);
+        assert_eq!(counted.occurrences_until(until(30), 10).len(), 3);
+
+        let once = Reminder::new(Frequency::ONCE, 11, 2, 2026, 9, 0, None, "x".to_string());
+        assert_eq!(once.occurrences_until(until(5), 10).len(), 1);
+        assert_eq!("Summary".parse(), Ok(CatchUp::Summary));
+        assert!("later".parse::<CatchUp>().is_err());
+    }
+
+    #[test]
+    fn interval_rule_keeps_its_phase() {
+        let mut gym = Reminder::new(Frequency::RULE, 11, 2, 2026, 9, 0, None, "x".to_string());
+        gym.rule = Some("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH".parse().unwrap());
+        gym.start = NaiveDate::from_ymd_opt(2026, 11, 2);
+
+        // Sent a day and a few hours late each time, then stored at the next occurrence
+        let mut sent = Vec::new();
+        for _ in 0..5 {
+            let late = gym.to_naive().unwrap() + chrono::Duration::hours(27);
+            let next = gym.next_occurrence(late).unwrap();
+            sent.push(next.format("%Y-%m-%d %H:%M").to_string());
+            gym.month = next.month() as u8;
+            gym.day = next.day() as u8;
+            gym.year = next.year() as u32;
+        }
+        assert_eq!(
+            sent,
+            [
+                "2026-11-05 09:00",
+                "2026-11-16 09:00",
+                "2026-11-19 09:00",
+                "2026-11-30 09:00",
+                "2026-12-03 09:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_after_last_date() {
+        let mut course = Reminder::new(Frequency::RULE, 11, 2, 2026, 8, 0, None, "x".to_string());
+        course.rule = Some("FREQ=HOURLY;INTERVAL=8".parse().unwrap());
+        course.until = NaiveDate::from_ymd_opt(2026, 11, 3);
+        let at = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2026, 11, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        assert_eq!(course.next_occurrence(at(3, 8)), Some(at(3, 16)));
+        assert_eq!(course.next_occurrence(at(3, 16)), None);
+        assert_eq!(course.occurrences_until(at(30, 0), 100).len(), 5);
+    }
+
+    #[test]
+    fn alerts() {
+        let mut meeting = Reminder::new(Frequency::ONCE, 11, 2, 2026, 9, 0, None, "x".to_string());
+        meeting.zone = Some(crate::zone::parse_zone("UTC").unwrap());
+        meeting.alerts = vec![30, 1440];
+        let at = |day: u32, hour: u32, minute: u32| {
+            Utc.with_ymd_and_hms(2026, 11, day, hour, minute, 0)
+                .unwrap()
+        };
+
+        assert_eq!(meeting.next_alert(at(1, 0, 0)), Some((at(1, 9, 0), 1440)));
+        assert_eq!(meeting.next_alert(at(1, 9, 0)), Some((at(2, 8, 30), 30)));
+        assert_eq!(meeting.next_alert(at(2, 8, 30)), None);
+
+        assert_eq!(meeting.alert_due(at(1, 8, 0)), None);
+        assert_eq!(meeting.alert_due(at(1, 10, 0)), Some(1440));
+        assert_eq!(meeting.alert_due(at(2, 8, 45)), Some(30));
+        assert_eq!(meeting.alert_due(at(2, 9, 0)), None);
+
+        assert_eq!(reminder::describe_lead(30), "30 min");
+        assert_eq!(reminder::describe_lead(120), "2 h");
+        assert_eq!(reminder::describe_lead(2880), "2 days");
+    }
+
+    #[test]
+    fn alerts_within_the_interval() {
+        let mut daily = Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 0, None, "x".to_string());
+        daily.alerts = vec![30, 1440];
+        assert_eq!(daily.validate(), Ok(()));
+        daily.alerts = vec![30, 1441];
+        let err = daily.validate().unwrap_err();
+        assert_eq!(
+            err,
+            ValidationError::AlertBeyondInterval {
+                lead: 1441,
+                interval: 1440
+            }
+        );
+        assert!(err.to_string().ends_with("recurs every 1 day"));
+
+        // The shortest time between occurrences counts, here Monday to Thursday
+        let mut gym = Reminder::new(Frequency::RULE, 11, 2, 2026, 18, 0, None, "x".to_string());
+        gym.rule = Some("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH".parse().unwrap());
+        gym.alerts = vec![3 * 1440];
+        assert_eq!(gym.validate(), Ok(()));
+        gym.alerts = vec![4 * 1440];
+        assert!(gym.validate().is_err());
+
+        // Months are as short as February
+        let mut rent = Reminder::new(Frequency::MONTHLY, 1, 31, 2027, 9, 0, None, "x".to_string());
+        rent.alerts = vec![28 * 1440];
+        assert_eq!(rent.validate(), Ok(()));
+        rent.alerts = vec![29 * 1440];
+        assert!(rent.validate().is_err());
+
+        // A reminder sent once, or with one occurrence left, has no interval
+        let mut once = Reminder::new(Frequency::ONCE, 11, 2, 2026, 9, 0, None, "x".to_string());
+        once.alerts = vec![30 * 1440];
+        assert_eq!(once.validate(), Ok(()));
+        daily.remaining = Some(1);
+        assert_eq!(daily.validate(), Ok(()));
+    }
+
+    #[test]
+    fn occurrences_in_a_window() {
+        let mut daily = Reminder::new(Frequency::DAILY, 11, 2, 2026, 9, 0, None, "x".to_string());
+        daily.zone = Some(crate::zone::parse_zone("America/New_York").unwrap());
+        daily.remaining = Some(4);
+        let at = |day: u32, hour: u32| Utc.with_ymd_and_hms(2026, 11, day, hour, 0, 0).unwrap();
+
+        // 09:00 in New York is 14:00 UTC, and the first occurrence counts against those left
+        assert_eq!(
+            daily.occurrences_between(at(3, 0), at(9, 0), 10),
+            vec![at(3, 14), at(4, 14), at(5, 14)]
+        );
+        assert_eq!(
+            daily.occurrences_between(at(2, 0), at(9, 0), 2),
+            vec![at(2, 14), at(3, 14)]
+        );
+        assert!(daily
+            .occurrences_between(at(2, 15), at(3, 13), 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn occurrences_in_a_distant_window() {
+        let mut frequent = Reminder::new(Frequency::RULE, 1, 1, 2000, 0, 0, None, "x".to_string());
+        frequent.rule = Some("FREQ=HOURLY".parse().unwrap());
+        let at = |year: i32, hour: u32| Utc.with_ymd_and_hms(year, 1, 1, hour, 0, 0).unwrap();
+
+        // Without a count, centuries of hours before the window are skipped rather than walked
+        assert_eq!(
+            frequent.occurrences_between(at(2200, 0), at(2200, 2), 10),
+            vec![at(2200, 0), at(2200, 1), at(2200, 2)]
+        );
+
+        // With one, they still use it up, but walking them stops after MAX_STEPS
+        frequent.remaining = Some(u32::MAX);
+        assert!(frequent
+            .occurrences_between(at(2200, 0), at(2200, 2), 10)
+            .is_empty());
+    }
+}
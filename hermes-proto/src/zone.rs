@@ -0,0 +1,110 @@
+// Module turning the wall-clock times of reminders into instants, in a named zone or local time
+
+use chrono::prelude::*;
+use chrono::{Duration, LocalResult};
+pub use chrono_tz::Tz;
+
+// Accepts IANA names such as "America/New_York" or "UTC", ignoring case
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    let name = name.trim();
+    return chrono_tz::TZ_VARIANTS
+        .iter()
+        .find(|zone| zone.name().eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| format!("Unknown time zone \"{}\", expected e.g. Europe/Paris", name));
+}
+
+/*
+* The instant a wall-clock time happens in `zone`, or in the local zone of the computer when
+* None. When the clocks are set back the time happens twice, and the first is used. When they
+* are set forward it doesn't happen at all, and is moved on by the length of the gap, so 02:30
+* on the night summer time starts becomes 03:30.
+*/
+pub fn resolve(zone: Option<Tz>, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    return match zone {
+        Some(zone) => resolve_in(&zone, time),
+        None => resolve_in(&Local, time),
+    };
+}
+
+fn resolve_in<Z: TimeZone>(zone: &Z, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    return match zone.from_local_datetime(&time) {
+        LocalResult::Single(instant) => Some(instant.with_timezone(&Utc)),
+        LocalResult::Ambiguous(first, _) => Some(first.with_timezone(&Utc)),
+        LocalResult::None => {
+            // Read with the offset from before the gap, which lands as far past it
+            let before = zone
+                .from_local_datetime(&(time - Duration::days(1)))
+                .earliest()?;
+            let offset = Duration::seconds(before.offset().fix().local_minus_utc() as i64);
+            Some(Utc.from_utc_datetime(&(time - offset)))
+        }
+    };
+}
+
+// What the clocks of `zone` read at an instant, or those of the computer when None
+pub fn wall_time(zone: Option<Tz>, instant: DateTime<Utc>) -> NaiveDateTime {
+    return match zone {
+        Some(zone) => instant.with_timezone(&zone).naive_local(),
+        None => instant.with_timezone(&Local).naive_local(),
+    };
+}
+
+/*
+* The local zone of the computer, as its offsets from UTC at `now` and half a year on, which tells
+* zones with and without summer time apart. A different answer than before means it was changed.
+*/
+pub fn local_offsets(now: DateTime<Utc>) -> (i32, i32) {
+    let offset = |instant: DateTime<Utc>| {
+        instant
+            .with_timezone(&Local)
+            .offset()
+            .fix()
+            .local_minus_utc()
+    };
+    return (offset(now), offset(now + Duration::days(182)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zone::{parse_zone, resolve, wall_time};
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        return NaiveDate::from_ymd_opt(2026, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn zone_names() {
+        assert_eq!(
+            parse_zone("america/new_york").unwrap().name(),
+            "America/New_York"
+        );
+        assert_eq!(parse_zone(" UTC ").unwrap().name(), "UTC");
+        assert!(parse_zone("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn offsets_follow_the_date() {
+        let new_york = Some(parse_zone("America/New_York").unwrap());
+        let winter = resolve(new_york, at(1, 15, 9, 0)).unwrap();
+        assert_eq!(winter.naive_utc(), at(1, 15, 14, 0));
+        let summer = resolve(new_york, at(7, 15, 9, 0)).unwrap();
+        assert_eq!(summer.naive_utc(), at(7, 15, 13, 0));
+        assert_eq!(wall_time(new_york, summer), at(7, 15, 9, 0));
+    }
+
+    #[test]
+    fn clock_changes() {
+        // Summer time starts at 02:00 on March 8th 2026, and ends at 02:00 on November 1st
+        let new_york = Some(parse_zone("America/New_York").unwrap());
+        let gap = resolve(new_york, at(3, 8, 2, 30)).unwrap();
+        assert_eq!(wall_time(new_york, gap), at(3, 8, 3, 30));
+
+        let overlap = resolve(new_york, at(11, 1, 1, 30)).unwrap();
+        assert_eq!(overlap.naive_utc(), at(11, 1, 5, 30));
+    }
+}
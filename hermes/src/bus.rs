@@ -0,0 +1,103 @@
+// Module keeping the connection to the D-Bus session bus, connecting again whenever it is lost
+
+use chrono::{DateTime, Utc};
+use dbus::blocking::Connection;
+use dbus::channel::{BusType, Channel};
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// Wait after the first failed attempt, doubled after each one that follows up to MAX_RETRY
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+pub struct Bus {
+    conn: Option<Connection>,
+    retry_in: Duration,
+    retry_at: Instant,
+    // When the bus was lost and why, kept until connected again
+    lost: Option<(DateTime<Utc>, String)>,
+}
+
+// Not connected yet, the first attempt being due straight away
+impl Default for Bus {
+    fn default() -> Self {
+        return Bus {
+            conn: None,
+            retry_in: FIRST_RETRY,
+            retry_at: Instant::now(),
+            lost: None,
+        };
+    }
+}
+
+impl Bus {
+    pub fn conn(&self) -> Option<&Connection> {
+        return self.conn.as_ref();
+    }
+
+    pub fn lost(&self) -> Option<&(DateTime<Utc>, String)> {
+        return self.lost.as_ref();
+    }
+
+    // Time left until the next attempt to connect, None while connected
+    pub fn until_retry(&self) -> Option<Duration> {
+        if self.conn.is_some() {
+            return None;
+        }
+        return Some(self.retry_at.saturating_duration_since(Instant::now()));
+    }
+
+    /*
+     * Drops a connection the bus has closed, such as when it was restarted, then connects once
+     * the wait since the last failed attempt is over. Returns true when this connected, or found
+     * the bus gone where it was not known to be, so the change can be acted on.
+     */
+    pub fn reconnect(&mut self, mut log: &File) -> bool {
+        let mut changed = false;
+        if let Some(conn) = &self.conn {
+            if conn.channel().is_connected() {
+                return false;
+            }
+            self.conn = None;
+            self.lost = Some((Utc::now(), "Disconnected from the session bus".to_string()));
+            self.retry_in = FIRST_RETRY;
+            self.retry_at = Instant::now();
+            let _ = log.write_all(b"Lost the D-Bus session bus\n");
+            changed = true;
+        }
+        if Instant::now() < self.retry_at {
+            return changed;
+        }
+
+        match connect() {
+            Ok(conn) => {
+                self.conn = Some(conn);
+                self.lost = None;
+                self.retry_in = FIRST_RETRY;
+                let _ = log.write_all(b"Connected to the D-Bus session bus\n");
+                return true;
+            }
+            Err(err) => {
+                let reason = err.message().unwrap_or("Unknown error").to_string();
+                // Only the first failure is logged, rather than every attempt while it is gone
+                if self.lost.is_none() {
+                    let fmt_str = format!("Failed to connect to D-Bus: {}\n", reason);
+                    let _ = log.write_all(fmt_str.as_bytes());
+                    self.lost = Some((Utc::now(), reason));
+                    changed = true;
+                }
+                self.retry_at = Instant::now() + self.retry_in;
+                self.retry_in = (self.retry_in * 2).min(MAX_RETRY);
+                return changed;
+            }
+        }
+    }
+}
+
+// Connects to the session bus, exposing the descriptor Scheduler::wait listens on
+fn connect() -> Result<Connection, dbus::Error> {
+    let mut channel = Channel::get_private(BusType::Session)?;
+    channel.set_watch_enabled(true);
+    return Ok(Connection::from(channel));
+}
@@ -1,194 +1,1398 @@
-use crate::{db, reminder};
-use dbus::arg::messageitem::MessageItem;
+use crate::db::{self, DeliveryKind};
+use crate::notifier::{Attempts, Notice, Notifiers};
+use crate::quiet::Delivery;
+use crate::scheduler;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Timelike, Utc};
 use dbus::blocking::Connection;
-use dbus::channel::Sender;
-use dbus::message as msg;
+use dbus::message::{self as msg, MatchRule};
+use hermes_proto::protocol::{self, Command, DoNotDisturb, Request, Response, Selector, Status};
+use hermes_proto::reminder::{self, CatchUp, Frequency, Pause, Sink};
+use hermes_proto::zone;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{fs::File, io::Write};
-use zmq::{self, Socket};
+use zmq;
+
+pub const SNOOZE_MINUTES: u32 = 10;
+pub const SNOOZE_ACTION: &str = "snooze";
+pub const DISMISS_ACTION: &str = "dismiss";
+// Reason given by NotificationClosed when the user closed the notification
+const CLOSED_BY_USER: u32 = 2;
+// How long a sent notification's actions are answered, notification servers may keep them around
+pub const SHOWN_FOR: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+// Sent reminders by notification id, with their reminder id and when they were sent
+pub type Shown = Arc<Mutex<HashMap<u32, (u32, reminder::Reminder, Instant)>>>;
 
 pub fn handle_message(
-    data: &Vec<Vec<u8>>,
+    data: &[Vec<u8>],
     log: &mut File,
     api_statements: &mut db::PreparedStatements,
     socket: &zmq::Socket,
+    waker: &scheduler::Waker,
 ) {
-    if data.len() < 2 {
-        let _ = log.write_all(b"Received Message of invalid part count\n");
-        return;
+    let response = match protocol::Request::from_frames(data) {
+        Err(e) => {
+            let fmt_str = format!("Rejected message: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            Response::error(e.request_id(), e.status(), &e.to_string())
+        }
+        Ok(request) => match request.command {
+            Command::Add => {
+                let _ = log.write_all(b"RECEIVED ADD COMMAND\n");
+                add_reminder(&request, api_statements, log)
+            }
+            Command::List => {
+                let _ = log.write_all(b"RECEIVED LIST COMMAND\n");
+                list_reminders(&request, api_statements, log)
+            }
+            Command::Delete => {
+                let _ = log.write_all(b"RECEIVED DELETE COMMAND\n");
+                handle_delete(&request, api_statements, log)
+            }
+            Command::Update => {
+                let _ = log.write_all(b"RECEIVED UPDATE COMMAND\n");
+                handle_update(&request, api_statements, log)
+            }
+            Command::Snooze => {
+                let _ = log.write_all(b"RECEIVED SNOOZE COMMAND\n");
+                handle_snooze(&request, api_statements, log)
+            }
+            Command::Ack => {
+                let _ = log.write_all(b"RECEIVED ACK COMMAND\n");
+                handle_ack(&request, api_statements, log)
+            }
+            Command::Pause => {
+                let _ = log.write_all(b"RECEIVED PAUSE COMMAND\n");
+                handle_pause(&request, api_statements, log)
+            }
+            Command::Resume => {
+                let _ = log.write_all(b"RECEIVED RESUME COMMAND\n");
+                handle_resume(&request, api_statements, log)
+            }
+            Command::DoNotDisturb => {
+                let _ = log.write_all(b"RECEIVED DO NOT DISTURB COMMAND\n");
+                handle_dnd(&request, api_statements, log)
+            }
+            Command::Agenda => {
+                let _ = log.write_all(b"RECEIVED AGENDA COMMAND\n");
+                handle_agenda(&request, api_statements, log)
+            }
+            Command::Status => {
+                let _ = log.write_all(b"RECEIVED STATUS COMMAND\n");
+                handle_status(&request, api_statements, log)
+            }
+        },
+    };
+
+    // The notifier looks at the reminders again, as they may have changed
+    if response.status == Status::Ok {
+        waker.wake();
     }
 
-    let valid_header = validate_header(&data[0], log);
-    if !valid_header {
-        return; // Already logged
+    // REP sockets must answer every request exactly once
+    if let Err(e) = socket.send_multipart(response.to_frames(), 0) {
+        let fmt_str = format!("Error sending response: {}\n", e);
+        let _ = log.write_all(fmt_str.as_bytes());
     }
+}
+
+// An occurrence this far in the past when found was missed, rather than only sent a little late
+const MISSED_AFTER_MINUTES: i64 = 5;
+// Missed occurrences counted for a summary, and the most sent one by one
+const MAX_COUNTED: usize = 10000;
+const MAX_CAUGHT_UP: usize = 24;
+const MISSED_FORMAT: &str = "%a %Y-%m-%d %H:%M";
+
+// A notification to send for a due reminder, and when the occurrence it stands for was scheduled
+pub struct Occurrence {
+    pub notice: reminder::Reminder,
+    pub scheduled: Option<DateTime<Utc>>,
+}
 
-    let command = &data[1];
-    if command.len() != 1 {
-        let _ = log.write_all(b"Invalid Command code received\n");
+/*
+* The notifications to send for a due reminder. Occurrences that passed while Hermes was not
+* running, or the computer was asleep, are sent as the reminder's catch-up policy says, or
+* `default` for a reminder without one. An occurrence that is on time is always sent. A snooze or
+* repeat stands for the occurrence last sent.
+*/
+pub fn catch_up(due: &db::Due, default: CatchUp, now: DateTime<Utc>) -> Vec<Occurrence> {
+    if !due.scheduled {
+        let notice = due.reminder.clone();
+        return vec![Occurrence {
+            notice,
+            scheduled: due.occurrence,
+        }];
     }
-    match command[0] {
-        1 => {
-            // add
-            let _ = socket.send("RECEIVED", 0);
-            if data.len() != 3 {
-                let _ = log.write_all(b"Received Message of invalid part count\n");
-                return;
+    let now = due.reminder.wall_time(now);
+    let instant = |time: &NaiveDateTime| zone::resolve(due.reminder.zone, *time);
+    let mut missed = due.reminder.occurrences_until(now, MAX_COUNTED);
+    let on_time = match missed.last() {
+        Some(last) if now - *last < Duration::minutes(MISSED_AFTER_MINUTES) => {
+            missed.pop().map(|time| instant(&time))
+        }
+        Some(_) => None,
+        None => Some(due.reminder.to_instant()),
+    };
+
+    let noting = |note: String, time: &NaiveDateTime| {
+        let mut notice = due.reminder.clone();
+        notice.message = format!("{}: {}", note, notice.message);
+        Occurrence {
+            notice,
+            scheduled: instant(time),
+        }
+    };
+    // Once, or a summary, stands for the latest occurrence missed
+    let mut notices = Vec::new();
+    match (due.reminder.catch_up.unwrap_or(default), missed.last()) {
+        (_, None) => {}
+        (CatchUp::Once, _) if on_time.is_some() => {}
+        (CatchUp::Once, Some(last)) => notices.push(Occurrence {
+            notice: due.reminder.clone(),
+            scheduled: instant(last),
+        }),
+        (CatchUp::All, _) => {
+            let first = missed.len().saturating_sub(MAX_CAUGHT_UP);
+            for time in &missed[first..] {
+                notices.push(noting(
+                    format!("Missed {}", time.format(MISSED_FORMAT)),
+                    time,
+                ));
             }
-            let reminder = reminder::Reminder::deserialize_reminder(&data[2], log);
-            if reminder.is_none() {
-                let _ = log.write_all(b"Could not deserialize reminder\n");
+        }
+        (CatchUp::Summary, Some(last)) if missed.len() == 1 => {
+            notices.push(noting(
+                format!("Missed {}", last.format(MISSED_FORMAT)),
+                last,
+            ));
+        }
+        (CatchUp::Summary, Some(last)) => notices.push(noting(
+            format!(
+                "Missed {} times since {}",
+                missed.len(),
+                missed[0].format(MISSED_FORMAT)
+            ),
+            last,
+        )),
+        (CatchUp::Skip, _) => {}
+    }
+    if let Some(scheduled) = on_time {
+        let notice = due.reminder.clone();
+        notices.push(Occurrence { notice, scheduled });
+    }
+    return notices;
+}
+
+/*
+* A notification worked out while the database is locked, and sent once it is let go, so a slow
+* notifier holds up neither the database nor the log. What became of it is settled afterwards.
+*/
+pub enum Outgoing {
+    /*
+     * A reminder's notification for its `number`th occurrence, scheduled at `scheduled`, replacing
+     * the desktop notification with id `replaces` unless 0, and kept for whichever notifiers fail
+     * as due at `due`
+     */
+    Notification {
+        id: u32,
+        reminder: reminder::Reminder,
+        scheduled: Option<DateTime<Utc>>,
+        number: Option<u32>,
+        due: DateTime<Utc>,
+        replaces: u32,
+    },
+    // One held back during quiet hours, forgotten once sent or kept
+    Held(db::Deferred),
+    // Several held back, as one notification listing them all, with the reminder it goes out as
+    Digest(Vec<db::Deferred>, reminder::Reminder),
+    // One that failed before, sent again through just the sinks it failed
+    Retry(db::Deferred),
+    // A warning that the reminder comes in `lead` minutes
+    Alert {
+        id: u32,
+        reminder: reminder::Reminder,
+        lead: u32,
+    },
+}
+
+impl Outgoing {
+    fn kind(&self) -> DeliveryKind {
+        return match self {
+            Outgoing::Notification { .. } | Outgoing::Held(_) => DeliveryKind::Notification,
+            Outgoing::Digest(..) => DeliveryKind::Digest,
+            Outgoing::Retry(_) => DeliveryKind::Retry,
+            Outgoing::Alert { .. } => DeliveryKind::Alert,
+        };
+    }
+
+    fn notice(&self) -> Notice<'_> {
+        return match self {
+            Outgoing::Notification {
+                id,
+                reminder,
+                scheduled,
+                number,
+                replaces,
+                ..
+            } => reminder_notice(*id, reminder, *replaces, *scheduled, *number),
+            Outgoing::Held(deferred) | Outgoing::Retry(deferred) => held_notice(deferred),
+            Outgoing::Digest(held, digest) => digest_notice(held, digest),
+            Outgoing::Alert { id, reminder, lead } => alert_notice(*id, reminder, *lead),
+        };
+    }
+}
+
+/*
+* The notifications held back during quiet hours, to send once they are over. When the settings
+* ask for a digest, several are sent as one listing them all.
+*/
+pub fn held(held: Vec<db::Deferred>, delivery: Delivery) -> Vec<Outgoing> {
+    if held.len() > 1 && delivery == Delivery::Digest {
+        let digest =
+            reminder::Reminder::new(Frequency::ONCE, 1, 1, 1970, 0, 0, None, String::new());
+        return vec![Outgoing::Digest(held, digest)];
+    }
+    return held.into_iter().map(Outgoing::Held).collect();
+}
+
+/*
+* The undelivered notifications whose next attempt is due to send again, oldest first. One still
+* waiting MAX_QUEUED_HOURS after it was due is dropped, as no longer worth showing. Those only for
+* the desktop are put off, untried, while there is no connection to it.
+*/
+pub fn retries(
+    undelivered: Vec<db::Deferred>,
+    notifiers: &Notifiers,
+    statements: &mut db::NotificationStatements,
+    mut log: &File,
+) -> Vec<Outgoing> {
+    let now = Utc::now();
+    let desktop_away = notifiers.bus().conn().is_none();
+    let mut retries = Vec::new();
+    for queued in undelivered {
+        if now - queued.due > Duration::hours(MAX_QUEUED_HOURS) {
+            let fmt_str = format!("Gave up sending reminder {}\n", queued.reminder);
+            let _ = log.write_all(fmt_str.as_bytes());
+            statements.delivered(queued.id, log);
+            continue;
+        }
+        let sinks = notifiers.sinks(&queued.notice);
+        if desktop_away && sinks.iter().all(|sink| *sink == Sink::Desktop) {
+            statements.retry_later(queued.id, &queued.notice, log);
+            continue;
+        }
+        retries.push(Outgoing::Retry(queued));
+    }
+    return retries;
+}
+
+// Sends the notification through its notifiers, without touching the database or the log
+pub fn send(outgoing: &Outgoing, notifiers: &Notifiers) -> Attempts {
+    return notifiers.send(&outgoing.notice());
+}
+
+/*
+* Records in the delivery table what each notifier made of a notification sent, and keeps it to
+* send again through those that failed. Held and retried ones are forgotten once every notifier
+* took them, a digest keeping each reminder it lists for those that didn't. A warning that fails
+* isn't kept, as it would come late and the reminder follows. A desktop notification shown is
+* remembered so its actions are answered.
+*/
+pub fn settle(
+    outgoing: Outgoing,
+    attempts: Attempts,
+    shown: &Shown,
+    statements: &mut db::NotificationStatements,
+    mut log: &File,
+) {
+    let kind = outgoing.kind();
+    let notice = outgoing.notice();
+    let mut notification = None;
+    let (mut failed, mut errors) = (Vec::new(), Vec::new());
+    for (sink, result) in attempts.iter() {
+        statements.record(kind, notice.id, notice.scheduled, *sink, result, log);
+        match result {
+            Ok(sent) => notification = notification.or(*sent),
+            Err(err) => {
+                failed.push(*sink);
+                errors.push(format!("{}: {}", sink, err));
+            }
+        }
+    }
+    if !failed.is_empty() && failed.len() == attempts.len() {
+        let fmt_str = match &outgoing {
+            Outgoing::Notification { id, .. }
+            | Outgoing::Held(db::Deferred { reminder: id, .. }) => {
+                format!("Failed to send reminder {}: {}\n", id, errors.join(", "))
+            }
+            Outgoing::Digest(..) => format!(
+                "Failed to send held back reminders: {}\n",
+                errors.join(", ")
+            ),
+            Outgoing::Retry(queued) => format!(
+                "Still can't send reminder {}: {}\n",
+                queued.reminder,
+                errors.join(", ")
+            ),
+            Outgoing::Alert { id, .. } => {
+                format!("Failed to warn of reminder {}: {}\n", id, errors.join(", "))
             }
-            let reminder = reminder.unwrap();
+        };
+        let _ = log.write_all(fmt_str.as_bytes());
+    } else {
+        for err in errors {
+            let fmt_str = format!("Failed to send to {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+        }
+    }
 
-            add_reminder(reminder, api_statements, log);
-            let _ = socket.send("RECEIVED", 0);
+    let show = |id: u32, reminder: &reminder::Reminder| {
+        if let Some(notification) = notification {
+            let sent = (id, reminder.clone(), Instant::now());
+            shown.lock().unwrap().insert(notification, sent);
+        }
+    };
+    match &outgoing {
+        Outgoing::Notification {
+            id, reminder, due, ..
+        } => {
+            keep(&notice, *due, &failed, statements, log);
+            show(*id, reminder);
         }
-        2 => {
-            // list
-            let _ = log.write_all(b"RECEIVED LIST COMMAND\n");
-            list_reminders(api_statements, socket, log);
-            let _ = socket.send("SUCCESS", 0);
+        Outgoing::Held(deferred) => {
+            keep(&notice, deferred.due, &failed, statements, log);
+            show(deferred.reminder, &deferred.notice);
+            statements.undefer(deferred.id, log);
         }
-        3 => {
-            // Delete
-            let _ = log.write_all(b"RECEIVED DELETE COMMAND\n");
-            handle_delete(api_statements, socket, log);
+        // Those a notifier missed are each kept to send again through it, as the digest can't be
+        Outgoing::Digest(held, _) => {
+            for deferred in held {
+                keep(
+                    &held_notice(deferred),
+                    deferred.due,
+                    &failed,
+                    statements,
+                    log,
+                );
+                statements.undefer(deferred.id, log);
+            }
+        }
+        Outgoing::Retry(queued) => {
+            show(queued.reminder, &queued.notice);
+            if failed.is_empty() {
+                statements.delivered(queued.id, log);
+            } else {
+                let mut retry = queued.notice.clone();
+                retry.sinks = failed.clone();
+                statements.retry_later(queued.id, &retry, log);
+            }
         }
-        _ => {}
+        Outgoing::Alert { .. } => {}
+    }
+}
+
+// Keeps a notification to send again through just the sinks that failed, as due when it first was
+fn keep(
+    notice: &Notice,
+    due: DateTime<Utc>,
+    failed: &[Sink],
+    statements: &mut db::NotificationStatements,
+    log: &File,
+) {
+    if let (Some(id), false) = (notice.id, failed.is_empty()) {
+        let mut retry = notice.reminder.clone();
+        retry.sinks = failed.to_vec();
+        let (scheduled, number) = (notice.scheduled, notice.occurrence);
+        statements.queue(id, &retry, due, scheduled, number, log);
+    }
+}
+
+// How long a notification no notifier could take is tried again
+const MAX_QUEUED_HOURS: i64 = 24;
+const DIGEST_TIME_FORMAT: &str = "%a %H:%M";
+// Milliseconds a notification stays on screen when its reminder doesn't say
+const DEFAULT_TIMEOUT: i32 = 3000;
+
+/*
+* The notification for a reminder's `number`th occurrence, scheduled at `scheduled`, replacing the
+* desktop notification with id `replaces` unless 0
+*/
+fn reminder_notice(
+    id: u32,
+    reminder: &reminder::Reminder,
+    replaces: u32,
+    scheduled: Option<DateTime<Utc>>,
+    number: Option<u32>,
+) -> Notice<'_> {
+    // Unless told otherwise, a persistent reminder stays on screen until the user acts on it
+    let timeout = match reminder.timeout {
+        Some(timeout) => timeout as i32,
+        None if reminder.renotify.is_some() => 0,
+        None => DEFAULT_TIMEOUT,
+    };
+    return Notice {
+        id: Some(id),
+        summary: reminder
+            .title
+            .clone()
+            .unwrap_or_else(|| "Hermes".to_string()),
+        body: reminder.message.clone(),
+        reminder,
+        snooze: true,
+        timeout,
+        replaces,
+        scheduled,
+        occurrence: number,
     };
 }
 
-// See https://specifications.freedesktop.org/notification-spec/notification-spec-latest.html for spec of commands
+// The notification for one held back or kept to send again
+fn held_notice(deferred: &db::Deferred) -> Notice<'_> {
+    let (scheduled, number) = (deferred.scheduled, deferred.number);
+    return reminder_notice(deferred.reminder, &deferred.notice, 0, scheduled, number);
+}
 
-pub fn notify(data: &Vec<u8>, conn: &Connection, mut log: &File) {
-    let reminder = reminder::Reminder::deserialize_reminder(data, log);
-    if reminder.is_none() {
-        return;
+// One notification listing the reminders held back, going out as `digest`
+fn digest_notice<'a>(held: &[db::Deferred], digest: &'a reminder::Reminder) -> Notice<'a> {
+    let mut lines = vec![format!("{} reminders during quiet hours:", held.len())];
+    for deferred in held.iter() {
+        lines.push(format!(
+            "{} {}",
+            zone::wall_time(None, deferred.due).format(DIGEST_TIME_FORMAT),
+            deferred.notice.message.trim_end()
+        ));
     }
+    return Notice {
+        id: None,
+        summary: "Hermes".to_string(),
+        body: lines.join("\n"),
+        reminder: digest,
+        snooze: false,
+        timeout: DEFAULT_TIMEOUT,
+        replaces: 0,
+        scheduled: None,
+        occurrence: None,
+    };
+}
 
-    let reminder = reminder.unwrap();
+/*
+* A warning that a reminder is coming in `lead` minutes, with its title or the first line of its
+* message in the summary. There is nothing to snooze, as the reminder itself is still to be sent,
+* at its next time, which the warning is for.
+*/
+fn alert_notice(id: u32, reminder: &reminder::Reminder, lead: u32) -> Notice<'_> {
+    let mut lines = reminder.message.trim_end().splitn(2, '\n');
+    let (title, body) = match &reminder.title {
+        Some(title) => (title.as_str(), reminder.message.as_str()),
+        None => (lines.next().unwrap_or(""), lines.next().unwrap_or("")),
+    };
+    return Notice {
+        id: Some(id),
+        summary: format!("In {}: {}", reminder::describe_lead(lead), title),
+        body: body.to_string(),
+        reminder,
+        snooze: false,
+        timeout: DEFAULT_TIMEOUT,
+        replaces: 0,
+        scheduled: reminder.to_instant(),
+        occurrence: None,
+    };
+}
 
-    let res = msg::Message::new_method_call(
-        "org.freedesktop.Notifications",
-        "/org/freedesktop/Notifications",
-        "org.freedesktop.Notifications",
-        "Notify",
+/*
+* Answers the actions of sent notifications, and notices the user closing them, from the
+* ActionInvoked and NotificationClosed signals received while the connection is processed.
+* Signals about notifications of other applications find nothing in `shown`.
+*/
+pub fn watch_notifications(
+    conn: &Connection,
+    shown: Shown,
+    database: Arc<Mutex<rusqlite::Connection>>,
+    log: Arc<Mutex<File>>,
+    waker: scheduler::Waker,
+) -> Result<(), dbus::Error> {
+    let (shown_closed, database_closed, log_closed, waker_closed) = (
+        Arc::clone(&shown),
+        Arc::clone(&database),
+        Arc::clone(&log),
+        waker.clone(),
     );
 
-    let mut dbus_msg = res.unwrap();
-    dbus_msg.append_items(&[
-        MessageItem::Str("Hermes".to_string()),
-        MessageItem::UInt32(0),
-        MessageItem::Str("".to_string()),
-        MessageItem::Str("Hermes".to_string()),
-        MessageItem::Str(reminder.get_message().to_string()),
-        MessageItem::new_array(vec![MessageItem::Str("".to_string())]).unwrap(),
-        MessageItem::new_dict(vec![(
-            MessageItem::Str("".to_string()),
-            MessageItem::Variant(Box::new(MessageItem::Str("".to_string()))),
-        )])
-        .unwrap(),
-        MessageItem::Int32(3000),
-    ]);
-
-    let _ = conn.send(dbus_msg);
-}
-
-fn validate_header(vec: &Vec<u8>, mut log: &File) -> bool {
-    let header = std::str::from_utf8(vec);
-    if let Err(e) = header {
-        let fmt_str = format!("Error decoding message header: {}\n", e);
-        let _ = log.write_all(fmt_str.as_bytes());
-        return false;
+    let rule = MatchRule::new_signal("org.freedesktop.Notifications", "ActionInvoked");
+    conn.add_match(
+        rule,
+        move |(notification, action): (u32, String), _: &Connection, _: &msg::Message| {
+            let sent = shown.lock().unwrap().remove(&notification);
+            if let Some((id, reminder, _)) = sent {
+                let mut log = log.lock().unwrap();
+                let db_conn = database.lock().unwrap();
+                match action.as_str() {
+                    SNOOZE_ACTION => snooze_sent(id, reminder, &db_conn, &mut log),
+                    DISMISS_ACTION => ack_sent(id, &db_conn, &mut log),
+                    _ => {}
+                }
+                waker.wake();
+            }
+            return true;
+        },
+    )?;
+
+    // An expired notification is still waiting for the user, so only closing it counts
+    let rule = MatchRule::new_signal("org.freedesktop.Notifications", "NotificationClosed");
+    conn.add_match(
+        rule,
+        move |(notification, reason): (u32, u32), _: &Connection, _: &msg::Message| {
+            let sent = shown_closed.lock().unwrap().remove(&notification);
+            if let Some((id, reminder, _)) = sent {
+                if reason == CLOSED_BY_USER && reminder.renotify.is_some() {
+                    let mut log = log_closed.lock().unwrap();
+                    let db_conn = database_closed.lock().unwrap();
+                    ack_sent(id, &db_conn, &mut log);
+                    waker_closed.wake();
+                }
+            }
+            return true;
+        },
+    )?;
+    return Ok(());
+}
+
+fn ack_sent(id: u32, db_conn: &rusqlite::Connection, log: &mut File) {
+    let api_statements = db::PreparedStatements::new(db_conn, log);
+    if api_statements.is_none() {
+        return;
+    }
+    if let Some(rows) = api_statements.unwrap().ack(id, log) {
+        if rows > 0 {
+            let fmt_str = format!("Reminder {} acknowledged\n", id);
+            let _ = log.write_all(fmt_str.as_bytes());
+        }
     }
+}
 
-    let header = header.unwrap();
-    if header != "HERMES" {
-        let fmt_str = format!("Invalid Header, received {}, expected: HERMES\n", header);
-        let _ = log.write_all(fmt_str.as_bytes());
-        return false;
+fn snooze_sent(
+    id: u32,
+    mut reminder: reminder::Reminder,
+    db_conn: &rusqlite::Connection,
+    log: &mut File,
+) {
+    let api_statements = db::PreparedStatements::new(db_conn, log);
+    if api_statements.is_none() {
+        return;
     }
+    let mut api_statements = api_statements.unwrap();
+
+    let until = Utc::now() + Duration::minutes(SNOOZE_MINUTES as i64);
+    let fmt_str = match api_statements.snooze(id, until, log) {
+        // A sent ONCE reminder is already deleted, so it is stored again
+        Some(0) if reminder.frequency == Frequency::ONCE => {
+            let wall = reminder.wall_time(until);
+            set_time(&mut reminder, wall);
+            match api_statements.add(reminder, log) {
+                Some(new_id) => format!("Snoozed reminder {} as reminder {}\n", id, new_id),
+                None => return,
+            }
+        }
+        Some(0) => format!("Reminder {} was removed before it could be snoozed\n", id),
+        Some(_) => format!("Snoozed reminder {}\n", id),
+        None => return,
+    };
+    let _ = log.write_all(fmt_str.as_bytes());
+}
 
-    return true;
+fn set_time(reminder: &mut reminder::Reminder, datetime: NaiveDateTime) {
+    reminder.month = datetime.month() as u8;
+    reminder.day = datetime.day() as u8;
+    reminder.year = datetime.year() as u32;
+    reminder.hour = datetime.hour() as u8;
+    reminder.minute = datetime.minute() as u8;
 }
 
 fn list_reminders(
+    request: &Request,
     api_statements: &mut db::PreparedStatements,
-    socket: &zmq::Socket,
     log: &mut File,
-) {
+) -> Response {
     let reminders = api_statements.list(log);
     if reminders.is_none() {
-        return;
+        return Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to read reminders, see log",
+        );
     }
-    let reminders = reminders.unwrap();
 
-    let mut msg_vec: Vec<Vec<u8>> = Vec::new();
-    // TODO: fill msg_vec
-    msg_vec.push("HERMES".as_bytes().to_vec());
-    for reminder in reminders {
-        msg_vec.push(reminder.1.serialize());
+    let payload = reminders
+        .unwrap()
+        .iter()
+        .map(|(id, reminder)| protocol::serialize_entry(*id, reminder))
+        .collect();
+    return Response::ok(request.request_id, payload);
+}
+
+fn add_reminder(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    if request.payload.len() != 1 {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Add expects exactly one reminder",
+        );
     }
+    let reminder = match read_reminder(&request.payload[0]) {
+        Ok(reminder) => reminder,
+        Err(e) => {
+            return Response::error(request.request_id, Status::InvalidReminder, &e);
+        }
+    };
 
-    let _ = socket.send_multipart(msg_vec, 0);
-    let mut buff = zmq::Message::new();
-    let _ = socket.recv(&mut buff, 0); //  To get response from server
+    return match api_statements.add(reminder, log) {
+        Some(id) => Response::ok(request.request_id, vec![protocol::encode_u32(id)]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to store reminder, see log",
+        ),
+    };
 }
 
-fn add_reminder(
-    reminder: reminder::Reminder,
+fn handle_update(
+    request: &Request,
     api_statements: &mut db::PreparedStatements,
     log: &File,
-) {
-    api_statements.add(reminder, log);
+) -> Response {
+    let id = match request.payload.as_slice() {
+        [id, _] => protocol::decode_u32(id),
+        _ => None,
+    };
+    if id.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Update expects a reminder id and a reminder",
+        );
+    }
+    let id = id.unwrap();
+
+    let reminder = match read_reminder(&request.payload[1]) {
+        Ok(reminder) => reminder,
+        Err(e) => {
+            return Response::error(request.request_id, Status::InvalidReminder, &e);
+        }
+    };
+
+    return match api_statements.update(id, &reminder, log) {
+        Some(0) => Response::error(
+            request.request_id,
+            Status::NotFound,
+            &format!("No reminder with id {}", id),
+        ),
+        Some(_) => Response::ok(request.request_id, vec![]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to update reminder, see log",
+        ),
+    };
 }
 
-fn handle_delete(
+fn handle_snooze(
+    request: &Request,
     api_statements: &mut db::PreparedStatements,
-    socket: &zmq::Socket,
-    log: &mut File,
-) {
-    //1. Get list, with numbers,
-    let reminders = api_statements.list(log);
+    log: &File,
+) -> Response {
+    let (id, minutes) = match request.payload.as_slice() {
+        [id, minutes] => (protocol::decode_u32(id), protocol::decode_u32(minutes)),
+        _ => (None, None),
+    };
+    if id.is_none() || minutes.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Snooze expects a reminder id and a number of minutes",
+        );
+    }
+    let (id, minutes) = (id.unwrap(), minutes.unwrap());
+
+    let until = Utc::now().checked_add_signed(Duration::minutes(minutes as i64));
+    if minutes == 0 || until.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            &format!("Cannot snooze for {} minutes", minutes),
+        );
+    }
+
+    return match api_statements.snooze(id, until.unwrap(), log) {
+        Some(0) => Response::error(
+            request.request_id,
+            Status::NotFound,
+            &format!("No reminder with id {}", id),
+        ),
+        Some(_) => Response::ok(request.request_id, vec![]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to snooze reminder, see log",
+        ),
+    };
+}
+
+fn handle_ack(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let id = match request.payload.as_slice() {
+        [id] => protocol::decode_u32(id),
+        _ => None,
+    };
+    if id.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Ack expects a reminder id",
+        );
+    }
+    let id = id.unwrap();
+
+    return match api_statements.ack(id, log) {
+        Some(0) => Response::error(
+            request.request_id,
+            Status::NotFound,
+            &format!("Reminder {} is not waiting to be acknowledged", id),
+        ),
+        Some(_) => Response::ok(request.request_id, vec![]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to acknowledge reminder, see log",
+        ),
+    };
+}
+
+fn handle_pause(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let (id, pause) = match request.payload.as_slice() {
+        [id, pause] => (protocol::decode_u32(id), Pause::deserialize(pause)),
+        _ => (None, None),
+    };
+    if id.is_none() || pause.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Pause expects a reminder id and a pause",
+        );
+    }
+    let (id, pause) = (id.unwrap(), pause.unwrap());
+
+    return match api_statements.pause(id, pause, log) {
+        Some(0) => Response::error(
+            request.request_id,
+            Status::NotFound,
+            &format!("No reminder with id {}", id),
+        ),
+        Some(_) => Response::ok(request.request_id, vec![]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to pause reminder, see log",
+        ),
+    };
+}
+
+fn handle_resume(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let id = match request.payload.as_slice() {
+        [id] => protocol::decode_u32(id),
+        _ => None,
+    };
+    if id.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Resume expects a reminder id",
+        );
+    }
+    let id = id.unwrap();
+
+    return match api_statements.resume(id, log) {
+        Some(0) => Response::error(
+            request.request_id,
+            Status::NotFound,
+            &format!("Reminder {} is not paused", id),
+        ),
+        Some(_) => Response::ok(request.request_id, vec![]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to resume reminder, see log",
+        ),
+    };
+}
+
+// Sets do not disturb when given, answering with the one in effect either way
+fn handle_dnd(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let dnd = match request.payload.as_slice() {
+        [] => None,
+        [dnd] => match DoNotDisturb::deserialize(dnd) {
+            Some(dnd) => Some(dnd),
+            None => {
+                return Response::error(
+                    request.request_id,
+                    Status::MalformedRequest,
+                    "Unreadable do not disturb state",
+                )
+            }
+        },
+        _ => {
+            return Response::error(
+                request.request_id,
+                Status::MalformedRequest,
+                "Do not disturb expects nothing, or the state to set",
+            )
+        }
+    };
+    if let Some(dnd) = dnd {
+        if !api_statements.set_dnd(dnd, log) {
+            return Response::error(
+                request.request_id,
+                Status::StorageFailure,
+                "Failed to set do not disturb, see log",
+            );
+        }
+    }
+
+    return match api_statements.dnd(log) {
+        Some(dnd) => Response::ok(request.request_id, vec![dnd.serialize()]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to read do not disturb, see log",
+        ),
+    };
+}
+
+// Answers how notifications are getting through, which the request can't change
+fn handle_status(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    if !request.payload.is_empty() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Status expects nothing",
+        );
+    }
+    return match api_statements.delivery_status(log) {
+        Some(status) => Response::ok(request.request_id, vec![status.serialize()]),
+        None => Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to read the state of delivery, see log",
+        ),
+    };
+}
+
+// Occurrences of one reminder listed in an agenda, so a reminder due every minute can't flood it
+const MAX_AGENDA_OCCURRENCES: usize = 1000;
+
+// Longest window an agenda covers, as every reminder is expanded across all of it
+const MAX_AGENDA_DAYS: i64 = 366;
+
+/*
+* Expands every reminder over a window, answering with its occurrences in the order they are due.
+* A snoozed occurrence is listed when it comes back, and a paused reminder from when it resumes,
+* its held occurrence included.
+*/
+fn handle_agenda(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let window = match request.payload.as_slice() {
+        [from, to] => protocol::decode_time(from).zip(protocol::decode_time(to)),
+        _ => None,
+    };
+    if window.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Agenda expects the times it starts and ends at",
+        );
+    }
+    let (from, to) = window.unwrap();
+    if to - from > Duration::days(MAX_AGENDA_DAYS) {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            &format!("Agenda covers at most {} days", MAX_AGENDA_DAYS),
+        );
+    }
+
+    let reminders = api_statements.agenda(log);
     if reminders.is_none() {
-        return;
+        return Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to read reminders, see log",
+        );
     }
-    let reminders = reminders.unwrap();
-    // 2. Send to client
-    let mut msg_vec: Vec<Vec<u8>> = vec!["HERMES".as_bytes().to_vec()];
-    for (id, reminder) in reminders {
-        let mut vec: Vec<u8> = id.to_be_bytes().to_vec();
-        vec.extend(reminder.serialize());
-        msg_vec.push(vec);
+
+    let mut occurrences = Vec::new();
+    for planned in reminders.unwrap() {
+        let reminder = &planned.reminder;
+        let mut start = from;
+        let mut times = Vec::new();
+        match reminder.paused {
+            Some(Pause { until: None, .. }) => continue,
+            Some(Pause {
+                until: Some(until),
+                hold,
+            }) => {
+                start = start.max(until);
+                let held = reminder.to_instant().is_some_and(|at| at < until);
+                if hold && held && from <= until && until <= to {
+                    times.push(until);
+                }
+            }
+            None => {}
+        }
+        times.extend(planned.snoozed_until.filter(|at| start <= *at && *at <= to));
+        times.extend(reminder.occurrences_between(start, to, MAX_AGENDA_OCCURRENCES));
+        occurrences.extend(
+            times
+                .into_iter()
+                .map(|at| (at, planned.id, reminder.clone())),
+        );
     }
+    occurrences.sort_by_key(|(at, id, _)| (*at, *id));
 
-    let res = socket.send_multipart(msg_vec, 0);
-    if let Err(e) = res {
-        let fmt_str = format!("Error sending list of reminders: {}", e);
-        let _ = log.write_all(fmt_str.as_bytes());
-        return;
+    let payload = occurrences
+        .iter()
+        .map(|(at, id, reminder)| protocol::serialize_occurrence(*at, *id, reminder))
+        .collect();
+    return Response::ok(request.request_id, payload);
+}
+
+// Decodes a reminder sent by a client, applying the rules every stored reminder must meet
+fn read_reminder(bytes: &[u8]) -> Result<reminder::Reminder, String> {
+    let mut reminder =
+        reminder::Reminder::deserialize_reminder(bytes).map_err(|e| e.to_string())?;
+    reminder.validate().map_err(|e| e.to_string())?;
+    // A new schedule counts from the first time it is given
+    if reminder.start.is_none() {
+        reminder.start = reminder.to_naive().map(|time| time.date());
     }
-    //3. Receive number back from client
-    let mut buff = zmq::Message::new();
-    let _ = socket.recv(&mut buff, 0);
-    let data = buff.as_str();
-    //TODO: maybe send should loop until success? Or client should know to timeout
-    if data.is_none() {
-        let _ = socket.send("Invalid message received", 0);
-        return;
+    return Ok(reminder);
+}
+
+fn handle_delete(
+    request: &Request,
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Response {
+    let selectors: Option<Vec<Selector>> = request
+        .payload
+        .iter()
+        .map(|frame| Selector::deserialize(frame))
+        .collect();
+    if selectors.is_none() || request.payload.is_empty() {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Delete expects one or more selectors",
+        );
     }
-    let data = data.unwrap().trim().parse::<u32>();
-    if data.is_err() {
-        let _ = socket.send("Invalid message received: not an int", 0);
-        return;
+
+    let selectors = selectors.unwrap();
+    // Every message contains the empty text, so it would select everything
+    let empty_text = selectors.iter().any(|selector| match selector {
+        Selector::Matching(text) => text.trim().is_empty(),
+        _ => false,
+    });
+    if empty_text {
+        return Response::error(
+            request.request_id,
+            Status::MalformedRequest,
+            "Matching expects some text to look for",
+        );
     }
-    let data = data.unwrap();
-    //4. delete
-    let success = api_statements.delete(data, log);
-    //5. send success
-    if success {
-        let _ = socket.send("Successfully deleted", 0);
-    } else {
-        let _ = socket.send("Failed to delete, see log", 0);
+
+    let ids = select_reminders(&selectors, api_statements, log);
+    if ids.is_none() {
+        return Response::error(
+            request.request_id,
+            Status::StorageFailure,
+            "Failed to read reminders, see log",
+        );
+    }
+
+    let mut results = Vec::new();
+    for id in ids.unwrap() {
+        let status = match api_statements.delete(id, log) {
+            Some(0) => Status::NotFound,
+            Some(_) => Status::Ok,
+            None => Status::StorageFailure,
+        };
+        results.push(protocol::serialize_result(id, status));
+    }
+    return Response::ok(request.request_id, results);
+}
+
+// Resolves selectors into reminder ids, in the order given and without repeats
+fn select_reminders(
+    selectors: &[Selector],
+    api_statements: &mut db::PreparedStatements,
+    log: &File,
+) -> Option<Vec<u32>> {
+    let mut ids: Vec<u32> = Vec::new();
+    let mut reminders = None;
+    let now = Utc::now();
+
+    for selector in selectors {
+        if let Selector::Id(id) = selector {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+            continue;
+        }
+
+        // Filters need the stored reminders, which are read at most once
+        if reminders.is_none() {
+            reminders = Some(api_statements.list(log)?);
+        }
+        for (id, reminder) in reminders.as_ref().unwrap() {
+            let selected = match selector {
+                Selector::OnceExpired => {
+                    reminder.frequency == Frequency::ONCE
+                        && reminder.to_instant().is_some_and(|time| time <= now)
+                }
+                Selector::Matching(text) => reminder
+                    .message
+                    .to_lowercase()
+                    .contains(&text.to_lowercase()),
+                Selector::Id(_) => false,
+            };
+            if selected && !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+    }
+    return Some(ids);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::comm::{self, Outgoing, Shown};
+    use crate::config::Settings;
+    use crate::db::{Due, NotificationStatements, PreparedStatements};
+    use crate::migrations;
+    use crate::notifier::Notifiers;
+    use crate::quiet::Delivery;
+    use chrono::{DateTime, Duration, Utc};
+    use hermes_proto::reminder::{CatchUp, Frequency, Reminder, Sink};
+    use hermes_proto::zone;
+    use rusqlite::Connection;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+
+    // A database at the current version, logging nowhere
+    fn setup() -> (Connection, File) {
+        let log = File::create("/dev/null").unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(migrations::migrate(&mut conn, ":memory:", &log));
+        return (conn, log);
+    }
+
+    // The log as the notifier thread shares it
+    fn log_lock(log: &File) -> Arc<Mutex<File>> {
+        return Arc::new(Mutex::new(log.try_clone().unwrap()));
+    }
+
+    fn reminder(message: &str) -> Reminder {
+        return Reminder::new(
+            Frequency::ONCE,
+            11,
+            2,
+            2026,
+            9,
+            0,
+            None,
+            message.to_string(),
+        );
+    }
+
+    // Past the first retry of anything kept now
+    fn later() -> DateTime<Utc> {
+        return Utc::now() + Duration::minutes(2);
+    }
+
+    // Sent and settled, as a pass of the notifier thread does either side of letting the database go
+    fn deliver(
+        outgoing: Vec<Outgoing>,
+        notifiers: &Notifiers,
+        shown: &Shown,
+        statements: &mut NotificationStatements,
+        log: &File,
+    ) {
+        for outgoing in outgoing {
+            let attempts = comm::send(&outgoing, notifiers);
+            comm::settle(outgoing, attempts, shown, statements, log);
+        }
+    }
+
+    // The kind of each delivery recorded, in order
+    fn kinds(conn: &Connection) -> Vec<String> {
+        let mut select = conn
+            .prepare("SELECT kind FROM delivery ORDER BY id")
+            .unwrap();
+        let kinds = select.query_map([], |row| row.get(0)).unwrap();
+        return kinds.map(|kind| kind.unwrap()).collect();
+    }
+
+    // Without a session bus, the desktop notifier of the default settings fails every notice
+    #[test]
+    fn failed_notification_is_kept() {
+        let (conn, log) = setup();
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let notifiers = Notifiers::new(&Settings::default(), &log_lock(&log));
+        let shown: Shown = Arc::new(Mutex::new(HashMap::new()));
+
+        let due = Utc::now();
+        let notification = Outgoing::Notification {
+            id: 7,
+            reminder: reminder("Call home"),
+            scheduled: reminder("Call home").to_instant(),
+            number: Some(1),
+            due,
+            replaces: 0,
+        };
+        deliver(
+            vec![notification],
+            &notifiers,
+            &shown,
+            &mut statements,
+            &log,
+        );
+        let undelivered = statements.undelivered(later(), &log).unwrap();
+        assert_eq!(undelivered.len(), 1);
+        assert_eq!(undelivered[0].reminder, 7);
+        assert_eq!(undelivered[0].due.timestamp(), due.timestamp());
+        assert_eq!(undelivered[0].notice.sinks, vec![Sink::Desktop]);
+        assert_eq!(undelivered[0].scheduled, reminder("Call home").to_instant());
+        assert_eq!(undelivered[0].number, Some(1));
+        assert_eq!(kinds(&conn), vec!["notification"]);
+    }
+
+    #[test]
+    fn failed_alert_is_not_kept() {
+        let (conn, log) = setup();
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let notifiers = Notifiers::new(&Settings::default(), &log_lock(&log));
+        let shown: Shown = Arc::new(Mutex::new(HashMap::new()));
+
+        let alert = Outgoing::Alert {
+            id: 7,
+            reminder: reminder("Call home"),
+            lead: 15,
+        };
+        deliver(vec![alert], &notifiers, &shown, &mut statements, &log);
+        assert!(statements.undelivered(later(), &log).unwrap().is_empty());
+        assert_eq!(kinds(&conn), vec!["alert"]);
+    }
+
+    #[test]
+    fn failed_digest_keeps_each_held() {
+        let (conn, log) = setup();
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let notifiers = Notifiers::new(&Settings::default(), &log_lock(&log));
+        let shown: Shown = Arc::new(Mutex::new(HashMap::new()));
+        for (id, message) in [(1, "Call home"), (2, "Water the plants")] {
+            let due = Due {
+                id,
+                reminder: reminder(message),
+                scheduled: true,
+                fired: 0,
+                occurrence: None,
+            };
+            let scheduled = due.reminder.to_instant();
+            assert!(statements.defer(&due, &due.reminder, scheduled, &log));
+        }
+
+        let held = statements.deferred(&log).unwrap();
+        let digest = comm::held(held, Delivery::Digest);
+        assert_eq!(digest.len(), 1);
+        deliver(digest, &notifiers, &shown, &mut statements, &log);
+        assert!(statements.deferred(&log).unwrap().is_empty());
+        let kept: Vec<u32> = statements
+            .undelivered(later(), &log)
+            .unwrap()
+            .iter()
+            .map(|queued| queued.reminder)
+            .collect();
+        assert_eq!(kept, vec![1, 2]);
+        assert_eq!(kinds(&conn), vec!["digest"]);
+    }
+
+    #[test]
+    fn undelivered_backs_off() {
+        let (conn, log) = setup();
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let notifiers = Notifiers::new(&Settings::default(), &log_lock(&log));
+        let due = Utc::now() - Duration::minutes(30);
+        assert!(statements.queue(7, &reminder("Call home"), due, None, None, &log));
+        assert!(statements.undelivered(Utc::now(), &log).unwrap().is_empty());
+        let wait = |statements: &mut NotificationStatements| {
+            (statements.next_retry(&log).unwrap() - Utc::now()).num_seconds()
+        };
+        assert!((55..=60).contains(&wait(&mut statements)));
+
+        // Only for the desktop, which isn't there, so put off without being tried
+        for expected in [60, 120, 240] {
+            let undelivered = statements.undelivered(later(), &log).unwrap();
+            let retries = comm::retries(undelivered, &notifiers, &mut statements, &log);
+            assert!(retries.is_empty());
+            assert!((expected - 5..=expected).contains(&wait(&mut statements)));
+        }
+        assert!(kinds(&conn).is_empty());
+
+        // Until the desktop is back, keeping the time it was first due
+        assert!(statements.retry_now(&log));
+        let undelivered = statements.undelivered(Utc::now(), &log).unwrap();
+        assert_eq!(undelivered.len(), 1);
+        assert_eq!(undelivered[0].due.timestamp(), due.timestamp());
+    }
+
+    // Due times as the computer's zone gave them before it changed
+    #[test]
+    fn local_reminders_are_retimed() {
+        let (conn, log) = setup();
+        let mut prepared = PreparedStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let local = prepared.add(reminder("Call home"), &log).unwrap();
+        let mut zoned = reminder("Water the plants");
+        zoned.zone = Some(zone::parse_zone("UTC").unwrap());
+        let zoned = prepared.add(zoned, &log).unwrap();
+        conn.execute("UPDATE reminder SET due_at = 0", []).unwrap();
+
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        assert!(statements.retime_local(&log));
+        let due_at = |id: u32| -> i64 {
+            conn.query_row("SELECT due_at FROM reminder WHERE id = ?", [id], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        let expected = reminder("Call home").to_instant().unwrap().timestamp();
+        assert_eq!(due_at(local), expected);
+        // Its own zone doesn't move with the computer
+        assert_eq!(due_at(zoned), 0);
+    }
+
+    // The snooze of an occurrence is recorded as of that occurrence, not the reminder's next one
+    #[test]
+    fn snooze_keeps_its_occurrence() {
+        let (conn, log) = setup();
+        let notifiers = Notifiers::new(&Settings::default(), &log_lock(&log));
+        let shown: Shown = Arc::new(Mutex::new(HashMap::new()));
+        let mut prepared = PreparedStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let wall = zone::wall_time(None, Utc::now() - Duration::minutes(1));
+        let mut daily = reminder("Call home");
+        daily.frequency = Frequency::DAILY;
+        comm::set_time(&mut daily, wall);
+        let occurrence = daily.to_instant();
+        let id = prepared.add(daily, &log).unwrap();
+
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let pass = |statements: &mut NotificationStatements| {
+            let mut outgoing = Vec::new();
+            for due in statements.get_notifications(&log).unwrap() {
+                let notices = comm::catch_up(&due, CatchUp::All, Utc::now());
+                let last = notices.last().and_then(|occurrence| occurrence.scheduled);
+                for occurrence in notices {
+                    outgoing.push(Outgoing::Notification {
+                        id: due.id,
+                        reminder: occurrence.notice,
+                        scheduled: occurrence.scheduled,
+                        number: due.number(),
+                        due: Utc::now(),
+                        replaces: 0,
+                    });
+                }
+                statements.update_notification(due, true, last, &log);
+            }
+            deliver(outgoing, &notifiers, &shown, statements, &log);
+        };
+        pass(&mut statements);
+        drop(statements);
+        prepared.snooze(id, Utc::now() - Duration::minutes(1), &log);
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        pass(&mut statements);
+
+        let mut select = conn
+            .prepare("SELECT scheduled_at FROM delivery ORDER BY id")
+            .unwrap();
+        let recorded: Vec<Option<i64>> = select
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|scheduled| scheduled.unwrap())
+            .collect();
+        let occurrence = occurrence.map(|at| at.timestamp());
+        assert_eq!(recorded, vec![occurrence, occurrence]);
+    }
+
+    #[test]
+    fn retry_narrows_to_what_failed() {
+        let (conn, log) = setup();
+        let mut statements =
+            NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("hermes-retry-{}", std::process::id()));
+        let settings = Settings {
+            notify_file: path.to_string_lossy().to_string(),
+            ..Settings::default()
+        };
+        let notifiers = Notifiers::new(&settings, &log_lock(&log));
+        let shown: Shown = Arc::new(Mutex::new(HashMap::new()));
+        let mut queued = reminder("Call home");
+        queued.sinks = vec![Sink::File, Sink::Desktop];
+        assert!(statements.queue(7, &queued, Utc::now(), None, None, &log));
+
+        let undelivered = statements.undelivered(later(), &log).unwrap();
+        let retries = comm::retries(undelivered, &notifiers, &mut statements, &log);
+        assert_eq!(retries.len(), 1);
+        deliver(retries, &notifiers, &shown, &mut statements, &log);
+        let _ = std::fs::remove_file(&path);
+
+        let undelivered = statements
+            .undelivered(Utc::now() + Duration::hours(1), &log)
+            .unwrap();
+        assert_eq!(undelivered.len(), 1);
+        assert_eq!(undelivered[0].notice.sinks, vec![Sink::Desktop]);
+        assert_eq!(kinds(&conn), vec!["retry", "retry"]);
     }
 }
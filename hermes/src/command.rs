@@ -0,0 +1,215 @@
+// Module running the shell commands the user sets up, for the hook and the command notifier alike
+
+use chrono::{DateTime, Utc};
+use hermes_proto::reminder::Urgency;
+use std::fs::File;
+use std::io::Write;
+use std::process::{self, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often a command that may be killed is checked on
+const POLL: Duration = Duration::from_millis(100);
+
+/*
+* What a command is told of a reminder. Each field is both an environment variable, empty when it
+* doesn't apply, and a member of a JSON object on standard input, null when it doesn't:
+*   HERMES_ID          id          the reminder's id, none for a digest of several
+*   HERMES_SUMMARY     summary     the title of its notification
+*   HERMES_BODY        body        the text of its notification
+*   HERMES_SCHEDULED   scheduled   when the reminder was due, in RFC 3339
+*   HERMES_OCCURRENCE  occurrence  how many times the reminder has fired, counting this one
+*   HERMES_URGENCY     urgency     low, normal or critical, when the reminder sets one
+*   HERMES_CATEGORY    category    the category of its notification, when the reminder sets one
+*   HERMES_SENT        sent        when it was passed on, in RFC 3339
+*/
+pub struct Payload {
+    pub id: Option<u32>,
+    pub summary: String,
+    pub body: String,
+    pub scheduled: Option<DateTime<Utc>>,
+    pub occurrence: Option<u32>,
+    pub urgency: Option<Urgency>,
+    pub category: Option<String>,
+    pub sent: DateTime<Utc>,
+}
+
+impl Payload {
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let text = |value: Option<String>| value.unwrap_or_default();
+        return vec![
+            ("HERMES_ID", text(self.id.map(|id| id.to_string()))),
+            ("HERMES_SUMMARY", self.summary.clone()),
+            ("HERMES_BODY", self.body.clone()),
+            (
+                "HERMES_SCHEDULED",
+                text(self.scheduled.map(|at| at.to_rfc3339())),
+            ),
+            (
+                "HERMES_OCCURRENCE",
+                text(self.occurrence.map(|n| n.to_string())),
+            ),
+            ("HERMES_URGENCY", text(self.urgency.map(|u| u.to_string()))),
+            ("HERMES_CATEGORY", text(self.category.clone())),
+            ("HERMES_SENT", self.sent.to_rfc3339()),
+        ];
+    }
+
+    pub fn to_json(&self) -> String {
+        let text = |value: Option<String>| value.as_deref().map_or("null".to_string(), json_string);
+        let number = |value: Option<u32>| value.map_or("null".to_string(), |n| n.to_string());
+        return format!(
+            "{{\"id\":{},\"summary\":{},\"body\":{},\"scheduled\":{},\"occurrence\":{},\"urgency\":{},\"category\":{},\"sent\":{}}}",
+            number(self.id),
+            json_string(&self.summary),
+            json_string(&self.body),
+            text(self.scheduled.map(|at| at.to_rfc3339())),
+            number(self.occurrence),
+            text(self.urgency.map(|u| u.to_string())),
+            text(self.category.clone()),
+            json_string(&self.sent.to_rfc3339()),
+        );
+    }
+}
+
+// A JSON string literal holding `text`
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+/*
+* Starts `command` with `sh -c` and the payload in its environment and on its standard input. It
+* runs on its own, so a slow command never holds up reminders; one still running after `timeout`,
+* if there is one, is killed. How it ended is written to the log, after `name`.
+*/
+pub fn spawn(
+    command: &str,
+    payload: &Payload,
+    timeout: Option<Duration>,
+    name: String,
+    log: &Arc<Mutex<File>>,
+) -> Result<(), String> {
+    let mut child = process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(payload.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Error running {}: {}", command, err))?;
+    // A command that doesn't read its input only closes the pipe early
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(payload.to_json().as_bytes());
+    }
+
+    let log = Arc::clone(log);
+    thread::spawn(move || {
+        let started = Instant::now();
+        let ended = loop {
+            let waited = match timeout {
+                Some(_) => child.try_wait(),
+                None => child.wait().map(Some),
+            };
+            match waited {
+                Ok(Some(status)) => break format!("exited with {}", status),
+                Ok(None) if timeout.is_some_and(|timeout| started.elapsed() >= timeout) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break format!("killed after {}s", timeout.unwrap().as_secs());
+                }
+                Ok(None) => thread::sleep(POLL),
+                Err(err) => break format!("could not be waited on: {}", err),
+            }
+        };
+        let fmt_str = format!("{} {}\n", name, ended);
+        let _ = log.lock().unwrap().write_all(fmt_str.as_bytes());
+    });
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{json_string, Payload};
+    use chrono::{TimeZone, Utc};
+    use hermes_proto::reminder::Urgency;
+
+    fn payload() -> Payload {
+        return Payload {
+            id: Some(3),
+            summary: "Hermes".to_string(),
+            body: "Water the plants".to_string(),
+            scheduled: Some(Utc.with_ymd_and_hms(2026, 11, 2, 9, 0, 0).unwrap()),
+            occurrence: Some(12),
+            urgency: Some(Urgency::Critical),
+            category: None,
+            sent: Utc.with_ymd_and_hms(2026, 11, 2, 9, 0, 5).unwrap(),
+        };
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("say \"hi\"\\ bye"),
+            "\"say \\\"hi\\\"\\\\ bye\""
+        );
+        assert_eq!(json_string("a\nb\tc\r"), "\"a\\nb\\tc\\r\"");
+        assert_eq!(json_string("\u{1}\u{1f}"), "\"\\u0001\\u001f\"");
+        assert_eq!(json_string("café ☕"), "\"café ☕\"");
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            payload().to_json(),
+            "{\"id\":3,\"summary\":\"Hermes\",\"body\":\"Water the plants\",\
+             \"scheduled\":\"2026-11-02T09:00:00+00:00\",\"occurrence\":12,\
+             \"urgency\":\"critical\",\"category\":null,\"sent\":\"2026-11-02T09:00:05+00:00\"}"
+        );
+        // A digest is of no one reminder
+        let digest = Payload {
+            id: None,
+            scheduled: None,
+            occurrence: None,
+            urgency: None,
+            ..payload()
+        };
+        assert!(digest.to_json().starts_with("{\"id\":null,"));
+        assert!(digest
+            .to_json()
+            .contains("\"scheduled\":null,\"occurrence\":null,\"urgency\":null,"));
+    }
+
+    #[test]
+    fn environment() {
+        let env = payload().env();
+        let get = |name: &str| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+        assert_eq!(get("HERMES_ID"), "3");
+        assert_eq!(get("HERMES_BODY"), "Water the plants");
+        assert_eq!(get("HERMES_SCHEDULED"), "2026-11-02T09:00:00+00:00");
+        assert_eq!(get("HERMES_OCCURRENCE"), "12");
+        assert_eq!(get("HERMES_URGENCY"), "critical");
+        assert_eq!(get("HERMES_CATEGORY"), "");
+        assert_eq!(env.len(), 8);
+    }
+}
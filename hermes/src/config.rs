@@ -1,11 +1,58 @@
+use crate::migrations;
+use crate::notifier::Endpoint;
+use crate::quiet::{self, Delivery, QuietHours};
+use chrono::Weekday;
+use hermes_proto::reminder::{self, CatchUp, Sink};
 use rusqlite;
 use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{ErrorKind, Write};
+use std::time::Duration;
 
 const DIRECTORY: &str = ".hermes";
 const DATABASE: &str = "hermes.sqlite";
+const SETTINGS: &str = "hermes.conf";
+// Relative to .hermes, like the database
+const NOTIFY_FILE: &str = "notifications.log";
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Options read from hermes.conf, each with a default for when it is left out
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // Handling of missed occurrences for reminders without their own
+    pub catch_up: CatchUp,
+    // Times of day and whole days when reminders are held back
+    pub quiet_hours: Option<QuietHours>,
+    pub quiet_days: Vec<Weekday>,
+    // How reminders held back are sent afterwards
+    pub quiet_delivery: Delivery,
+    // Where reminders without their own sinks are sent, and what the other sinks need
+    pub notify: Vec<Sink>,
+    pub notify_file: String,
+    pub notify_command: Option<String>,
+    pub notify_url: Option<Endpoint>,
+    // Command run whenever a reminder fires, and how long it may take before it is killed
+    pub hook: Option<String>,
+    pub hook_timeout: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Settings {
+            catch_up: CatchUp::Once,
+            quiet_hours: None,
+            quiet_days: Vec::new(),
+            quiet_delivery: Delivery::Digest,
+            notify: vec![Sink::Desktop],
+            notify_file: NOTIFY_FILE.to_string(),
+            notify_command: None,
+            notify_url: None,
+            hook: None,
+            hook_timeout: HOOK_TIMEOUT,
+        };
+    }
+}
 
 /* Setups environment: .hermes directory, and database file */
 pub fn initialize_environment(log: &File) -> Option<rusqlite::Connection> {
@@ -82,7 +129,7 @@ fn create_directory(mut log: &File) -> bool {
     return true;
 }
 
-/* Setup database connection, creating db and table if it is not present */
+/* Setup database connection, creating db and bringing its tables up to date */
 fn setup_database(mut log: &File) -> Option<rusqlite::Connection> {
     let db_conn = rusqlite::Connection::open(DATABASE);
     if let Err(err) = db_conn {
@@ -92,25 +139,128 @@ fn setup_database(mut log: &File) -> Option<rusqlite::Connection> {
         let _ = log.write_all(dir.into_os_string().into_string().unwrap().as_bytes());
         return None;
     }
-    let db_conn = db_conn.unwrap();
-    let created_table = db_conn.execute(
-        " CREATE TABLE IF NOT EXISTS reminder (\
-           id INTEGER PRIMARY KEY,\
-           frequency INTEGER NOT NULL,\
-           message TEXT NOT NULL,\
-           month INTEGER NOT NULL,\
-           day INTEGER NOT NULL,\
-	   year INTEGER NOT NULL,\
-           hour INTEGER NOT NULL,\
-           minute INTEGER NOT NULL,\
-	   n INTEGER
-	   );",
-        [],
-    );
-    if let Err(err) = created_table {
-        let fmt_str = format!("Error creating database table: {}\n", err);
-        let _ = log.write_all(fmt_str.as_bytes());
+    let mut db_conn = db_conn.unwrap();
+    if !migrations::migrate(&mut db_conn, DATABASE, log) {
         return None;
     }
     return Some(db_conn);
 }
+
+/*
+* Reads hermes.conf from the .hermes directory. A missing file means every default, and a line
+* that can't be used is logged and skipped.
+*/
+pub fn load_settings(mut log: &File) -> Settings {
+    let text = match fs::read_to_string(SETTINGS) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Settings::default(),
+        Err(err) => {
+            let fmt_str = format!("Error reading {}: {}\n", SETTINGS, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return Settings::default();
+        }
+    };
+    return read_settings(&text, log);
+}
+
+/*
+* Reads `name = value` lines over the defaults. A comment is a line starting with #, so a value
+* such as a command or an address can hold one.
+*/
+fn read_settings(text: &str, mut log: &File) -> Settings {
+    let mut settings = Settings::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let applied = match line.split_once('=') {
+            Some((name, value)) => match name.trim() {
+                "catch_up" => value.parse().map(|value| settings.catch_up = value),
+                "quiet_hours" if value.trim().eq_ignore_ascii_case("none") => {
+                    settings.quiet_hours = None;
+                    Ok(())
+                }
+                "quiet_hours" => value
+                    .parse()
+                    .map(|value| settings.quiet_hours = Some(value)),
+                "quiet_days" => quiet::parse_days(value).map(|value| settings.quiet_days = value),
+                "quiet_delivery" => value.parse().map(|value| settings.quiet_delivery = value),
+                "notify" => reminder::parse_sinks(value).map(|value| settings.notify = value),
+                "notify_file" => {
+                    settings.notify_file = value.trim().to_string();
+                    Ok(())
+                }
+                "notify_command" if value.trim().eq_ignore_ascii_case("none") => {
+                    settings.notify_command = None;
+                    Ok(())
+                }
+                "notify_command" => {
+                    settings.notify_command = Some(value.trim().to_string());
+                    Ok(())
+                }
+                "notify_url" if value.trim().eq_ignore_ascii_case("none") => {
+                    settings.notify_url = None;
+                    Ok(())
+                }
+                "notify_url" => value.parse().map(|value| settings.notify_url = Some(value)),
+                "hook" if value.trim().eq_ignore_ascii_case("none") => {
+                    settings.hook = None;
+                    Ok(())
+                }
+                "hook" => {
+                    settings.hook = Some(value.trim().to_string());
+                    Ok(())
+                }
+                "hook_timeout" => parse_seconds(value).map(|value| settings.hook_timeout = value),
+                name => Err(format!("Unknown setting {}", name)),
+            },
+            None => Err("Expected name = value".to_string()),
+        };
+        if let Err(err) = applied {
+            let fmt_str = format!("{} line {}: {}\n", SETTINGS, number + 1, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+        }
+    }
+    return settings;
+}
+
+// Reads a whole number of seconds, such as "30" or "30s"
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    return match value
+        .strip_suffix('s')
+        .unwrap_or(value)
+        .trim()
+        .parse::<u64>()
+    {
+        Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+        _ => Err(format!("Invalid seconds \"{}\", expected e.g. 30s", value)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::read_settings;
+    use std::fs::File;
+
+    #[test]
+    fn comments() {
+        let log = File::create("/dev/null").unwrap();
+        let settings = read_settings(
+            "# Where reminders go\n  # indented too\nnotify = file, command\n\
+             notify_command = sh -c 'echo #1'\nhook = printf '%s#%s' a b\n",
+            &log,
+        );
+        assert_eq!(settings.notify.len(), 2);
+        assert_eq!(settings.notify_command.as_deref(), Some("sh -c 'echo #1'"));
+        assert_eq!(settings.hook.as_deref(), Some("printf '%s#%s' a b"));
+
+        // A fragment isn't sent, so the path ends before it
+        let settings = read_settings("notify_url = http://localhost:8080/hook#part\n", &log);
+        assert_eq!(
+            settings.notify_url,
+            Some("http://localhost:8080/hook".parse().unwrap())
+        );
+    }
+}
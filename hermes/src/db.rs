@@ -1,30 +1,194 @@
 use std::fs::File;
 use std::io::Write;
 
-use crate::reminder::{self, Reminder};
 use chrono::{prelude::*, Duration};
+use hermes_proto::protocol::{DeliveryStatus, DoNotDisturb};
+use hermes_proto::reminder::{self, Pause, Reminder, Sink};
+use hermes_proto::zone;
 use rusqlite::Error;
-use rusqlite::{self, params, Connection, Statement};
+use rusqlite::{self, params, Connection, OptionalExtension, Statement};
+
+/*
+* Times a reminder is due are kept as UTC timestamps: by its schedule in due_at, next to the date
+* and time in the reminder's own zone that it is worked out from, and when a snooze, repeat or pause
+* ends. Times in the state table, and those of databases from before timestamps, are UTC text.
+*/
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+// Do not disturb is kept in the state table as "on", or the UTC time it ends
+const DND_STATE: &str = "dnd";
+const DND_ON: &str = "on";
+// While the desktop can't be reached, the state table holds since when, as UTC text, and why
+const DEGRADED_SINCE: &str = "degraded_since";
+const DEGRADED_REASON: &str = "degraded_reason";
+/*
+* An undelivered notification is tried again RETRY_SECONDS after it failed, the wait doubling with
+* each further failure, at most MAX_RETRY_DOUBLINGS times
+*/
+const RETRY_SECONDS: i64 = 60;
+const MAX_RETRY_DOUBLINGS: u32 = 6;
+// The result column of the delivery table
+const DELIVERED: &str = "delivered";
+const FAILED: &str = "failed";
+// The paused column is NULL for a reminder that isn't
+const PAUSED_SKIP: u8 = 1;
+const PAUSED_HOLD: u8 = 2;
+
+// What was sent, as the kind column of the delivery table says
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DeliveryKind {
+    Notification,
+    // A warning ahead of a reminder's time
+    Alert,
+    // Several reminders held back during quiet hours, sent as one
+    Digest,
+    // A notification sent again to the notifiers that failed it
+    Retry,
+}
+
+impl DeliveryKind {
+    fn name(self) -> &'static str {
+        return match self {
+            DeliveryKind::Notification => "notification",
+            DeliveryKind::Alert => "alert",
+            DeliveryKind::Digest => "digest",
+            DeliveryKind::Retry => "retry",
+        };
+    }
+}
+
+// A reminder to send, and whether its own schedule is due rather than only a snooze or repeat
+pub struct Due {
+    pub id: u32,
+    pub reminder: Reminder,
+    pub scheduled: bool,
+    // Occurrences sent before this one
+    pub fired: u32,
+    // When the occurrence last sent was scheduled, which a snooze or repeat of it stands for
+    pub occurrence: Option<DateTime<Utc>>,
+}
+
+impl Due {
+    /*
+     * Which of the reminder's occurrences this is, counting from 1, where a snooze or repeat is of
+     * the last one sent. None for a snooze of a reminder never sent.
+     */
+    pub fn number(&self) -> Option<u32> {
+        if self.scheduled {
+            return Some(self.fired + 1);
+        }
+        return Some(self.fired).filter(|fired| *fired > 0);
+    }
+}
+
+// A stored reminder as an agenda needs it, with when it is snoozed until if it is
+pub struct Planned {
+    pub id: u32,
+    pub reminder: Reminder,
+    pub snoozed_until: Option<DateTime<Utc>>,
+}
 
 pub struct NotificationStatements<'c> {
     notify_statment: Statement<'c>,
     update_statement: Statement<'c>,
     delete_statement: Statement<'c>,
+    sent_statement: Statement<'c>,
+    times_statement: Statement<'c>,
+    local_statement: Statement<'c>,
+    retime_statement: Statement<'c>,
+    dnd_statement: Statement<'c>,
+    defer_statement: Statement<'c>,
+    deferred_statement: Statement<'c>,
+    undefer_statement: Statement<'c>,
+    resume_statement: Statement<'c>,
+    alerts_statement: Statement<'c>,
+    alerted_statement: Statement<'c>,
+    queue_statement: Statement<'c>,
+    undelivered_statement: Statement<'c>,
+    delivered_statement: Statement<'c>,
+    retry_statement: Statement<'c>,
+    retry_now_statement: Statement<'c>,
+    next_retry_statement: Statement<'c>,
+    set_state_statement: Statement<'c>,
+    clear_state_statement: Statement<'c>,
+    record_statement: Statement<'c>,
+    prune_statement: Statement<'c>,
+}
+
+/*
+* A notification held back during quiet hours, or kept after failing to send, with the reminder it
+* is for, when it was due, and when the occurrence it stands for was scheduled and which it is
+*/
+pub struct Deferred {
+    pub id: u32,
+    pub reminder: u32,
+    pub notice: Reminder,
+    pub due: DateTime<Utc>,
+    pub scheduled: Option<DateTime<Utc>>,
+    pub number: Option<u32>,
 }
 
 impl<'c> NotificationStatements<'c> {
     pub fn new<'a>(conn: &'a Connection, log: &mut File) -> Option<NotificationStatements<'a>> {
+        /*
+         * A sent ONCE reminder waiting to be acknowledged only comes back through renotify_at. One
+         * skipping its occurrences while paused still comes up, to be moved on without being sent.
+         */
         let notify_stmt = conn.prepare(
-            "SELECT * from reminder WHERE year < ?\
-					OR (year = ? AND month < ?) OR (year = ? AND month = ? AND day < ?)\
-					OR (year = ? AND month = ? and day = ? AND hour = ? AND minute <= ?)",
+            "SELECT * from reminder WHERE ((due_at <= ?1\
+					AND NOT (frequency = ?2 AND renotify_at IS NOT NULL))\
+					OR snoozed_until <= ?3 OR renotify_at <= ?3)\
+					AND (paused IS NULL OR paused = ?4)",
         );
 
         let update_stmt = conn.prepare(
-            "UPDATE reminder SET year = ?, month = ?, day = ?, hour = ?, minute = ?\
-					WHERE id = ?",
+            "UPDATE reminder SET year = ?, month = ?, day = ?, hour = ?, minute = ?,\
+					due_at = ?, remaining = ?, alert_at = ? WHERE id = ?",
         );
         let delete_stmt = conn.prepare("DELETE FROM reminder WHERE id = ?");
+        let sent_stmt = conn.prepare(
+            "UPDATE reminder SET snoozed_until = NULL, renotify_at = ?, fired = fired + ?,\
+					occurrence_at = COALESCE(?, occurrence_at) WHERE id = ?",
+        );
+        let times_stmt = conn.prepare("SELECT * FROM reminder");
+        let local_stmt = conn.prepare("SELECT * FROM reminder WHERE zone IS NULL");
+        let retime_stmt = conn.prepare("UPDATE reminder SET due_at = ?, alert_at = ? WHERE id = ?");
+        let dnd_stmt = conn.prepare("SELECT value FROM state WHERE name = ?");
+        // A repeat or snooze of a reminder already held back isn't held again
+        let defer_stmt = conn.prepare(
+            "INSERT INTO deferred (reminder, notice, due_at, scheduled_at, occurrence)\
+					SELECT ?1, ?2, ?3, ?4, ?6\
+					WHERE NOT (?5 AND EXISTS (SELECT 1 FROM deferred WHERE reminder = ?1))",
+        );
+        let deferred_stmt = conn.prepare("SELECT * FROM deferred ORDER BY due_at, id");
+        let undefer_stmt = conn.prepare("DELETE FROM deferred WHERE id = ?");
+        let resume_stmt = conn
+            .prepare("UPDATE reminder SET paused = NULL, resume_at = NULL WHERE resume_at <= ?");
+        let alerts_stmt =
+            conn.prepare("SELECT * FROM reminder WHERE alert_at <= ? AND paused IS NULL");
+        let alerted_stmt = conn.prepare("UPDATE reminder SET alert_at = ? WHERE id = ?");
+        let queue_stmt = conn.prepare(
+            "INSERT INTO undelivered (reminder, notice, due_at, scheduled_at, occurrence, \
+             attempts, next_attempt) VALUES (?, ?, ?, ?, ?, 0, ?)",
+        );
+        let undelivered_stmt =
+            conn.prepare("SELECT * FROM undelivered WHERE next_attempt <= ? ORDER BY due_at, id");
+        let delivered_stmt = conn.prepare("DELETE FROM undelivered WHERE id = ?");
+        // The attempts on the right are those before this one
+        let retry_stmt = conn.prepare(
+            "UPDATE undelivered SET notice = ?1, attempts = attempts + 1, \
+             next_attempt = ?2 + (?3 << MIN(attempts, ?4)) WHERE id = ?5",
+        );
+        let retry_now_stmt = conn.prepare("UPDATE undelivered SET next_attempt = ?");
+        let next_retry_stmt = conn.prepare("SELECT MIN(next_attempt) FROM undelivered");
+        let set_state_stmt =
+            conn.prepare("INSERT OR REPLACE INTO state (name, value) VALUES (?, ?)");
+        let clear_state_stmt = conn.prepare("DELETE FROM state WHERE name IN (?, ?)");
+        let record_stmt = conn.prepare(
+            "INSERT INTO delivery (reminder, scheduled_at, sent_at, backend, result, error, \
+             notification, kind) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        );
+        let prune_stmt = conn.prepare("DELETE FROM delivery WHERE sent_at < ?");
 
         if let Err(e) = notify_stmt {
             let fmt_str = format!("Failed to setup notification statement: {}\n", e);
@@ -41,60 +205,543 @@ impl<'c> NotificationStatements<'c> {
             let _ = log.write_all(fmt_str.as_bytes());
             return None;
         }
+        if let Err(e) = sent_stmt {
+            let fmt_str = format!("Failed to setup sent statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = times_stmt {
+            let fmt_str = format!("Failed to setup due times statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = local_stmt {
+            let fmt_str = format!("Failed to setup local time statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = retime_stmt {
+            let fmt_str = format!("Failed to setup retime statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = dnd_stmt {
+            let fmt_str = format!("Failed to setup do not disturb statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = defer_stmt {
+            let fmt_str = format!("Failed to setup defer statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = deferred_stmt {
+            let fmt_str = format!("Failed to setup deferred statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = undefer_stmt {
+            let fmt_str = format!("Failed to setup undefer statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = resume_stmt {
+            let fmt_str = format!("Failed to setup resume statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = alerts_stmt {
+            let fmt_str = format!("Failed to setup alerts statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = alerted_stmt {
+            let fmt_str = format!("Failed to setup alerted statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = queue_stmt {
+            let fmt_str = format!("Failed to setup queue statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = undelivered_stmt {
+            let fmt_str = format!("Failed to setup undelivered statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = delivered_stmt {
+            let fmt_str = format!("Failed to setup delivered statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = retry_stmt {
+            let fmt_str = format!("Failed to setup retry statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = retry_now_stmt {
+            let fmt_str = format!("Failed to setup retry now statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = next_retry_stmt {
+            let fmt_str = format!("Failed to setup next retry statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = set_state_stmt {
+            let fmt_str = format!("Failed to setup set state statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = clear_state_stmt {
+            let fmt_str = format!("Failed to setup clear state statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = record_stmt {
+            let fmt_str = format!("Failed to setup record statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        if let Err(e) = prune_stmt {
+            let fmt_str = format!("Failed to setup prune statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
 
         return Some(NotificationStatements {
             notify_statment: notify_stmt.unwrap(),
             update_statement: update_stmt.unwrap(),
             delete_statement: delete_stmt.unwrap(),
+            sent_statement: sent_stmt.unwrap(),
+            times_statement: times_stmt.unwrap(),
+            local_statement: local_stmt.unwrap(),
+            retime_statement: retime_stmt.unwrap(),
+            dnd_statement: dnd_stmt.unwrap(),
+            defer_statement: defer_stmt.unwrap(),
+            deferred_statement: deferred_stmt.unwrap(),
+            undefer_statement: undefer_stmt.unwrap(),
+            resume_statement: resume_stmt.unwrap(),
+            alerts_statement: alerts_stmt.unwrap(),
+            alerted_statement: alerted_stmt.unwrap(),
+            queue_statement: queue_stmt.unwrap(),
+            undelivered_statement: undelivered_stmt.unwrap(),
+            delivered_statement: delivered_stmt.unwrap(),
+            retry_statement: retry_stmt.unwrap(),
+            retry_now_statement: retry_now_stmt.unwrap(),
+            next_retry_statement: next_retry_stmt.unwrap(),
+            set_state_statement: set_state_stmt.unwrap(),
+            clear_state_statement: clear_state_stmt.unwrap(),
+            record_statement: record_stmt.unwrap(),
+            prune_statement: prune_stmt.unwrap(),
         });
     }
 
+    // Reminders with a warning due, each with how far ahead of its time the warning is
+    pub fn get_alerts(&mut self, mut log: &File) -> Option<Vec<(u32, Reminder, Option<u32>)>> {
+        let now = Utc::now();
+        let res = self
+            .alerts_statement
+            .query_map(params!(now.timestamp()), reminder_from_row)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, Error>>());
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving warnings: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(
+            res.unwrap()
+                .into_iter()
+                .map(|(id, reminder)| {
+                    let lead = reminder.alert_due(now);
+                    (id, reminder, lead)
+                })
+                .collect(),
+        );
+    }
+
+    // Moves a reminder on to its next warning, once the one due has been handled
+    pub fn alerted(&mut self, id: u32, reminder: &Reminder, mut log: &File) -> bool {
+        if let Err(err) = self
+            .alerted_statement
+            .execute(params!(next_alert(reminder), id))
+        {
+            let fmt_str = format!("Error moving on warnings of reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Resumes the reminders whose pause has ended, returning false if that failed
+    pub fn resume_due(&mut self, mut log: &File) -> bool {
+        let now = Utc::now().timestamp();
+        if let Err(err) = self.resume_statement.execute(params!(now)) {
+            let fmt_str = format!("Error resuming paused reminders: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    /*
+     * Works out again when the reminders without a zone of their own are due, and their next
+     * warning, as the computer's zone may have changed since. Returns false if that failed.
+     */
+    pub fn retime_local(&mut self, mut log: &File) -> bool {
+        let res = self
+            .local_statement
+            .query_map([], |row| {
+                let due_at: Option<i64> = row.get("due_at")?;
+                let (id, reminder) = reminder_from_row(row)?;
+                Ok((id, reminder, due_at))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, Error>>());
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving reminders in local time: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        for (id, reminder, due_at) in res.unwrap() {
+            let at = reminder.to_instant().map(|at| at.timestamp());
+            if at == due_at {
+                continue;
+            }
+            if let Err(err) = self
+                .retime_statement
+                .execute(params!(at, next_alert(&reminder), id))
+            {
+                let fmt_str = format!("Error moving reminder {} to local time: {}\n", id, err);
+                let _ = log.write_all(fmt_str.as_bytes());
+                return false;
+            }
+        }
+        return true;
+    }
+
+    // Do not disturb as last set, Off if it couldn't be read
+    pub fn dnd(&mut self, mut log: &File) -> DoNotDisturb {
+        let dnd = read_dnd(&mut self.dnd_statement);
+        if let Err(err) = dnd {
+            let fmt_str = format!("Error reading do not disturb: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return DoNotDisturb::Off;
+        }
+        return dnd.unwrap();
+    }
+
+    /*
+     * Holds back a notification for a reminder until quiet hours are over, for the occurrence
+     * scheduled at `scheduled`
+     */
+    pub fn defer(
+        &mut self,
+        due: &Due,
+        notice: &Reminder,
+        scheduled: Option<DateTime<Utc>>,
+        mut log: &File,
+    ) -> bool {
+        let id = due.id;
+        let res = self.defer_statement.execute(params!(
+            id,
+            notice.serialize(),
+            Utc::now().timestamp(),
+            scheduled.map(|at| at.timestamp()),
+            !due.scheduled,
+            due.number()
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Error holding back reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Notifications held back, oldest first
+    pub fn deferred(&mut self, mut log: &File) -> Option<Vec<Deferred>> {
+        let res = read_deferred(&mut self.deferred_statement, []);
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving held back reminders: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let (deferred, unreadable) = res.unwrap();
+        for (id, reminder) in unreadable {
+            let fmt_str = format!("Dropped unreadable held back reminder {}\n", reminder);
+            let _ = log.write_all(fmt_str.as_bytes());
+            self.undefer(id, log);
+        }
+        return Some(deferred);
+    }
+
+    // Forgets a held back notification once it has been sent
+    pub fn undefer(&mut self, id: u32, mut log: &File) -> bool {
+        if let Err(err) = self.undefer_statement.execute(params!(id)) {
+            let fmt_str = format!("Error removing held back reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    /*
+     * Keeps a notification a notifier couldn't take, to send again through the sinks of `notice`.
+     * `due` is when it was first meant to be shown, which doesn't change however often it is tried,
+     * and `scheduled` when the occurrence it stands for was, the `number`th of its reminder.
+     */
+    pub fn queue(
+        &mut self,
+        id: u32,
+        notice: &Reminder,
+        due: DateTime<Utc>,
+        scheduled: Option<DateTime<Utc>>,
+        number: Option<u32>,
+        mut log: &File,
+    ) -> bool {
+        let next_attempt = Utc::now().timestamp() + RETRY_SECONDS;
+        let res = self.queue_statement.execute(params!(
+            id,
+            notice.serialize(),
+            due.timestamp(),
+            scheduled.map(|at| at.timestamp()),
+            number,
+            next_attempt
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Error keeping undelivered reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Notifications waiting to be sent again whose next attempt is due by `now`, oldest first
+    pub fn undelivered(&mut self, now: DateTime<Utc>, mut log: &File) -> Option<Vec<Deferred>> {
+        let res = read_deferred(&mut self.undelivered_statement, params!(now.timestamp()));
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving undelivered reminders: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let (undelivered, unreadable) = res.unwrap();
+        for (id, reminder) in unreadable {
+            let fmt_str = format!("Dropped unreadable undelivered reminder {}\n", reminder);
+            let _ = log.write_all(fmt_str.as_bytes());
+            self.delivered(id, log);
+        }
+        return Some(undelivered);
+    }
+
+    // Forgets an undelivered notification once it has been sent
+    pub fn delivered(&mut self, id: u32, mut log: &File) -> bool {
+        if let Err(err) = self.delivered_statement.execute(params!(id)) {
+            let fmt_str = format!("Error removing undelivered reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    /*
+     * Puts off the next attempt at an undelivered notification, waiting longer the more often it
+     * has been tried, and keeps it for the sinks of `notice` only.
+     */
+    pub fn retry_later(&mut self, id: u32, notice: &Reminder, mut log: &File) -> bool {
+        let res = self.retry_statement.execute(params!(
+            notice.serialize(),
+            Utc::now().timestamp(),
+            RETRY_SECONDS,
+            MAX_RETRY_DOUBLINGS,
+            id
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Error putting off undelivered reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Makes every undelivered notification due to be tried again, such as once the desktop is back
+    pub fn retry_now(&mut self, mut log: &File) -> bool {
+        if let Err(err) = self
+            .retry_now_statement
+            .execute(params!(Utc::now().timestamp()))
+        {
+            let fmt_str = format!("Error retrying undelivered reminders: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // When the next undelivered notification is to be tried again, None if there are none
+    pub fn next_retry(&mut self, mut log: &File) -> Option<DateTime<Utc>> {
+        let res = self
+            .next_retry_statement
+            .query_row([], |row| row.get::<_, Option<i64>>(0));
+        if let Err(err) = res {
+            let fmt_str = format!("Error reading next retry: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return res
+            .unwrap()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single());
+    }
+
+    // Records what a notifier made of a notification, for the reminder it is for unless a digest
+    pub fn record(
+        &mut self,
+        kind: DeliveryKind,
+        reminder: Option<u32>,
+        scheduled: Option<DateTime<Utc>>,
+        sink: Sink,
+        result: &Result<Option<u32>, String>,
+        mut log: &File,
+    ) -> bool {
+        let (outcome, error, notification) = match result {
+            Ok(notification) => (DELIVERED, None, *notification),
+            Err(err) => (FAILED, Some(err), None),
+        };
+        let res = self.record_statement.execute(params!(
+            reminder,
+            scheduled.map(|at| at.timestamp()),
+            Utc::now().timestamp(),
+            sink.to_string(),
+            outcome,
+            error,
+            notification,
+            kind.name()
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Error recording delivery: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Forgets the deliveries sent before `before`
+    pub fn prune_deliveries(&mut self, before: DateTime<Utc>, mut log: &File) -> bool {
+        if let Err(err) = self.prune_statement.execute(params!(before.timestamp())) {
+            let fmt_str = format!("Error removing old deliveries: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // Records since when and why the desktop can't be reached, or clears it with None
+    pub fn set_degraded(&mut self, lost: Option<&(DateTime<Utc>, String)>, mut log: &File) -> bool {
+        let res = match lost {
+            Some((since, reason)) => self
+                .set_state_statement
+                .execute(params!(
+                    DEGRADED_SINCE,
+                    since.format(TIME_FORMAT).to_string()
+                ))
+                .and_then(|_| {
+                    self.set_state_statement
+                        .execute(params!(DEGRADED_REASON, reason))
+                }),
+            None => self
+                .clear_state_statement
+                .execute(params!(DEGRADED_SINCE, DEGRADED_REASON)),
+        };
+        if let Err(err) = res {
+            let fmt_str = format!("Error recording state of delivery: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        return true;
+    }
+
+    // When each reminder is next due, by its schedule, a snooze or a repeat, whichever is first
+    pub fn due_times(&mut self, mut log: &File) -> Option<Vec<(u32, DateTime<Utc>)>> {
+        let res = self.times_statement.query_map(
+            [],
+            |row: &rusqlite::Row| -> Result<(u32, Option<DateTime<Utc>>), Error> {
+                let id: u32 = row.get("id")?;
+                let frequency: u8 = row.get("frequency")?;
+                let time = |column: &str| -> Result<Option<DateTime<Utc>>, Error> {
+                    let seconds: Option<i64> = row.get(column)?;
+                    Ok(seconds.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()))
+                };
+                let renotify_at = time("renotify_at")?;
+                // Matching get_notifications, a sent ONCE reminder only comes back to repeat
+                let once = reminder::serialize_frequency(reminder::Frequency::ONCE);
+                let awaiting_ack = frequency == once && renotify_at.is_some();
+                let scheduled = time("due_at")?.filter(|_| !awaiting_ack);
+                let alert_at = time("alert_at")?;
+                let mut times = vec![scheduled, time("snoozed_until")?, renotify_at];
+                // A reminder holding its occurrences waits for its pause to end
+                let paused: Option<u8> = row.get("paused")?;
+                if paused == Some(PAUSED_HOLD) {
+                    times.clear();
+                }
+                // No warnings are sent while paused
+                if paused.is_none() {
+                    times.push(alert_at);
+                }
+                times.push(time("resume_at")?);
+                let next = times.iter().flatten().min().copied();
+                Ok((id, next))
+            },
+        );
+
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving due times: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let mut times = Vec::new();
+        for time in res.unwrap() {
+            match time {
+                Ok((id, Some(time))) => times.push((id, time)),
+                Ok((_, None)) => {}
+                Err(e) => {
+                    let fmt_str = format!("Error retrieving reminder: {}\n", e);
+                    let _ = log.write_all(fmt_str.as_bytes());
+                }
+            }
+        }
+        return Some(times);
+    }
+
     /* Function to pull notifications that need to be sent */
-    pub fn get_notifications(&mut self, mut log: &File) -> Option<Vec<(u32, Reminder)>> {
-        let time = chrono::offset::Local::now();
+    pub fn get_notifications(&mut self, mut log: &File) -> Option<Vec<Due>> {
+        let time = Utc::now();
+        let once = reminder::serialize_frequency(reminder::Frequency::ONCE);
         let res = self.notify_statment.query_map(
-            params!(
-                time.year(),
-                time.year(),
-                time.month(),
-                time.year(),
-                time.month(),
-                time.day(),
-                time.year(),
-                time.month(),
-                time.day(),
-                time.hour(),
-                time.minute()
-            ),
-            |row: &rusqlite::Row| -> Result<(u32, Reminder), Error> {
-                let mut n: Option<u32> = row.get(8).unwrap();
-                if let Some(v) = n {
-                    n = Some(u32::from_be(v));
-                }
-                Ok((
-                    row.get(0)?,
-                    Reminder::new(
-                        reminder::deserialize_frequency(row.get(1).unwrap()).unwrap(),
-                        row.get(3)?,
-                        row.get(4)?,
-                        row.get(5)?,
-                        row.get(6)?,
-                        row.get(7)?,
-                        n,
-                        row.get(2)?,
-                    ),
-                ))
+            params!(time.timestamp(), once, time.timestamp(), PAUSED_SKIP),
+            |row: &rusqlite::Row| -> Result<Due, Error> {
+                let (id, reminder) = reminder_from_row(row)?;
+                let awaiting_ack = row.get::<_, Option<i64>>("renotify_at")?.is_some();
+                let scheduled = reminder.to_instant().is_none_or(|at| at <= time)
+                    && !(reminder.frequency == reminder::Frequency::ONCE && awaiting_ack);
+                let occurrence: Option<i64> = row.get("occurrence_at")?;
+                Ok(Due {
+                    id,
+                    reminder,
+                    scheduled,
+                    fired: row.get("fired")?,
+                    occurrence: occurrence
+                        .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+                })
             },
         );
 
-        // NOTE: Code duplicated from PreparedStatements, can't have closure as function argument to abstract this
         if let Err(err) = res {
             let fmt_str = format!("Error retrieving data: {}\n", err);
             let _ = log.write_all(fmt_str.as_bytes());
             return None;
         }
         let reminders = res.unwrap();
-        let mut rem_vec: Vec<(u32, Reminder)> = Vec::new();
+        let mut rem_vec: Vec<Due> = Vec::new();
         for reminder in reminders {
             if let Err(e) = reminder {
                 let fmt_str = format!("Error retrieving reminder: {}\n", e);
@@ -107,27 +754,76 @@ impl<'c> NotificationStatements<'c> {
         return Some(rem_vec);
     }
 
-    pub fn update_notification(&mut self, reminder: (u32, Reminder), mut log: &File) -> bool {
-        let (id, mut rem) = reminder;
+    /*
+     * Moves a reminder on once handled, where `sent` is false if its catch-up policy skipped it.
+     * `occurrence` is when the last occurrence sent was scheduled, kept for its snoozes and repeats.
+     */
+    pub fn update_notification(
+        &mut self,
+        due: Due,
+        sent: bool,
+        occurrence: Option<DateTime<Utc>>,
+        mut log: &File,
+    ) -> bool {
+        let (id, rem) = (due.id, due.reminder);
+
+        // A snooze is used up once sent, and a persistent reminder is sent again until acknowledged
+        let renotify_at = rem
+            .renotify
+            .filter(|_| sent)
+            .map(|minutes| (Utc::now() + Duration::minutes(minutes as i64)).timestamp());
+        let fired = sent && due.scheduled;
+        let occurrence = occurrence.filter(|_| sent).map(|at| at.timestamp());
+        let res = self
+            .sent_statement
+            .execute(params!(renotify_at, fired, occurrence, id));
+        if let Err(err) = res {
+            let fmt_str = format!("Error marking reminder {} as sent: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        if !due.scheduled {
+            return true;
+        }
+
         match rem.frequency {
-            reminder::Frequency::DAILY => {
-                let old_date = rem.to_datetime();
-                if old_date.is_none() {
-                    log.write_all(b"Error parsing datetime");
+            // The next occurrence after now, so any missed while asleep are not sent one by one
+            reminder::Frequency::DAILY
+            | reminder::Frequency::WEEKLY
+            | reminder::Frequency::NDAYS
+            | reminder::Frequency::MONTHLY
+            | reminder::Frequency::YEARLY
+            | reminder::Frequency::RULE => {
+                let now = rem.wall_time(Utc::now());
+                // Every occurrence up to now is used up, also those missed
+                let remaining = rem.remaining.map(|remaining| {
+                    let passed = rem.occurrences_until(now, remaining as usize).len();
+                    remaining.saturating_sub(passed as u32)
+                });
+                let next = rem.next_occurrence(now).filter(|_| remaining != Some(0));
+                let res = match next {
+                    Some(date) => self.update_statement.execute(params!(
+                        date.year(),
+                        date.month(),
+                        date.day(),
+                        date.hour(),
+                        date.minute(),
+                        zone::resolve(rem.zone, date).map(|at| at.timestamp()),
+                        remaining,
+                        next_alert(&moved(&rem, date)),
+                        id
+                    )),
+                    // Nothing left to send
+                    None => self.delete_statement.execute(params!(id)),
+                };
+                if let Err(err) = res {
+                    let fmt_str = format!("Error scheduling reminder {}: {}\n", id, err);
+                    let _ = log.write_all(fmt_str.as_bytes());
                     return false;
                 }
-                let old_date = old_date.unwrap();
-
-                let date = chrono::offset::Local::now() + Duration::days(1);
-                self.update_statement.execute(params!(
-                    date.year(),
-                    date.month(),
-                    date.day(),
-                    old_date.hour(),
-                    old_date.minute(),
-                    id
-                ));
             }
+            // Kept until acknowledged when persistent
+            reminder::Frequency::ONCE if rem.renotify.is_some() && sent => {}
             reminder::Frequency::ONCE => {
                 let res = self.delete_statement.execute(params!(id));
                 if res.is_err() {
@@ -136,42 +832,6 @@ impl<'c> NotificationStatements<'c> {
                     return false;
                 }
             }
-            reminder::Frequency::WEEKLY => {
-                let old_date = rem.to_datetime();
-                if old_date.is_none() {
-                    log.write_all(b"Error parsing datetime");
-                    return false;
-                }
-                let old_date = old_date.unwrap();
-
-                let date = chrono::offset::Local::now() + Duration::days(7);
-                self.update_statement.execute(params!(
-                    date.year(),
-                    date.month(),
-                    date.day(),
-                    old_date.hour(),
-                    old_date.minute(),
-                    id
-                ));
-            }
-            reminder::Frequency::NDAYS => {
-                let old_date = rem.to_datetime();
-                if old_date.is_none() {
-                    log.write_all(b"Error parsing datetime");
-                    return false;
-                }
-                let old_date = old_date.unwrap();
-                let n = rem.n.unwrap();
-                let date = chrono::offset::Local::now() + Duration::days(n as i64);
-                self.update_statement.execute(params!(
-                    date.year(),
-                    date.month(),
-                    date.day(),
-                    old_date.hour(),
-                    old_date.minute(),
-                    id
-                ));
-            }
         }
         return true;
     }
@@ -181,7 +841,19 @@ impl<'c> NotificationStatements<'c> {
 pub struct PreparedStatements<'c> {
     list_statement: Statement<'c>,
     insert_statement: Statement<'c>,
+    update_statement: Statement<'c>,
     delete_statement: Statement<'c>,
+    zone_statement: Statement<'c>,
+    snooze_once_statement: Statement<'c>,
+    snooze_statement: Statement<'c>,
+    ack_once_statement: Statement<'c>,
+    ack_statement: Statement<'c>,
+    dnd_statement: Statement<'c>,
+    set_dnd_statement: Statement<'c>,
+    clear_dnd_statement: Statement<'c>,
+    pause_statement: Statement<'c>,
+    resume_statement: Statement<'c>,
+    queued_statement: Statement<'c>,
 }
 
 impl<'c> PreparedStatements<'c> {
@@ -196,7 +868,9 @@ impl<'c> PreparedStatements<'c> {
 
         let insert_stmt = conn.prepare(
             "INSERT INTO reminder (frequency, message, month, day, year, hour, minute,\
-					n) VALUES (?,?,?,?,?,?,?,?)",
+					n, renotify_minutes, rule, start, catch_up, zone, due_at, priority,\
+					until, remaining, alerts, alert_at, title, urgency, icon, category,\
+					timeout, sinks) VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
         );
         if let Err(e) = insert_stmt {
             let fmt_str = format!("Failed to prepare insert statement: {}\n", e);
@@ -205,6 +879,22 @@ impl<'c> PreparedStatements<'c> {
         }
         let insert_stmt = insert_stmt.unwrap();
 
+        let update_stmt = conn.prepare(
+            "UPDATE reminder SET frequency = ?, message = ?, month = ?, day = ?, year = ?,\
+					hour = ?, minute = ?, n = ?, renotify_minutes = ?, rule = ?,\
+					start = ?, catch_up = ?, zone = ?, due_at = ?, priority = ?,\
+					until = ?, remaining = ?, alerts = ?, alert_at = ?, title = ?,\
+					urgency = ?, icon = ?, category = ?, timeout = ?, sinks = ?,\
+					renotify_at = NULL \
+					WHERE id = ?",
+        );
+        if let Err(e) = update_stmt {
+            let fmt_str = format!("Failed to prepare update statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let update_stmt = update_stmt.unwrap();
+
         let delete_stmt = conn.prepare("DELETE FROM reminder WHERE id = ?");
 
         if let Err(e) = delete_stmt {
@@ -214,37 +904,134 @@ impl<'c> PreparedStatements<'c> {
         }
         let delete_stmt = delete_stmt.unwrap();
 
+        let zone_stmt = conn.prepare("SELECT zone FROM reminder WHERE id = ?");
+        if let Err(e) = zone_stmt {
+            let fmt_str = format!("Failed to prepare zone statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let zone_stmt = zone_stmt.unwrap();
+
+        // A ONCE reminder only has the one time, so snoozing moves it. Either way it counts as seen
+        let snooze_once_stmt = conn.prepare(
+            "UPDATE reminder SET year = ?, month = ?, day = ?, hour = ?, minute = ?,\
+					due_at = ?, renotify_at = NULL, alert_at = NULL WHERE id = ? AND frequency = ?",
+        );
+        if let Err(e) = snooze_once_stmt {
+            let fmt_str = format!("Failed to prepare snooze statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let snooze_once_stmt = snooze_once_stmt.unwrap();
+
+        let snooze_stmt = conn.prepare(
+            "UPDATE reminder SET snoozed_until = ?, renotify_at = NULL \
+					WHERE id = ? AND frequency != ?",
+        );
+        if let Err(e) = snooze_stmt {
+            let fmt_str = format!("Failed to prepare snooze statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let snooze_stmt = snooze_stmt.unwrap();
+
+        // A ONCE reminder waiting for an acknowledgement has already been sent, so is done
+        let ack_once_stmt = conn.prepare(
+            "DELETE FROM reminder WHERE id = ? AND frequency = ? AND renotify_at IS NOT NULL",
+        );
+        if let Err(e) = ack_once_stmt {
+            let fmt_str = format!("Failed to prepare acknowledge statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let ack_once_stmt = ack_once_stmt.unwrap();
+
+        let ack_stmt = conn.prepare(
+            "UPDATE reminder SET renotify_at = NULL WHERE id = ? AND renotify_at IS NOT NULL",
+        );
+        if let Err(e) = ack_stmt {
+            let fmt_str = format!("Failed to prepare acknowledge statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let ack_stmt = ack_stmt.unwrap();
+
+        let dnd_stmt = conn.prepare("SELECT value FROM state WHERE name = ?");
+        if let Err(e) = dnd_stmt {
+            let fmt_str = format!("Failed to prepare do not disturb statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let dnd_stmt = dnd_stmt.unwrap();
+
+        let set_dnd_stmt = conn.prepare("INSERT OR REPLACE INTO state (name, value) VALUES (?, ?)");
+        if let Err(e) = set_dnd_stmt {
+            let fmt_str = format!("Failed to prepare do not disturb statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let set_dnd_stmt = set_dnd_stmt.unwrap();
+
+        let clear_dnd_stmt = conn.prepare("DELETE FROM state WHERE name = ?");
+        if let Err(e) = clear_dnd_stmt {
+            let fmt_str = format!("Failed to prepare do not disturb statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let clear_dnd_stmt = clear_dnd_stmt.unwrap();
+
+        // A ONCE reminder has nothing to move on to, so always holds its time
+        let pause_stmt = conn.prepare(
+            "UPDATE reminder SET paused = CASE WHEN frequency = ? THEN ? ELSE ? END,\
+					resume_at = ? WHERE id = ?",
+        );
+        if let Err(e) = pause_stmt {
+            let fmt_str = format!("Failed to prepare pause statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let pause_stmt = pause_stmt.unwrap();
+
+        let resume_stmt = conn.prepare(
+            "UPDATE reminder SET paused = NULL, resume_at = NULL \
+					WHERE id = ? AND paused IS NOT NULL",
+        );
+        if let Err(e) = resume_stmt {
+            let fmt_str = format!("Failed to prepare resume statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let resume_stmt = resume_stmt.unwrap();
+
+        let queued_stmt = conn.prepare("SELECT COUNT(*) FROM undelivered");
+        if let Err(e) = queued_stmt {
+            let fmt_str = format!("Failed to prepare queued statement: {}\n", e);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let queued_stmt = queued_stmt.unwrap();
+
         return Some(PreparedStatements {
             list_statement: list_stmt,
             insert_statement: insert_stmt,
+            update_statement: update_stmt,
             delete_statement: delete_stmt,
+            zone_statement: zone_stmt,
+            snooze_once_statement: snooze_once_stmt,
+            snooze_statement: snooze_stmt,
+            ack_once_statement: ack_once_stmt,
+            ack_statement: ack_stmt,
+            dnd_statement: dnd_stmt,
+            set_dnd_statement: set_dnd_stmt,
+            clear_dnd_statement: clear_dnd_stmt,
+            pause_statement: pause_stmt,
+            resume_statement: resume_stmt,
+            queued_statement: queued_stmt,
         });
     }
 
     pub fn list(&mut self, mut log: &File) -> Option<Vec<(u32, Reminder)>> {
-        let reminders = self.list_statement.query_map(
-            [],
-            |row: &rusqlite::Row| -> Result<(u32, Reminder), Error> {
-                let mut n: Option<u32> = row.get(8).unwrap();
-                if let Some(v) = n {
-                    n = Some(u32::from_be(v));
-                }
-
-                Ok((
-                    row.get(0)?,
-                    Reminder::new(
-                        reminder::deserialize_frequency(row.get(1).unwrap()).unwrap(),
-                        row.get(3)?,
-                        row.get(4)?,
-                        u32::from_be(row.get(5)?),
-                        row.get(6)?,
-                        row.get(7)?,
-                        n,
-                        row.get(2)?,
-                    ),
-                ))
-            },
-        );
+        let reminders = self.list_statement.query_map([], reminder_from_row);
         if let Err(err) = reminders {
             let fmt_str = format!("Error retrieving data: {}\n", err);
             let _ = log.write_all(fmt_str.as_bytes());
@@ -264,51 +1051,471 @@ impl<'c> PreparedStatements<'c> {
         return Some(rem_vec);
     }
 
-    pub fn add(&mut self, reminder: Reminder, mut log: &File) -> bool {
-        let params = reminder.as_tuple();
+    pub fn agenda(&mut self, mut log: &File) -> Option<Vec<Planned>> {
+        let res = self
+            .list_statement
+            .query_map([], |row| {
+                let (id, reminder) = reminder_from_row(row)?;
+                let snoozed_until: Option<i64> = row.get("snoozed_until")?;
+                Ok(Planned {
+                    id,
+                    reminder,
+                    snoozed_until: snoozed_until
+                        .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, Error>>());
+        if let Err(err) = res {
+            let fmt_str = format!("Error retrieving agenda: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(res.unwrap());
+    }
+
+    // Returns the id of the new row
+    pub fn add(&mut self, reminder: Reminder, mut log: &File) -> Option<u32> {
         // Handle n to big endian.
-        let mut n = params.6;
-        if let Some(value) = n {
-            n = Some(value.to_be());
-        }
-
-        let res = self.insert_statement.execute(params!(
-            reminder::serialize_frequency(params.0),
-            *(params.7),
-            params.1,
-            params.2,
-            params.3,
-            params.4,
-            params.5,
-            n
+        let n = reminder.n.map(|value| value.to_be());
+
+        let res = self.insert_statement.insert(params!(
+            reminder::serialize_frequency(reminder.frequency),
+            reminder.message,
+            reminder.month,
+            reminder.day,
+            reminder.year,
+            reminder.hour,
+            reminder.minute,
+            n,
+            reminder.renotify,
+            reminder.rule.as_ref().map(|rule| rule.to_string()),
+            reminder
+                .start
+                .map(|start| start.format(DATE_FORMAT).to_string()),
+            reminder.catch_up.map(reminder::serialize_catch_up),
+            reminder.zone.map(|zone| zone.name()),
+            reminder.to_instant().map(|at| at.timestamp()),
+            priority(&reminder),
+            reminder
+                .until
+                .map(|until| until.format(DATE_FORMAT).to_string()),
+            reminder.remaining,
+            alerts(&reminder),
+            next_alert(&reminder),
+            reminder.title,
+            reminder.urgency.map(reminder::serialize_urgency),
+            reminder.icon,
+            reminder.category,
+            reminder.timeout,
+            sinks(&reminder)
         ));
 
         if let Err(err) = res {
             let fmt_str = format!("Failed to insert reminder: {}\n", err);
             let _ = log.write_all(fmt_str.as_bytes());
-            return false;
-        } else {
-            let res = res.unwrap();
-            if res != 1 {
-                let fmt_str = format!("Row count changed: {}\n", res);
-                let _ = log.write_all(fmt_str.as_bytes());
-                return false;
-            }
+            return None;
         }
-        return true;
+        return Some(res.unwrap() as u32);
     }
 
-    pub fn delete(&mut self, id: u32, mut log: &File) -> bool {
+    // Replaces the stored reminder, returning the number of rows changed, None if the statement failed
+    pub fn update(&mut self, id: u32, reminder: &Reminder, mut log: &File) -> Option<usize> {
+        let n = reminder.n.map(|value| value.to_be());
+
+        let res = self.update_statement.execute(params!(
+            reminder::serialize_frequency(reminder.frequency),
+            reminder.message,
+            reminder.month,
+            reminder.day,
+            reminder.year,
+            reminder.hour,
+            reminder.minute,
+            n,
+            reminder.renotify,
+            reminder.rule.as_ref().map(|rule| rule.to_string()),
+            reminder
+                .start
+                .map(|start| start.format(DATE_FORMAT).to_string()),
+            reminder.catch_up.map(reminder::serialize_catch_up),
+            reminder.zone.map(|zone| zone.name()),
+            reminder.to_instant().map(|at| at.timestamp()),
+            priority(reminder),
+            reminder
+                .until
+                .map(|until| until.format(DATE_FORMAT).to_string()),
+            reminder.remaining,
+            alerts(reminder),
+            next_alert(reminder),
+            reminder.title,
+            reminder.urgency.map(reminder::serialize_urgency),
+            reminder.icon,
+            reminder.category,
+            reminder.timeout,
+            sinks(reminder),
+            id
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Failed to update reminder {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(res.unwrap());
+    }
+
+    // Returns the number of rows removed, None if the statement failed
+    pub fn delete(&mut self, id: u32, mut log: &File) -> Option<usize> {
         let rows = self.delete_statement.execute(params!(id));
         if let Err(err) = rows {
             let fmt_str = format!("Error deleting id {}: {}\n", id, err);
             let _ = log.write_all(fmt_str.as_bytes());
-            return false;
+            return None;
         }
-        let count = rows.unwrap();
-        if count == 0 {
+        return Some(rows.unwrap());
+    }
+
+    // Sends the reminder again at the given time, returning the number of rows changed
+    pub fn snooze(&mut self, id: u32, until: DateTime<Utc>, mut log: &File) -> Option<usize> {
+        let once = reminder::serialize_frequency(reminder::Frequency::ONCE);
+        // The new time of a ONCE reminder is set in its own zone
+        let zone = self
+            .zone_statement
+            .query_row(params!(id), |row| row.get::<_, Option<String>>(0))
+            .optional()
+            .map(|zone| zone.flatten().and_then(|zone| zone::parse_zone(&zone).ok()));
+        let moved = zone.and_then(|zone| {
+            let wall = zone::wall_time(zone, until);
+            self.snooze_once_statement.execute(params!(
+                wall.year(),
+                wall.month(),
+                wall.day(),
+                wall.hour(),
+                wall.minute(),
+                until.timestamp(),
+                id,
+                once
+            ))
+        });
+        let snoozed = moved.and_then(|moved| {
+            let snoozed = self
+                .snooze_statement
+                .execute(params!(until.timestamp(), id, once))?;
+            Ok(moved + snoozed)
+        });
+        if let Err(err) = snoozed {
+            let fmt_str = format!("Error snoozing id {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(snoozed.unwrap());
+    }
+
+    // Stops the repeated notifications of a sent reminder, returning the number of rows changed
+    pub fn ack(&mut self, id: u32, mut log: &File) -> Option<usize> {
+        let once = reminder::serialize_frequency(reminder::Frequency::ONCE);
+        let acked = self
+            .ack_once_statement
+            .execute(params!(id, once))
+            .and_then(|deleted| Ok(deleted + self.ack_statement.execute(params!(id))?));
+        if let Err(err) = acked {
+            let fmt_str = format!("Error acknowledging id {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(acked.unwrap());
+    }
+
+    // Pauses a reminder, returning the number of rows changed
+    pub fn pause(&mut self, id: u32, pause: Pause, mut log: &File) -> Option<usize> {
+        let once = reminder::serialize_frequency(reminder::Frequency::ONCE);
+        let mode = if pause.hold { PAUSED_HOLD } else { PAUSED_SKIP };
+        let res = self.pause_statement.execute(params!(
+            once,
+            PAUSED_HOLD,
+            mode,
+            pause.until.map(|until| until.timestamp()),
+            id
+        ));
+        if let Err(err) = res {
+            let fmt_str = format!("Error pausing id {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(res.unwrap());
+    }
+
+    // Resumes a paused reminder, returning the number of rows changed
+    pub fn resume(&mut self, id: u32, mut log: &File) -> Option<usize> {
+        let res = self.resume_statement.execute(params!(id));
+        if let Err(err) = res {
+            let fmt_str = format!("Error resuming id {}: {}\n", id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(res.unwrap());
+    }
+
+    pub fn dnd(&mut self, mut log: &File) -> Option<DoNotDisturb> {
+        let dnd = read_dnd(&mut self.dnd_statement);
+        if let Err(err) = dnd {
+            let fmt_str = format!("Error reading do not disturb: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(dnd.unwrap());
+    }
+
+    // How notifications are getting through, as recorded by the notifier thread
+    pub fn delivery_status(&mut self, mut log: &File) -> Option<DeliveryStatus> {
+        // Any value of the state table is read as do not disturb is
+        let res = read_degraded(&mut self.dnd_statement).and_then(|degraded| {
+            let queued = self.queued_statement.query_row([], |row| row.get(0))?;
+            Ok(DeliveryStatus { queued, degraded })
+        });
+        if let Err(err) = res {
+            let fmt_str = format!("Error reading state of delivery: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        return Some(res.unwrap());
+    }
+
+    pub fn set_dnd(&mut self, dnd: DoNotDisturb, mut log: &File) -> bool {
+        let res = match dnd {
+            DoNotDisturb::Off => self.clear_dnd_statement.execute(params!(DND_STATE)),
+            DoNotDisturb::On => self.set_dnd_statement.execute(params!(DND_STATE, DND_ON)),
+            DoNotDisturb::Until(until) => self
+                .set_dnd_statement
+                .execute(params!(DND_STATE, until.format(TIME_FORMAT).to_string())),
+        };
+        if let Err(err) = res {
+            let fmt_str = format!("Error setting do not disturb: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
             return false;
         }
         return true;
     }
 }
+
+// Reads a row of the reminder table, whose later columns are looked up by name
+fn reminder_from_row(row: &rusqlite::Row) -> Result<(u32, Reminder), Error> {
+    let mut n: Option<u32> = row.get(8)?;
+    if let Some(v) = n {
+        n = Some(u32::from_be(v));
+    }
+
+    let frequency: u8 = row.get(1)?;
+    let mut reminder = Reminder::new(
+        reminder::deserialize_frequency(frequency).unwrap(),
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+        n,
+        row.get(2)?,
+    );
+    reminder.renotify = row.get("renotify_minutes")?;
+    let rule: Option<String> = row.get("rule")?;
+    reminder.rule = rule.and_then(|rule| rule.parse().ok());
+    let start: Option<String> = row.get("start")?;
+    reminder.start = start.and_then(|start| NaiveDate::parse_from_str(&start, DATE_FORMAT).ok());
+    let catch_up: Option<u8> = row.get("catch_up")?;
+    reminder.catch_up = catch_up.and_then(reminder::deserialize_catch_up);
+    let zone: Option<String> = row.get("zone")?;
+    reminder.zone = zone.and_then(|zone| zone::parse_zone(&zone).ok());
+    let priority: Option<u8> = row.get("priority")?;
+    reminder.high_priority = priority == Some(PRIORITY_HIGH);
+    let until: Option<String> = row.get("until")?;
+    reminder.until = until.and_then(|until| NaiveDate::parse_from_str(&until, DATE_FORMAT).ok());
+    reminder.remaining = row.get("remaining")?;
+    reminder.title = row.get("title")?;
+    let urgency: Option<u8> = row.get("urgency")?;
+    reminder.urgency = urgency.and_then(reminder::deserialize_urgency);
+    reminder.icon = row.get("icon")?;
+    reminder.category = row.get("category")?;
+    reminder.timeout = row.get("timeout")?;
+    let sinks: Option<String> = row.get("sinks")?;
+    reminder.sinks = sinks
+        .and_then(|sinks| reminder::parse_sinks(&sinks).ok())
+        .unwrap_or_default();
+    let paused: Option<u8> = row.get("paused")?;
+    let resume_at: Option<i64> = row.get("resume_at")?;
+    let alerts: Option<String> = row.get("alerts")?;
+    reminder.alerts = alerts
+        .iter()
+        .flat_map(|alerts| alerts.split(','))
+        .filter_map(|lead| lead.parse().ok())
+        .collect();
+    reminder.paused = paused.map(|paused| Pause {
+        until: resume_at.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+        hold: paused == PAUSED_HOLD,
+    });
+    return Ok((row.get(0)?, reminder));
+}
+
+// Warnings are stored as the minutes ahead of each, separated by commas
+fn alerts(reminder: &Reminder) -> Option<String> {
+    if reminder.alerts.is_empty() {
+        return None;
+    }
+    let leads: Vec<String> = reminder
+        .alerts
+        .iter()
+        .map(|lead| lead.to_string())
+        .collect();
+    return Some(leads.join(","));
+}
+
+// The reminder's own sinks as their names, such as "desktop,file", NULL to use the settings
+fn sinks(reminder: &Reminder) -> Option<String> {
+    if reminder.sinks.is_empty() {
+        return None;
+    }
+    let names: Vec<String> = reminder.sinks.iter().map(|sink| sink.to_string()).collect();
+    return Some(names.join(","));
+}
+
+// When the first warning still to come for the reminder's time is due, as a UTC timestamp
+fn next_alert(reminder: &Reminder) -> Option<i64> {
+    return reminder
+        .next_alert(Utc::now())
+        .map(|(at, _)| at.timestamp());
+}
+
+// The reminder with its date and time moved to `date`
+fn moved(reminder: &Reminder, date: NaiveDateTime) -> Reminder {
+    let mut moved = reminder.clone();
+    moved.year = date.year() as u32;
+    moved.month = date.month() as u8;
+    moved.day = date.day() as u8;
+    moved.hour = date.hour() as u8;
+    moved.minute = date.minute() as u8;
+    return moved;
+}
+
+// The priority column is NULL for an ordinary reminder
+const PRIORITY_HIGH: u8 = 1;
+
+fn priority(reminder: &Reminder) -> Option<u8> {
+    return Some(PRIORITY_HIGH).filter(|_| reminder.high_priority);
+}
+
+fn read_dnd(dnd_statement: &mut Statement) -> Result<DoNotDisturb, Error> {
+    let value = dnd_statement
+        .query_row(params!(DND_STATE), |row| row.get::<_, String>(0))
+        .optional()?;
+    return Ok(match value {
+        Some(value) if value == DND_ON => DoNotDisturb::On,
+        // Once passed it is the same as off
+        Some(value) => match parse_time(&value) {
+            Some(until) if until > Utc::now() => DoNotDisturb::Until(until),
+            _ => DoNotDisturb::Off,
+        },
+        None => DoNotDisturb::Off,
+    });
+}
+
+// Rows of the deferred or undelivered table, and the id and reminder of any that can't be read
+type DeferredRows = (Vec<Deferred>, Vec<(u32, u32)>);
+
+fn read_deferred<P: rusqlite::Params>(
+    statement: &mut Statement,
+    params: P,
+) -> Result<DeferredRows, Error> {
+    let rows = statement
+        .query_map(params, |row| {
+            let notice: Vec<u8> = row.get("notice")?;
+            let due: i64 = row.get("due_at")?;
+            let scheduled: Option<i64> = row.get("scheduled_at")?;
+            let number: Option<u32> = row.get("occurrence")?;
+            Ok((
+                row.get("id")?,
+                row.get("reminder")?,
+                notice,
+                due,
+                (scheduled, number),
+            ))
+        })?
+        .collect::<Result<Vec<(u32, u32, Vec<u8>, i64, (Option<i64>, Option<u32>))>, Error>>()?;
+
+    let (mut deferred, mut unreadable) = (Vec::new(), Vec::new());
+    for (id, reminder, notice, due, (scheduled, number)) in rows {
+        let notice = Reminder::deserialize_reminder(&notice);
+        let due = Utc.timestamp_opt(due, 0).single();
+        match (notice, due) {
+            (Ok(notice), Some(due)) => deferred.push(Deferred {
+                id,
+                reminder,
+                notice,
+                due,
+                scheduled: scheduled.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+                number,
+            }),
+            _ => unreadable.push((id, reminder)),
+        }
+    }
+    return Ok((deferred, unreadable));
+}
+
+fn read_degraded(
+    state_statement: &mut Statement,
+) -> Result<Option<(DateTime<Utc>, String)>, Error> {
+    let mut read = |name: &str| {
+        state_statement
+            .query_row(params!(name), |row| row.get::<_, String>(0))
+            .optional()
+    };
+    let since = read(DEGRADED_SINCE)?.and_then(|since| parse_time(&since));
+    return Ok(since.zip(read(DEGRADED_REASON)?));
+}
+
+// Reads a time stored as UTC text
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    return NaiveDateTime::parse_from_str(text, TIME_FORMAT)
+        .ok()
+        .map(|time| Utc.from_utc_datetime(&time));
+}
+
+/*
+* Fills in due_at for databases from before it existed, when their snooze and re-notification
+* times were still stored in local time, which are converted to UTC. It runs as part of the first
+* migration, so only reads the columns a database has at that step, never reminder_from_row.
+*/
+pub fn fill_due_times(conn: &Connection) -> Result<(), Error> {
+    let mut select = conn.prepare(
+        "SELECT id, year, month, day, hour, minute, zone, snoozed_until, renotify_at \
+         FROM reminder",
+    )?;
+    let rows = select
+        .query_map([], |row| {
+            let id: u32 = row.get("id")?;
+            let (hour, minute) = (row.get("hour")?, row.get("minute")?);
+            let time =
+                NaiveDate::from_ymd_opt(row.get("year")?, row.get("month")?, row.get("day")?)
+                    .and_then(|date| date.and_hms_opt(hour, minute, 0));
+            let zone: Option<String> = row.get("zone")?;
+            let zone = zone.and_then(|zone| zone::parse_zone(&zone).ok());
+            let snoozed_until: Option<String> = row.get("snoozed_until")?;
+            let renotify_at: Option<String> = row.get("renotify_at")?;
+            Ok((id, time, zone, snoozed_until, renotify_at))
+        })?
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let to_utc = |text: Option<String>| {
+        let time = NaiveDateTime::parse_from_str(&text?, TIME_FORMAT).ok()?;
+        Some(zone::resolve(None, time)?.format(TIME_FORMAT).to_string())
+    };
+    for (id, time, zone, snoozed_until, renotify_at) in rows {
+        let due_at = time.and_then(|time| zone::resolve(zone, time));
+        conn.execute(
+            "UPDATE reminder SET due_at = ?, snoozed_until = ?, renotify_at = ? WHERE id = ?",
+            params!(
+                due_at.map(|at| at.timestamp()),
+                to_utc(snoozed_until),
+                to_utc(renotify_at),
+                id
+            ),
+        )?;
+    }
+    return Ok(());
+}
@@ -0,0 +1,56 @@
+// Module running the command the user set up to run whenever a reminder fires
+
+use crate::command::{self, Payload};
+use crate::config::Settings;
+use crate::db::Due;
+use chrono::Utc;
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct Hook {
+    command: String,
+    timeout: Duration,
+    // Where how each run ended is written, once it has
+    log: Arc<Mutex<File>>,
+}
+
+impl Hook {
+    // The hook of the settings, None when there isn't one
+    pub fn new(settings: &Settings, log: &Arc<Mutex<File>>) -> Option<Hook> {
+        return settings.hook.clone().map(|command| Hook {
+            command,
+            timeout: settings.hook_timeout,
+            log: Arc::clone(log),
+        });
+    }
+
+    /*
+     * Starts the command, telling it of the reminder as command::Payload describes. One still
+     * running after the timeout is killed.
+     */
+    pub fn run(&self, due: &Due, mut log: &File) {
+        let reminder = &due.reminder;
+        let payload = Payload {
+            id: Some(due.id),
+            summary: reminder
+                .title
+                .clone()
+                .unwrap_or_else(|| "Hermes".to_string()),
+            body: reminder.message.clone(),
+            scheduled: Some(reminder.to_instant().unwrap_or_else(Utc::now)),
+            occurrence: due.number(),
+            urgency: reminder.urgency,
+            category: reminder.category.clone(),
+            sent: Utc::now(),
+        };
+        let name = format!("Hook for reminder {}", due.id);
+        if let Err(err) =
+            command::spawn(&self.command, &payload, Some(self.timeout), name, &self.log)
+        {
+            let fmt_str = format!("Failed to run hook for reminder {}: {}\n", due.id, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+        }
+    }
+}
@@ -1,17 +1,29 @@
-use chrono;
-use dbus::blocking::Connection;
+// Explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
 
+use hermes_proto::zone;
+
+pub mod bus;
 pub mod comm;
+pub mod command;
 pub mod config;
 pub mod db;
-pub mod reminder;
+pub mod hook;
+pub mod migrations;
+pub mod notifier;
+pub mod quiet;
+pub mod scheduler;
 pub mod socket;
 
+// How long the delivery table keeps what became of each notification
+const DELIVERIES_KEPT_DAYS: i64 = 90;
+
 fn main() {
     let proc_id: libc::pid_t = unsafe { libc::fork() };
     if proc_id < 0 {
@@ -48,6 +60,7 @@ fn main() {
     }
     let db_conn = db_conn.unwrap();
     let _ = log.write_all(b"SUCCESSFUL DB CONN\n");
+    let settings = config::load_settings(&log);
 
     let _ = log.write_all(b"SUCCESSFUL API SETUP\n");
     //Now Daemon is in proper environment, with a database connection
@@ -56,7 +69,16 @@ fn main() {
     if socket.is_none() {
         return; // Socket binding failed, terminate (already logged)
     }
-    let mut socket = socket.unwrap();
+    let socket = socket.unwrap();
+
+    let scheduler = scheduler::Scheduler::new(&log);
+    if scheduler.is_none() {
+        return; // Already logged
+    }
+    let (mut scheduler, waker) = scheduler.unwrap();
+    let waker_notifier = waker.clone();
+    // Loads the queue on the first pass of the notifier
+    waker.wake();
 
     let database_lock = Arc::new(Mutex::new(db_conn)); // mutex to sync database use, as threads have different statements
 
@@ -64,47 +86,216 @@ fn main() {
     let log_lock = Arc::new(Mutex::new(log));
     let log_lock_notifier = Arc::clone(&log_lock);
     thread::spawn(move || {
-        let conn = Connection::new_session();
-        if let Err(e) = &conn {
-            return;
-        }
-        let conn = conn.unwrap();
+        let mut notifiers = notifier::Notifiers::new(&settings, &log_lock_notifier);
+        let hook = hook::Hook::new(&settings, &log_lock_notifier);
+        let shown: comm::Shown = Arc::new(Mutex::new(HashMap::new()));
+        // The computer's zone as reminders in local time were last worked out for, None at first
+        let mut offsets = None;
 
         loop {
-            thread::sleep(Duration::from_millis(60000));
+            // Connects to D-Bus, and again whenever it is lost, waiting longer after each failure
+            let bus_changed = notifiers
+                .bus_mut()
+                .reconnect(&log_lock_notifier.lock().unwrap());
+            if let Some(conn) = notifiers.bus().conn().filter(|_| bus_changed) {
+                // Notifications shown over a lost connection can't be acted on any more
+                shown.lock().unwrap().clear();
+                if let Err(err) = comm::watch_notifications(
+                    conn,
+                    Arc::clone(&shown),
+                    Arc::clone(&db_lock_notifier),
+                    Arc::clone(&log_lock_notifier),
+                    waker_notifier.clone(),
+                ) {
+                    let fmt_str = format!("Failed to watch notifications: {}\n", err);
+                    let _ = log_lock_notifier
+                        .lock()
+                        .unwrap()
+                        .write_all(fmt_str.as_bytes());
+                }
+            }
+
+            // Answer notification actions while waiting for the next reminder
+            let changed = bus_changed || scheduler.wait(notifiers.bus(), chrono::Utc::now());
+            shown
+                .lock()
+                .unwrap()
+                .retain(|_, (_, _, sent)| sent.elapsed() < comm::SHOWN_FOR);
+
+            let due = scheduler.take_due(chrono::Utc::now());
+            // Looked at on every wait, so a change of zone is seen within a minute
+            let local_offsets = Some(zone::local_offsets(chrono::Utc::now()));
+            let zone_changed = local_offsets != offsets;
+            if !changed && !due && !zone_changed {
+                continue;
+            }
+
             let mut log = log_lock_notifier.lock().unwrap();
-            log.write_all(b"woke up\n");
-            let mut db_lock = db_lock_notifier.lock().unwrap();
-            log.write_all(b"got db lock\n");
-            let notifier_statements = db::NotificationStatements::new(&*db_lock, &mut *log); //TODO: fix the log issue
+            let _ = log.write_all(b"woke up\n");
+            let db_lock = db_lock_notifier.lock().unwrap();
+            let _ = log.write_all(b"got db lock\n");
+            let notifier_statements = db::NotificationStatements::new(&db_lock, &mut log); //TODO: fix the log issue
             if notifier_statements.is_none() {
-                log.write_all(b"failed to construct statements");
+                let _ = log.write_all(b"failed to construct statements");
                 return;
             }
-            log.write_all(b"made statements");
+            let _ = log.write_all(b"made statements");
             let mut notifier_statements = notifier_statements.unwrap();
+            notifier_statements.resume_due(&log);
+            if zone_changed && notifier_statements.retime_local(&log) {
+                offsets = local_offsets;
+            }
+            notifier_statements.prune_deliveries(
+                chrono::Utc::now() - chrono::Duration::days(DELIVERIES_KEPT_DAYS),
+                &log,
+            );
+            if bus_changed {
+                notifier_statements.set_degraded(notifiers.bus().lost(), &log);
+                // What was waiting for the desktop needn't wait out its retry
+                if notifiers.bus().conn().is_some() {
+                    notifier_statements.retry_now(&log);
+                }
+            }
+            let dnd = notifier_statements.dnd(&log);
+            let held_until = quiet::held_until(&settings, dnd, chrono::Utc::now());
+
+            // What to send is worked out here, and sent once the database and log are let go
+            let mut outgoing = Vec::new();
+            if due {
+                // Warnings ahead aren't held back, only dropped, as the reminder itself will be
+                for (id, reminder, lead) in notifier_statements.get_alerts(&log).unwrap_or_default()
+                {
+                    match lead {
+                        Some(_) if held_until.is_some() && !reminder.high_priority => {
+                            let fmt_str = format!("Dropped warning of reminder {}\n", id);
+                            let _ = log.write_all(fmt_str.as_bytes());
+                        }
+                        Some(lead) => outgoing.push(comm::Outgoing::Alert {
+                            id,
+                            reminder: reminder.clone(),
+                            lead,
+                        }),
+                        None => {}
+                    }
+                    notifier_statements.alerted(id, &reminder, &log);
+                }
 
-            let reminders_to_send = notifier_statements.get_notifications(&*log);
-
-            if !reminders_to_send.is_none() {
-                let reminders = reminders_to_send.unwrap();
-                let fmt_str = format!(
-                    "At time {}, found {} reminders to send",
-                    chrono::offset::Local::now(),
-                    reminders.len()
-                );
-                log.write_all(fmt_str.as_bytes());
-                for (id, reminder) in reminders {
-                    comm::notify(&reminder.serialize(), &conn, &*log);
-                    notifier_statements.update_notification((id, reminder), &*log);
+                let reminders_to_send = notifier_statements.get_notifications(&log);
+
+                if let Some(reminders) = reminders_to_send {
+                    let fmt_str = format!(
+                        "At time {}, found {} reminders to send",
+                        chrono::offset::Local::now(),
+                        reminders.len()
+                    );
+                    let _ = log.write_all(fmt_str.as_bytes());
+                    let now = chrono::Utc::now();
+                    for due in reminders {
+                        // Paused to skip its occurrences, so only moved on
+                        if due.reminder.paused.is_some() {
+                            let fmt_str = format!("Skipped paused reminder {}\n", due.id);
+                            let _ = log.write_all(fmt_str.as_bytes());
+                            notifier_statements.update_notification(due, false, None, &log);
+                            continue;
+                        }
+                        let notices = comm::catch_up(&due, settings.catch_up, now);
+                        if notices.is_empty() {
+                            let fmt_str = format!("Skipped missed reminder {}\n", due.id);
+                            let _ = log.write_all(fmt_str.as_bytes());
+                        }
+                        // The hook runs when the time comes, even if the notification is held back
+                        if let Some(hook) = &hook {
+                            if due.scheduled && !notices.is_empty() {
+                                hook.run(&due, &log);
+                            }
+                        }
+                        let sent = !notices.is_empty();
+                        let last = notices.last().and_then(|occurrence| occurrence.scheduled);
+                        if held_until.is_some() && !due.reminder.high_priority {
+                            for occurrence in notices.iter() {
+                                let (notice, scheduled) =
+                                    (&occurrence.notice, occurrence.scheduled);
+                                notifier_statements.defer(&due, notice, scheduled, &log);
+                            }
+                            notifier_statements.update_notification(due, sent, last, &log);
+                            continue;
+                        }
+
+                        // A reminder sent again replaces its notification still on screen
+                        let mut shown = shown.lock().unwrap();
+                        let mut replaces = shown
+                            .iter()
+                            .find(|(_, (id, _, _))| *id == due.id)
+                            .map_or(0, |(notification, _)| *notification);
+                        shown.remove(&replaces);
+                        drop(shown);
+
+                        for occurrence in notices {
+                            outgoing.push(comm::Outgoing::Notification {
+                                id: due.id,
+                                reminder: occurrence.notice,
+                                scheduled: occurrence.scheduled,
+                                number: due.number(),
+                                due: now,
+                                replaces,
+                            });
+                            // Missed occurrences each get their own notification
+                            replaces = 0;
+                        }
+                        notifier_statements.update_notification(due, sent, last, &log);
+                    }
+                } else {
+                    let fmt_str = format!(
+                        "At time {}, found {} reminders to send",
+                        chrono::offset::Local::now(),
+                        0
+                    );
+                    let _ = log.write_all(fmt_str.as_bytes());
+                }
+            }
+            if held_until.is_none() {
+                if let Some(held) = notifier_statements.deferred(&log) {
+                    outgoing.extend(comm::held(held, settings.quiet_delivery));
+                }
+                if let Some(undelivered) = notifier_statements.undelivered(chrono::Utc::now(), &log)
+                {
+                    outgoing.extend(comm::retries(
+                        undelivered,
+                        &notifiers,
+                        &mut notifier_statements,
+                        &log,
+                    ));
                 }
-            } else {
-                let fmt_str = format!(
-                    "At time {}, found {} reminders to send",
-                    chrono::offset::Local::now(),
-                    0
-                );
-                log.write_all(fmt_str.as_bytes());
+            }
+
+            // A slow notifier holds up neither requests on the socket nor notification actions
+            drop(notifier_statements);
+            drop(db_lock);
+            drop(log);
+            let sent: Vec<_> = outgoing
+                .into_iter()
+                .map(|outgoing| {
+                    let attempts = comm::send(&outgoing, &notifiers);
+                    (outgoing, attempts)
+                })
+                .collect();
+
+            let mut log = log_lock_notifier.lock().unwrap();
+            let db_lock = db_lock_notifier.lock().unwrap();
+            let notifier_statements = db::NotificationStatements::new(&db_lock, &mut log);
+            if notifier_statements.is_none() {
+                let _ = log.write_all(b"failed to construct statements");
+                return;
+            }
+            let mut notifier_statements = notifier_statements.unwrap();
+            for (outgoing, attempts) in sent {
+                comm::settle(outgoing, attempts, &shown, &mut notifier_statements, &log);
+            }
+            scheduler.reload(&mut notifier_statements, &log);
+            // Held back reminders are sent once quiet hours are over, and undelivered ones retried
+            match held_until {
+                Some(_) => scheduler.wake_at(held_until),
+                None => scheduler.wake_at(notifier_statements.next_retry(&log)),
             }
         }
     });
@@ -114,25 +305,21 @@ fn main() {
 
         // Get access to log
         let mut log = log_lock.lock().unwrap();
-        let db_conn = database_lock.lock().unwrap();
-
-        let api_statements = db::PreparedStatements::new(&db_conn, &mut log);
-        if api_statements.is_none() {
-            return;
-        }
-        let mut api_statements = api_statements.unwrap();
-
         let _ = log.write_all(b"RECEIVED MESSAGE\n");
         if let Err(err) = data {
             let fmt_str = format!("Error while receiving data: {}\n", err);
             let _ = log.write_all(fmt_str.as_bytes());
+            continue;
         }
         let data = data.unwrap();
-        comm::handle_message(&data, &mut *log, &mut api_statements, &socket);
-        let mut new_sock = socket::set_socket(&*log);
-        while new_sock.is_none() {
-            new_sock = socket::set_socket(&*log);
+
+        let db_conn = database_lock.lock().unwrap();
+        let api_statements = db::PreparedStatements::new(&db_conn, &mut log);
+        if api_statements.is_none() {
+            return;
         }
-        socket = new_sock.unwrap(); // Reset socket
+        let mut api_statements = api_statements.unwrap();
+
+        comm::handle_message(&data, &mut log, &mut api_statements, &socket, &waker);
     }
 }
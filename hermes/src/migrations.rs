@@ -0,0 +1,427 @@
+// Module upgrading the database to the schema this Hermes uses, one numbered step at a time
+
+use crate::db;
+use rusqlite::{Connection, Transaction};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/*
+* Step n takes the database from version n to n + 1, the version being kept in PRAGMA user_version.
+* A database may have been left at any earlier version, so steps are only ever appended.
+*/
+const MIGRATIONS: &[Migration] = &[
+    unversioned,
+    quiet_hours,
+    end_conditions,
+    pauses,
+    alerts,
+    presentation,
+    sinks,
+    fired,
+    undelivered,
+    deliveries,
+    occurrences,
+    timestamps,
+    numbers,
+];
+
+// The version a database is at once every step has run
+pub const VERSION: u32 = MIGRATIONS.len() as u32;
+
+/*
+* Brings the database at `path` up to VERSION, copying it to `path.v<old version>.bak` first. Each
+* step runs in its own transaction, so a failure leaves the database at the last finished version.
+* A database from a newer Hermes is left alone, and false returned so it isn't used.
+*/
+pub fn migrate(db_conn: &mut Connection, path: &str, mut log: &File) -> bool {
+    let version = db_conn.query_row("PRAGMA user_version", [], |row| row.get::<_, u32>(0));
+    if let Err(err) = version {
+        let fmt_str = format!("Error reading database version: {}\n", err);
+        let _ = log.write_all(fmt_str.as_bytes());
+        return false;
+    }
+    let version = version.unwrap();
+    if version > VERSION {
+        let fmt_str = format!(
+            "Database {} is at version {}, but this Hermes only understands up to {}. Update Hermes, or restore a backup made before the upgrade\n",
+            path, version, VERSION
+        );
+        let _ = log.write_all(fmt_str.as_bytes());
+        return false;
+    }
+    if version == VERSION {
+        return true;
+    }
+
+    // A new database has nothing to lose
+    let tables = db_conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+        row.get::<_, u32>(0)
+    });
+    if let Err(err) = tables {
+        let fmt_str = format!("Error reading database tables: {}\n", err);
+        let _ = log.write_all(fmt_str.as_bytes());
+        return false;
+    }
+    if tables.unwrap() > 0 {
+        let backup = format!("{}.v{}.bak", path, version);
+        if let Err(err) = fs::copy(path, &backup) {
+            let fmt_str = format!("Error backing up database to {}: {}\n", backup, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+        let fmt_str = format!(
+            "Backed up database to {} before upgrading it from version {}\n",
+            backup, version
+        );
+        let _ = log.write_all(fmt_str.as_bytes());
+    }
+
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let target = step as u32 + 1;
+        let migrated = db_conn.transaction().and_then(|tx| {
+            migration(&tx)?;
+            tx.execute_batch(&format!("PRAGMA user_version = {}", target))?;
+            tx.commit()
+        });
+        if let Err(err) = migrated {
+            let fmt_str = format!("Error upgrading database to version {}: {}\n", target, err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return false;
+        }
+    }
+    let fmt_str = format!("Upgraded database to version {}\n", VERSION);
+    let _ = log.write_all(fmt_str.as_bytes());
+    return true;
+}
+
+/*
+* Databases from before versioning grew a column at a time, so this creates whatever is missing
+* rather than assuming any one starting point.
+*/
+fn unversioned(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        " CREATE TABLE IF NOT EXISTS reminder (\
+           id INTEGER PRIMARY KEY,\
+           frequency INTEGER NOT NULL,\
+           message TEXT NOT NULL,\
+           month INTEGER NOT NULL,\
+           day INTEGER NOT NULL,\
+	   year INTEGER NOT NULL,\
+           hour INTEGER NOT NULL,\
+           minute INTEGER NOT NULL,\
+	   n INTEGER
+	   );",
+        [],
+    )?;
+
+    let columns = [
+        ("snoozed_until", "TEXT"),
+        ("renotify_minutes", "INTEGER"),
+        ("renotify_at", "TEXT"),
+        ("rule", "TEXT"),
+        ("start", "TEXT"),
+        ("catch_up", "INTEGER"),
+        ("zone", "TEXT"),
+    ];
+    for (column, definition) in columns.iter() {
+        add_column(tx, column, definition)?;
+    }
+    if add_column(tx, "due_at", "INTEGER")? {
+        db::fill_due_times(tx)?;
+    }
+    return Ok(());
+}
+
+// Reminders held back during quiet hours, and do not disturb, which outlast a restart
+fn quiet_hours(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN priority INTEGER;\
+         CREATE TABLE deferred (\
+           id INTEGER PRIMARY KEY,\
+           reminder INTEGER NOT NULL,\
+           notice BLOB NOT NULL,\
+           due_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE state (\
+           name TEXT PRIMARY KEY,\
+           value TEXT NOT NULL\
+         );",
+    );
+}
+
+// The last date of a recurring reminder, and how many occurrences it has left
+fn end_conditions(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN until TEXT;\
+         ALTER TABLE reminder ADD COLUMN remaining INTEGER;",
+    );
+}
+
+// Whether a reminder is paused, skipping or holding its occurrences, and when it resumes
+fn pauses(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN paused INTEGER;\
+         ALTER TABLE reminder ADD COLUMN resume_at TEXT;",
+    );
+}
+
+// How far ahead of each occurrence to warn, and when the next warning is due as a UTC timestamp
+fn alerts(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN alerts TEXT;\
+         ALTER TABLE reminder ADD COLUMN alert_at INTEGER;",
+    );
+}
+
+// How a reminder's notification looks, and how long it stays on screen
+fn presentation(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN title TEXT;\
+         ALTER TABLE reminder ADD COLUMN urgency INTEGER;\
+         ALTER TABLE reminder ADD COLUMN icon TEXT;\
+         ALTER TABLE reminder ADD COLUMN category TEXT;\
+         ALTER TABLE reminder ADD COLUMN timeout INTEGER;",
+    );
+}
+
+// Which notifiers a reminder is sent through, NULL for those of the settings
+fn sinks(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch("ALTER TABLE reminder ADD COLUMN sinks TEXT;");
+}
+
+// How many of a reminder's occurrences have been sent
+fn fired(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch("ALTER TABLE reminder ADD COLUMN fired INTEGER NOT NULL DEFAULT 0;");
+}
+
+// Notifications no notifier could take, kept to send again once one can
+fn undelivered(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "CREATE TABLE undelivered (\
+           id INTEGER PRIMARY KEY,\
+           reminder INTEGER NOT NULL,\
+           notice BLOB NOT NULL,\
+           due_at INTEGER NOT NULL\
+         );",
+    );
+}
+
+/*
+* What each notifier made of each notification: the reminder, NULL for a digest, the time it was
+* due and the time it was sent as UTC timestamps, the sink, and whether it took the notification,
+* with the error if not or the id of the desktop notification. The kind says whether it was a
+* reminder's notification, a warning ahead of it, a digest, or a retry. Undelivered notifications
+* count how often they were tried, and when they are next, as a UTC timestamp.
+*/
+fn deliveries(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "CREATE TABLE delivery (\
+           id INTEGER PRIMARY KEY,\
+           reminder INTEGER,\
+           scheduled_at INTEGER,\
+           sent_at INTEGER NOT NULL,\
+           backend TEXT NOT NULL,\
+           result TEXT NOT NULL,\
+           error TEXT,\
+           notification INTEGER,\
+           kind TEXT NOT NULL\
+         );\
+         CREATE INDEX delivery_sent_at ON delivery (sent_at);\
+         ALTER TABLE undelivered ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;\
+         ALTER TABLE undelivered ADD COLUMN next_attempt INTEGER NOT NULL DEFAULT 0;",
+    );
+}
+
+/*
+* When the occurrence a notification stands for was scheduled, as a UTC timestamp. A reminder keeps
+* that of the last one it sent, which its snoozes and repeats are of.
+*/
+fn occurrences(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE reminder ADD COLUMN occurrence_at INTEGER;\
+         ALTER TABLE deferred ADD COLUMN scheduled_at INTEGER;\
+         ALTER TABLE undelivered ADD COLUMN scheduled_at INTEGER;",
+    );
+}
+
+/*
+* When a snooze, repeat or pause ends, as UTC timestamps rather than text to the minute. SQLite
+* can't change a column's type, and only recent versions drop one, so the table is made again.
+*/
+fn timestamps(tx: &Transaction) -> rusqlite::Result<()> {
+    let seconds = |column: &str| format!("CAST(strftime('%s', {}) AS INTEGER)", column);
+    return tx.execute_batch(&format!(
+        "CREATE TABLE reminder_new (\
+           id INTEGER PRIMARY KEY,\
+           frequency INTEGER NOT NULL,\
+           message TEXT NOT NULL,\
+           month INTEGER NOT NULL,\
+           day INTEGER NOT NULL,\
+           year INTEGER NOT NULL,\
+           hour INTEGER NOT NULL,\
+           minute INTEGER NOT NULL,\
+           n INTEGER,\
+           snoozed_until INTEGER,\
+           renotify_minutes INTEGER,\
+           renotify_at INTEGER,\
+           rule TEXT,\
+           start TEXT,\
+           catch_up INTEGER,\
+           zone TEXT,\
+           due_at INTEGER,\
+           priority INTEGER,\
+           until TEXT,\
+           remaining INTEGER,\
+           paused INTEGER,\
+           resume_at INTEGER,\
+           alerts TEXT,\
+           alert_at INTEGER,\
+           title TEXT,\
+           urgency INTEGER,\
+           icon TEXT,\
+           category TEXT,\
+           timeout INTEGER,\
+           sinks TEXT,\
+           fired INTEGER NOT NULL DEFAULT 0,\
+           occurrence_at INTEGER\
+         );\
+         INSERT INTO reminder_new SELECT id, frequency, message, month, day, year, hour, minute, \
+           n, {}, renotify_minutes, {}, rule, start, catch_up, zone, due_at, priority, until, \
+           remaining, paused, {}, alerts, alert_at, title, urgency, icon, category, timeout, \
+           sinks, fired, occurrence_at FROM reminder;\
+         DROP TABLE reminder;\
+         ALTER TABLE reminder_new RENAME TO reminder;",
+        seconds("snoozed_until"),
+        seconds("renotify_at"),
+        seconds("resume_at")
+    ));
+}
+
+// Which of its reminder's occurrences a held back or undelivered notification is, counting from 1
+fn numbers(tx: &Transaction) -> rusqlite::Result<()> {
+    return tx.execute_batch(
+        "ALTER TABLE deferred ADD COLUMN occurrence INTEGER;\
+         ALTER TABLE undelivered ADD COLUMN occurrence INTEGER;",
+    );
+}
+
+// Adds a column to the reminder table unless it is already there, returning whether it was added
+fn add_column(tx: &Transaction, column: &str, definition: &str) -> rusqlite::Result<bool> {
+    let exists: u32 = tx.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('reminder') WHERE name = ?",
+        [column],
+        |row| row.get(0),
+    )?;
+    if exists > 0 {
+        return Ok(false);
+    }
+    tx.execute(
+        &format!("ALTER TABLE reminder ADD COLUMN {} {}", column, definition),
+        [],
+    )?;
+    return Ok(true);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{NotificationStatements, PreparedStatements};
+    use crate::migrations::{migrate, VERSION};
+    use chrono::NaiveDate;
+    use hermes_proto::zone;
+    use rusqlite::Connection;
+    use std::env;
+    use std::fs::{self, File};
+
+    // A database as left by a Hermes from before versioning, with a snoozed reminder in it
+    const BASELINE: &str = "CREATE TABLE reminder (\
+           id INTEGER PRIMARY KEY,\
+           frequency INTEGER NOT NULL,\
+           message TEXT NOT NULL,\
+           month INTEGER NOT NULL,\
+           day INTEGER NOT NULL,\
+           year INTEGER NOT NULL,\
+           hour INTEGER NOT NULL,\
+           minute INTEGER NOT NULL,\
+           n INTEGER,\
+           snoozed_until TEXT\
+         );\
+         INSERT INTO reminder (frequency, message, month, day, year, hour, minute, n, snoozed_until)\
+           VALUES (2, 'Call home', 11, 2, 2030, 9, 30, NULL, '2030-11-02 09:40'),\
+                  (1, 'Water the plants', 1, 5, 2030, 8, 0, NULL, NULL);";
+
+    #[test]
+    fn upgrades_baseline_database_with_rows() {
+        let dir = env::temp_dir().join(format!("hermes-migrations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hermes.db");
+        let path = path.to_str().unwrap();
+        let log = File::create(dir.join("hermes.log")).unwrap();
+        let mut conn = Connection::open(path).unwrap();
+        conn.execute_batch(BASELINE).unwrap();
+
+        assert!(migrate(&mut conn, path, &log));
+        let version: u32 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, VERSION);
+        assert!(fs::metadata(format!("{}.v0.bak", path)).is_ok());
+        let unfilled: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM reminder WHERE due_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(unfilled, 0);
+        // The snooze, given in local time, is now a UTC timestamp
+        let snoozed_until: i64 = conn
+            .query_row(
+                "SELECT snoozed_until FROM reminder WHERE message = 'Call home'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let local = NaiveDate::from_ymd_opt(2030, 11, 2)
+            .unwrap()
+            .and_hms_opt(9, 40, 0)
+            .unwrap();
+        assert_eq!(
+            snoozed_until,
+            zone::resolve(None, local).unwrap().timestamp()
+        );
+
+        let mut statements = PreparedStatements::new(&conn, &mut log.try_clone().unwrap()).unwrap();
+        let reminders = statements.list(&log).unwrap();
+        let messages: Vec<&str> = reminders
+            .iter()
+            .map(|(_, reminder)| reminder.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["Call home", "Water the plants"]);
+
+        assert!(NotificationStatements::new(&conn, &mut log.try_clone().unwrap()).is_some());
+
+        drop(statements);
+        drop(conn);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn leaves_newer_database_alone() {
+        let dir = env::temp_dir().join(format!("hermes-migrations-newer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hermes.db");
+        let path = path.to_str().unwrap();
+        let log = File::create(dir.join("hermes.log")).unwrap();
+        let mut conn = Connection::open(path).unwrap();
+        conn.execute_batch(&format!("PRAGMA user_version = {}", VERSION + 1))
+            .unwrap();
+
+        assert!(!migrate(&mut conn, path, &log));
+
+        drop(conn);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
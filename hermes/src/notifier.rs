@@ -0,0 +1,534 @@
+// Module sending notifications, to the desktop or to whatever else should hear of a reminder
+
+use crate::bus::Bus;
+use crate::comm;
+use crate::command::{self, Payload};
+use crate::config::Settings;
+use chrono::{DateTime, Utc};
+use dbus::arg::messageitem::{MessageItem, MessageItemDict};
+use dbus::message as msg;
+use dbus::strings::Signature;
+use hermes_proto::reminder::{self, Reminder, Sink};
+use hermes_proto::zone;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv6Addr, TcpStream, ToSocketAddrs};
+use std::os::unix::fs::MetadataExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+// How long a local HTTP endpoint gets to answer
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A notification to send, for the reminder it belongs to
+pub struct Notice<'a> {
+    // Id of the reminder, None for a digest of several
+    pub id: Option<u32>,
+    pub summary: String,
+    pub body: String,
+    // The reminder it is for, which also says how it looks and where it goes
+    pub reminder: &'a Reminder,
+    // Whether the user can snooze it, where the sink lets them act on it
+    pub snooze: bool,
+    // Milliseconds on screen, 0 until dismissed
+    pub timeout: i32,
+    // Desktop notification it replaces, 0 for none
+    pub replaces: u32,
+    // When the occurrence it is for was scheduled, and which of the reminder's occurrences it is
+    pub scheduled: Option<DateTime<Utc>>,
+    pub occurrence: Option<u32>,
+}
+
+pub trait Notifier {
+    // Sends the notice, returning the id of a desktop notification the user can act on
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String>;
+}
+
+// See https://specifications.freedesktop.org/notification-spec/notification-spec-latest.html
+pub struct Desktop {
+    bus: Bus,
+}
+
+impl Notifier for Desktop {
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String> {
+        let conn = self.bus.conn().ok_or("Not connected to D-Bus")?;
+        let mut actions = Vec::new();
+        if notice.snooze {
+            actions.push(MessageItem::Str(comm::SNOOZE_ACTION.to_string()));
+            actions.push(MessageItem::Str(format!(
+                "Snooze {} min",
+                comm::SNOOZE_MINUTES
+            )));
+        }
+        actions.push(MessageItem::Str(comm::DISMISS_ACTION.to_string()));
+        actions.push(MessageItem::Str("Dismiss".to_string()));
+
+        let style = notice.reminder;
+        let mut hints = Vec::new();
+        if let Some(urgency) = style.urgency {
+            hints.push((
+                MessageItem::Str("urgency".to_string()),
+                MessageItem::Variant(Box::new(MessageItem::Byte(reminder::serialize_urgency(
+                    urgency,
+                )))),
+            ));
+        }
+        if let Some(category) = &style.category {
+            hints.push((
+                MessageItem::Str("category".to_string()),
+                MessageItem::Variant(Box::new(MessageItem::Str(category.clone()))),
+            ));
+        }
+        let hints =
+            MessageItemDict::new(hints, Signature::from("s"), Signature::from("v")).unwrap();
+
+        let mut dbus_msg = msg::Message::new_method_call(
+            "org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            "org.freedesktop.Notifications",
+            "Notify",
+        )?;
+        dbus_msg.append_items(&[
+            MessageItem::Str("Hermes".to_string()),
+            MessageItem::UInt32(notice.replaces),
+            MessageItem::Str(style.icon.clone().unwrap_or_default()),
+            MessageItem::Str(notice.summary.clone()),
+            MessageItem::Str(notice.body.clone()),
+            MessageItem::new_array(actions).unwrap(),
+            MessageItem::Dict(hints),
+            MessageItem::Int32(notice.timeout),
+        ]);
+
+        let reply = conn
+            .channel()
+            .send_with_reply_and_block(dbus_msg, Duration::from_secs(5))
+            .map_err(|err| err.to_string())?;
+        return Ok(Some(reply.read1::<u32>().map_err(|err| err.to_string())?));
+    }
+}
+
+// Appends a line for each notice to a file, with any further lines of the body indented
+pub struct Append {
+    path: String,
+}
+
+impl Notifier for Append {
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String> {
+        let mut line = format!(
+            "{} {}: {}",
+            zone::wall_time(None, Utc::now()).format(TIME_FORMAT),
+            notice.summary,
+            notice.body.trim_end().replace('\n', "\n    ")
+        );
+        if let Some(id) = notice.id {
+            line = format!("{} (reminder {})", line, id);
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| format!("Error opening {}: {}", self.path, err))?;
+        writeln!(file, "{}", line)
+            .map_err(|err| format!("Error writing {}: {}", self.path, err))?;
+        return Ok(None);
+    }
+}
+
+// Writes to every terminal the user is logged in on, as wall does
+pub struct Wall;
+
+impl Notifier for Wall {
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String> {
+        let mut text = format!(
+            "\r\n\x07Reminder from Hermes at {}:\r\n{}\r\n",
+            zone::wall_time(None, Utc::now()).format("%H:%M"),
+            notice.summary
+        );
+        for line in notice.body.trim_end().lines() {
+            text.push_str(&format!("{}\r\n", line));
+        }
+
+        let user = unsafe { libc::getuid() };
+        let mut written = 0;
+        for directory in ["/dev/pts", "/dev"] {
+            let entries = match fs::read_dir(directory) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let terminal = directory == "/dev/pts" || name.starts_with("tty");
+                let owned = entry
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.uid() == user);
+                if !terminal || !owned || name == "ptmx" {
+                    continue;
+                }
+                let opened = OpenOptions::new().write(true).open(entry.path());
+                if let Ok(mut terminal) = opened {
+                    if terminal.write_all(text.as_bytes()).is_ok() {
+                        written += 1;
+                    }
+                }
+            }
+        }
+        if written == 0 {
+            return Err("No terminal of the user to write to".to_string());
+        }
+        return Ok(None);
+    }
+}
+
+// Runs a command, telling it of the notice as command::Payload describes, and doesn't wait for it
+pub struct RunCommand {
+    command: String,
+    // Where how each run ended is written, once it has
+    log: Arc<Mutex<File>>,
+}
+
+impl Notifier for RunCommand {
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String> {
+        let name = match notice.id {
+            Some(id) => format!("Command notifier for reminder {}", id),
+            None => "Command notifier for a digest".to_string(),
+        };
+        command::spawn(&self.command, &payload(notice), None, name, &self.log)?;
+        return Ok(None);
+    }
+}
+
+/*
+* A plain http:// address such as http://127.0.0.1:8080/reminders, for an endpoint on this computer.
+* An IPv6 address goes between brackets, as in http://[::1]:8080/.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Endpoint {
+    // Without the brackets of an IPv6 address
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    // The host and port as they are written in an address
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            return format!("[{}]:{}", self.host, self.port);
+        }
+        return format!("{}:{}", self.host, self.port);
+    }
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Endpoint, String> {
+        let invalid = || {
+            format!(
+                "Invalid address \"{}\", expected e.g. http://127.0.0.1:8080/reminders",
+                input.trim()
+            )
+        };
+        let rest = input.trim().strip_prefix("http://").ok_or_else(invalid)?;
+        // A fragment only means something to the one reading the address
+        let rest = rest.split('#').next().unwrap();
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, port) = bracketed.split_once(']').ok_or_else(invalid)?;
+                if host.parse::<Ipv6Addr>().is_err() {
+                    return Err(format!(
+                        "Invalid address \"{}\", expected an IPv6 address between the brackets",
+                        input.trim()
+                    ));
+                }
+                match port {
+                    "" => (host, 80),
+                    port => {
+                        let port = port.strip_prefix(':').ok_or_else(invalid)?;
+                        (host, port.parse().map_err(|_| invalid())?)
+                    }
+                }
+            }
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (authority, 80),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        // Such as ::1:8080, where the port can't be told from the address
+        if host.contains(':') && !authority.starts_with('[') {
+            return Err(format!(
+                "Invalid address \"{}\", an IPv6 address goes between brackets, as in http://[::1]:8080/",
+                input.trim()
+            ));
+        }
+        // Reminders are posted as plain text, so never leave the computer
+        let loopback = host.eq_ignore_ascii_case("localhost")
+            || host
+                .parse::<IpAddr>()
+                .is_ok_and(|address| address.is_loopback());
+        if !loopback {
+            return Err(format!(
+                "Invalid address \"{}\", expected one on this computer, such as localhost, 127.0.0.1 or [::1]",
+                input.trim()
+            ));
+        }
+        return Ok(Endpoint {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        });
+    }
+}
+
+// Posts the notice as JSON, counting any 2xx status as received
+pub struct Http {
+    endpoint: Endpoint,
+}
+
+impl Notifier for Http {
+    fn send(&self, notice: &Notice) -> Result<Option<u32>, String> {
+        let endpoint = &self.endpoint;
+        let failed = |err: std::io::Error| {
+            format!(
+                "Error posting to {}{}: {}",
+                endpoint.authority(),
+                endpoint.path,
+                err
+            )
+        };
+        let address = (endpoint.host.as_str(), endpoint.port)
+            .to_socket_addrs()
+            .map_err(failed)?
+            .next()
+            .ok_or_else(|| format!("No address for {}", endpoint.host))?;
+        let mut stream = TcpStream::connect_timeout(&address, HTTP_TIMEOUT).map_err(failed)?;
+        stream
+            .set_read_timeout(Some(HTTP_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(HTTP_TIMEOUT)))
+            .map_err(failed)?;
+
+        let body = payload(notice).to_json();
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            endpoint.path,
+            endpoint.authority(),
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).map_err(failed)?;
+
+        // Only the status line matters, such as "HTTP/1.1 204 No Content"
+        let mut reply = [0; 64];
+        let read = stream.read(&mut reply).map_err(failed)?;
+        let reply = String::from_utf8_lossy(&reply[..read]);
+        let status = reply.split_whitespace().nth(1).unwrap_or("");
+        if !status.starts_with('2') {
+            return Err(format!(
+                "{}{} answered {}",
+                endpoint.authority(),
+                endpoint.path,
+                reply.lines().next().unwrap_or("nothing")
+            ));
+        }
+        return Ok(None);
+    }
+}
+
+// What became of a notice at each sink it was sent to
+pub type Attempts = Vec<(Sink, Result<Option<u32>, String>)>;
+
+// Every notifier, of which a notice goes to those its reminder or the settings pick
+pub struct Notifiers {
+    desktop: Desktop,
+    file: Append,
+    wall: Wall,
+    command: Option<RunCommand>,
+    http: Option<Http>,
+    default: Vec<Sink>,
+}
+
+impl Notifiers {
+    pub fn new(settings: &Settings, log: &Arc<Mutex<File>>) -> Notifiers {
+        return Notifiers {
+            desktop: Desktop {
+                bus: Bus::default(),
+            },
+            file: Append {
+                path: settings.notify_file.clone(),
+            },
+            wall: Wall,
+            command: settings.notify_command.clone().map(|command| RunCommand {
+                command,
+                log: Arc::clone(log),
+            }),
+            http: settings
+                .notify_url
+                .clone()
+                .map(|endpoint| Http { endpoint }),
+            default: settings.notify.clone(),
+        };
+    }
+
+    // The connection of the desktop notifier, which also carries the actions taken on them
+    pub fn bus(&self) -> &Bus {
+        return &self.desktop.bus;
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        return &mut self.desktop.bus;
+    }
+
+    fn get(&self, sink: Sink) -> Result<&dyn Notifier, String> {
+        return match sink {
+            Sink::Desktop => Ok(&self.desktop),
+            Sink::File => Ok(&self.file),
+            Sink::Wall => Ok(&self.wall),
+            Sink::Command => match &self.command {
+                Some(command) => Ok(command),
+                None => Err("No notify_command is set".to_string()),
+            },
+            Sink::Http => match &self.http {
+                Some(http) => Ok(http),
+                None => Err("No notify_url is set".to_string()),
+            },
+        };
+    }
+
+    // The sinks the reminder names, or those of the settings when it names none
+    pub fn sinks<'a>(&'a self, reminder: &'a Reminder) -> &'a [Sink] {
+        return match reminder.sinks.is_empty() {
+            true => &self.default,
+            false => &reminder.sinks,
+        };
+    }
+
+    // Sends the notice to each of the sinks of its reminder
+    pub fn send(&self, notice: &Notice) -> Attempts {
+        return self
+            .sinks(notice.reminder)
+            .iter()
+            .map(|&sink| {
+                (
+                    sink,
+                    self.get(sink).and_then(|notifier| notifier.send(notice)),
+                )
+            })
+            .collect();
+    }
+}
+
+// What commands and HTTP endpoints are told of the notice
+fn payload(notice: &Notice) -> Payload {
+    let reminder = notice.reminder;
+    return Payload {
+        id: notice.id,
+        summary: notice.summary.clone(),
+        body: notice.body.clone(),
+        scheduled: notice.scheduled,
+        occurrence: notice.occurrence,
+        urgency: reminder.urgency,
+        category: reminder.category.clone(),
+        sent: Utc::now(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notifier::{payload, Endpoint, Notice};
+    use hermes_proto::reminder::{Frequency, Reminder};
+
+    fn endpoint(host: &str, port: u16, path: &str) -> Endpoint {
+        return Endpoint {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        };
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(
+            "http://127.0.0.1:8080/reminders".parse(),
+            Ok(endpoint("127.0.0.1", 8080, "/reminders"))
+        );
+        assert_eq!(
+            " http://localhost ".parse(),
+            Ok(endpoint("localhost", 80, "/"))
+        );
+        assert_eq!("http://[::1]:8080/".parse(), Ok(endpoint("::1", 8080, "/")));
+        assert_eq!(
+            "http://127.1.2.3/hook".parse(),
+            Ok(endpoint("127.1.2.3", 80, "/hook"))
+        );
+        assert_eq!(endpoint("::1", 8080, "/").authority(), "[::1]:8080");
+        assert_eq!(endpoint("localhost", 80, "/").authority(), "localhost:80");
+    }
+
+    #[test]
+    fn malformed_endpoints() {
+        for input in [
+            "https://127.0.0.1/",
+            "127.0.0.1:8080",
+            "http://:8080/",
+            "http://localhost:http/",
+            "http://localhost:70000/",
+            "http://::1:8080/",
+            "http://[::1/",
+            "http://[::1]8080/",
+            "http://[localhost]:8080/",
+            "http://[]:8080/",
+            // Elsewhere than this computer
+            "http://example.com/",
+            "http://192.168.1.10:8080/",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:10.0.0.1]/",
+        ] {
+            assert!(input.parse::<Endpoint>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn payloads() {
+        let reminder = Reminder::new(
+            Frequency::ONCE,
+            11,
+            2,
+            2026,
+            9,
+            0,
+            None,
+            "Water the plants".to_string(),
+        );
+        let mut notice = Notice {
+            id: Some(3),
+            summary: "Hermes".to_string(),
+            body: reminder.message.clone(),
+            reminder: &reminder,
+            snooze: true,
+            timeout: 0,
+            replaces: 0,
+            scheduled: reminder.to_instant(),
+            occurrence: Some(4),
+        };
+        let json = payload(&notice).to_json();
+        assert!(json.starts_with("{\"id\":3,\"summary\":\"Hermes\",\"body\":\"Water the plants\","));
+        assert!(json.contains("\"occurrence\":4,"));
+        assert_eq!(payload(&notice).scheduled, reminder.to_instant());
+
+        // A digest stands for several reminders, so has no one time
+        notice.id = None;
+        notice.scheduled = None;
+        notice.occurrence = None;
+        assert_eq!(payload(&notice).scheduled, None);
+        assert!(payload(&notice).to_json().contains("\"occurrence\":null,"));
+        assert!(payload(&notice).to_json().starts_with("{\"id\":null,"));
+    }
+}
@@ -0,0 +1,258 @@
+// Module deciding when notifications are held back, by quiet hours and do not disturb
+
+use crate::config::Settings;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, Utc, Weekday};
+use hermes_proto::protocol::DoNotDisturb;
+use hermes_proto::zone;
+
+// A daily stretch of local time, which ends the next day when it ends before it starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            return self.start <= time && time < self.end;
+        }
+        return time >= self.start || time < self.end;
+    }
+}
+
+impl std::str::FromStr for QuietHours {
+    type Err = String;
+
+    // Reads a range such as "22:00-07:30"
+    fn from_str(input: &str) -> Result<QuietHours, String> {
+        let invalid = || {
+            format!(
+                "Invalid quiet hours \"{}\", expected e.g. 22:00-07:30",
+                input
+            )
+        };
+        let (start, end) = input.split_once('-').ok_or_else(invalid)?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?;
+        return Ok(QuietHours { start, end });
+    }
+}
+
+// How notifications held back are sent once they no longer are
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    // One notification listing all of them
+    Digest,
+    // Each as it would have been sent
+    Each,
+}
+
+impl std::str::FromStr for Delivery {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Delivery, String> {
+        return match input.trim().to_lowercase().as_str() {
+            "digest" => Ok(Delivery::Digest),
+            "each" => Ok(Delivery::Each),
+            _ => Err(format!(
+                "Invalid quiet delivery \"{}\", expected digest or each",
+                input.trim()
+            )),
+        };
+    }
+}
+
+// Reads a list of days such as "sat, sun", where "weekends" and "weekdays" stand for theirs
+pub fn parse_days(input: &str) -> Result<Vec<Weekday>, String> {
+    let mut days = Vec::new();
+    for name in input.split(',').map(|name| name.trim().to_lowercase()) {
+        match name.as_str() {
+            "" | "none" => {}
+            "weekends" => days.extend([Weekday::Sat, Weekday::Sun]),
+            "weekdays" => days.extend([
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ]),
+            name => days.push(
+                name.parse::<Weekday>()
+                    .map_err(|_| format!("Invalid day \"{}\", expected e.g. sat, sun", name))?,
+            ),
+        }
+    }
+    return Ok(days);
+}
+
+/*
+* When notifications stop being held back, None if they aren't at `now`. Quiet hours, quiet days
+* and do not disturb that follow on from each other count as one stretch. Do not disturb without
+* an end holds them until it is turned off, given as DateTime::MAX_UTC.
+*/
+pub fn held_until(
+    settings: &Settings,
+    dnd: DoNotDisturb,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let dnd_until = match dnd {
+        DoNotDisturb::On => return Some(DateTime::<Utc>::MAX_UTC),
+        DoNotDisturb::Until(until) => Some(zone::wall_time(None, until)),
+        DoNotDisturb::Off => None,
+    };
+
+    let start = zone::wall_time(None, now);
+    let mut time = start;
+    // Every day quiet never ends, so only look a couple of weeks ahead
+    for _ in 0..32 {
+        let next = if dnd_until.is_some_and(|until| until > time) {
+            dnd_until.unwrap()
+        } else if settings.quiet_days.contains(&time.weekday()) {
+            next_day(time)
+        } else {
+            match settings.quiet_hours {
+                Some(hours) if hours.contains(time.time()) => next_end(hours, time),
+                _ => break,
+            }
+        };
+        time = next;
+    }
+    if time == start {
+        return None;
+    }
+    return zone::resolve(None, time);
+}
+
+fn next_day(time: NaiveDateTime) -> NaiveDateTime {
+    return (time.date() + Duration::days(1)).and_time(NaiveTime::MIN);
+}
+
+fn next_end(hours: QuietHours, time: NaiveDateTime) -> NaiveDateTime {
+    let end = time.date().and_time(hours.end);
+    if end > time {
+        return end;
+    }
+    return end + Duration::days(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Settings;
+    use crate::quiet::{held_until, parse_days, QuietHours};
+    use chrono::{DateTime, NaiveDate, Utc, Weekday};
+    use hermes_proto::protocol::DoNotDisturb;
+    use hermes_proto::zone;
+
+    // An instant by the computer's clock, in a week of November 2026 starting on Monday the 2nd
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        let time = NaiveDate::from_ymd_opt(2026, 11, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        return zone::resolve(None, time).unwrap();
+    }
+
+    fn settings(hours: Option<&str>, days: &str) -> Settings {
+        return Settings {
+            quiet_hours: hours.map(|hours| hours.parse().unwrap()),
+            quiet_days: parse_days(days).unwrap(),
+            ..Settings::default()
+        };
+    }
+
+    #[test]
+    fn hours_across_midnight() {
+        let settings = settings(Some("22:00-07:30"), "");
+        let held = |now| held_until(&settings, DoNotDisturb::Off, now);
+        assert_eq!(held(at(2, 21, 59)), None);
+        assert_eq!(held(at(2, 22, 0)), Some(at(3, 7, 30)));
+        assert_eq!(held(at(3, 3, 0)), Some(at(3, 7, 30)));
+        assert_eq!(held(at(3, 7, 30)), None);
+    }
+
+    #[test]
+    fn hours_within_a_day() {
+        let settings = settings(Some("12:00-13:00"), "");
+        let held = |now| held_until(&settings, DoNotDisturb::Off, now);
+        assert_eq!(held(at(2, 11, 0)), None);
+        assert_eq!(held(at(2, 12, 30)), Some(at(2, 13, 0)));
+        assert_eq!(held(at(2, 13, 0)), None);
+    }
+
+    #[test]
+    fn weekend_only() {
+        let settings = settings(None, "weekends");
+        let held = |now| held_until(&settings, DoNotDisturb::Off, now);
+        assert_eq!(held(at(6, 23, 59)), None);
+        // Saturday and Sunday count as one stretch, up to Monday
+        assert_eq!(held(at(7, 10, 0)), Some(at(9, 0, 0)));
+        assert_eq!(held(at(8, 23, 0)), Some(at(9, 0, 0)));
+        assert_eq!(held(at(9, 0, 0)), None);
+    }
+
+    #[test]
+    fn hours_run_on_from_quiet_days() {
+        let settings = settings(Some("22:00-07:30"), "sun");
+        let held = |now| held_until(&settings, DoNotDisturb::Off, now);
+        assert_eq!(held(at(7, 23, 0)), Some(at(9, 7, 30)));
+    }
+
+    #[test]
+    fn do_not_disturb() {
+        let settings = settings(None, "");
+        assert_eq!(
+            held_until(&settings, DoNotDisturb::On, at(2, 10, 0)),
+            Some(DateTime::<Utc>::MAX_UTC)
+        );
+        let until = DoNotDisturb::Until(at(2, 11, 0));
+        assert_eq!(
+            held_until(&settings, until, at(2, 10, 0)),
+            Some(at(2, 11, 0))
+        );
+        assert_eq!(held_until(&settings, until, at(2, 11, 0)), None);
+        assert_eq!(held_until(&settings, DoNotDisturb::Off, at(2, 10, 0)), None);
+    }
+
+    #[test]
+    fn do_not_disturb_runs_into_quiet_hours() {
+        let settings = settings(Some("22:00-07:30"), "");
+        let until = DoNotDisturb::Until(at(2, 22, 30));
+        assert_eq!(
+            held_until(&settings, until, at(2, 20, 0)),
+            Some(at(3, 7, 30))
+        );
+        // And ending after them keeps its own end
+        let until = DoNotDisturb::Until(at(3, 9, 0));
+        assert_eq!(
+            held_until(&settings, until, at(2, 23, 0)),
+            Some(at(3, 9, 0))
+        );
+    }
+
+    #[test]
+    fn every_day_quiet_is_held_on() {
+        let settings = settings(None, "weekdays, weekends");
+        assert!(held_until(&settings, DoNotDisturb::Off, at(2, 10, 0)).unwrap() > at(16, 0, 0));
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(
+            parse_days(" Sat, sun ").unwrap(),
+            vec![Weekday::Sat, Weekday::Sun]
+        );
+        assert_eq!(parse_days("weekdays").unwrap().len(), 5);
+        assert!(parse_days("none").unwrap().is_empty());
+        assert!(parse_days("").unwrap().is_empty());
+        assert!(parse_days("sat, funday").is_err());
+        assert!(parse_days("sat; sun").is_err());
+    }
+
+    #[test]
+    fn malformed_hours() {
+        assert!("22:00".parse::<QuietHours>().is_err());
+        assert!("22:00-25:00".parse::<QuietHours>().is_err());
+        assert!("late-early".parse::<QuietHours>().is_err());
+    }
+}
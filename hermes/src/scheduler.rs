@@ -0,0 +1,338 @@
+// Module deciding when the notifier thread wakes, from the times the stored reminders are next due
+
+use crate::bus::Bus;
+use crate::db;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/*
+* Longest wait between looks at the queue. The wait is measured on a clock that stops while the
+* computer is asleep and ignores changes to the time of day, so this bounds how late a reminder
+* can be after either.
+*/
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+// First wait before looking again at a reminder a pass left due, doubling each time up to MAX_SLEEP
+const STUCK_RETRY: Duration = Duration::from_secs(1);
+
+// Wakes the notifier thread when the stored reminders change, from any thread
+#[derive(Clone)]
+pub struct Waker {
+    stream: Arc<UnixStream>,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        // A full buffer already holds a wake up
+        let _ = (&*self.stream).write(&[1]);
+    }
+}
+
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<(DateTime<Utc>, u32)>>,
+    wakeups: UnixStream,
+    // Time to wake without a reminder being due, such as the end of quiet hours
+    alarm: Option<DateTime<Utc>>,
+    // When the last reminders taken were due by, until the next look at the queue
+    handled: Option<DateTime<Utc>>,
+    /*
+     * Reminders still due after a pass handled them, as the time they were left at, how many
+     * passes in a row did so, and when they are looked at again
+     */
+    stuck: HashMap<u32, (DateTime<Utc>, u32, DateTime<Utc>)>,
+}
+
+impl Scheduler {
+    pub fn new(mut log: &File) -> Option<(Scheduler, Waker)> {
+        let pair = UnixStream::pair().and_then(|(wakeups, stream)| {
+            wakeups.set_nonblocking(true)?;
+            stream.set_nonblocking(true)?;
+            Ok((wakeups, stream))
+        });
+        if let Err(err) = pair {
+            let fmt_str = format!("Failed to set up scheduler: {}\n", err);
+            let _ = log.write_all(fmt_str.as_bytes());
+            return None;
+        }
+        let (wakeups, stream) = pair.unwrap();
+
+        let scheduler = Scheduler {
+            queue: BinaryHeap::new(),
+            wakeups,
+            alarm: None,
+            handled: None,
+            stuck: HashMap::new(),
+        };
+        let waker = Waker {
+            stream: Arc::new(stream),
+        };
+        return Some((scheduler, waker));
+    }
+
+    // Replaces the queue with the next due time of each stored reminder
+    pub fn reload(&mut self, statements: &mut db::NotificationStatements, log: &File) -> bool {
+        let times = statements.due_times(log);
+        if times.is_none() {
+            return false;
+        }
+        self.requeue(times.unwrap(), log);
+        return true;
+    }
+
+    /*
+     * Queues the given times. One the last pass should have moved on, but didn't, such as when the
+     * database couldn't be written, is put off rather than waking the thread again at once.
+     */
+    fn requeue(&mut self, times: Vec<(u32, DateTime<Utc>)>, mut log: &File) {
+        let mut stuck = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (id, time) in times {
+            let previous = self.stuck.get(&id).copied();
+            let queued = match (self.handled, previous) {
+                (Some(handled), _) if time <= handled => {
+                    let passes = previous.map_or(1, |(_, passes, _)| passes + 1);
+                    if passes == 1 {
+                        let fmt_str =
+                            format!("Reminder {} was left due, looking again later\n", id);
+                        let _ = log.write_all(fmt_str.as_bytes());
+                    }
+                    let retry_at = handled + backoff(passes);
+                    stuck.insert(id, (time, passes, retry_at));
+                    retry_at
+                }
+                // Unchanged since, so still waiting to be looked at again
+                (_, Some((left_at, passes, retry_at))) if time == left_at => {
+                    stuck.insert(id, (left_at, passes, retry_at));
+                    retry_at
+                }
+                _ => time,
+            };
+            queue.push(Reverse((queued, id)));
+        }
+        self.queue = queue;
+        self.stuck = stuck;
+    }
+
+    // An alarm the last pass should have seen to is put off like a reminder it left due
+    pub fn wake_at(&mut self, alarm: Option<DateTime<Utc>>) {
+        self.alarm = match self.handled {
+            Some(handled) => alarm.map(|alarm| match alarm <= handled {
+                true => handled + backoff(u32::MAX),
+                false => alarm,
+            }),
+            None => alarm,
+        };
+    }
+
+    // Removes the reminders due by `now`, and the alarm once it has passed, returning whether there were any
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> bool {
+        let mut due = self.alarm.is_some_and(|alarm| alarm <= now);
+        if due {
+            self.alarm = None;
+        }
+        while let Some(Reverse((time, _))) = self.queue.peek() {
+            if *time > now {
+                break;
+            }
+            self.queue.pop();
+            due = true;
+        }
+        self.handled = Some(now).filter(|_| due);
+        return due;
+    }
+
+    /*
+     * Answers D-Bus messages until the earliest reminder is due, or a Waker is used. Without a
+     * connection it only waits, until it is time to try the bus again at the latest. Returns true
+     * when woken, meaning the stored reminders changed and the queue should be reloaded.
+     */
+    pub fn wait(&mut self, bus: &Bus, now: DateTime<Utc>) -> bool {
+        let next = self.queue.peek().map(|Reverse((time, _))| *time);
+        let until_due = match next.into_iter().chain(self.alarm).min() {
+            Some(time) => (time - now).to_std().unwrap_or(Duration::ZERO),
+            None => MAX_SLEEP,
+        };
+        let timeout = bus
+            .until_retry()
+            .into_iter()
+            .fold(until_due.min(MAX_SLEEP), Duration::min);
+
+        // Messages read along with a reply are already buffered, and would not wake the poll
+        if let Some(conn) = bus.conn() {
+            while let Ok(true) = conn.process(Duration::ZERO) {}
+        }
+
+        let mut fds = vec![libc::pollfd {
+            fd: self.wakeups.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        if let Some(conn) = bus.conn() {
+            fds.push(libc::pollfd {
+                fd: conn.channel().watch().fd,
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        // Rounded up, so the queue is looked at once the time has come rather than just before
+        let millis = (timeout.as_micros() as i64 + 999) / 1000;
+        let ready =
+            unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, millis as i32) };
+        let bus_ready = ready > 0 && fds.len() > 1 && fds[1].revents != 0;
+        if let Some(conn) = bus.conn().filter(|_| bus_ready) {
+            loop {
+                match conn.process(Duration::ZERO) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    // A closed connection is dropped by Bus::reconnect, after returning
+                    Err(_) if !conn.channel().is_connected() => break,
+                    // Otherwise its descriptor may stay ready, so wait out the time instead
+                    Err(_) => {
+                        thread::sleep(timeout);
+                        break;
+                    }
+                }
+            }
+        }
+        if ready <= 0 || fds[0].revents == 0 {
+            return false;
+        }
+
+        // Any number of wake ups since the last wait count as one, ending with WouldBlock
+        let mut buffer = [0; 64];
+        while let Ok(read) = self.wakeups.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+        }
+        return true;
+    }
+}
+
+// How long to wait before looking again at what `passes` passes in a row left due
+fn backoff(passes: u32) -> chrono::Duration {
+    let wait = STUCK_RETRY
+        .saturating_mul(1 << passes.saturating_sub(1).min(16))
+        .min(MAX_SLEEP);
+    return chrono::Duration::from_std(wait).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::scheduler::{Scheduler, Waker, MAX_SLEEP, STUCK_RETRY};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use std::fs::File;
+
+    fn setup() -> (Scheduler, Waker, File) {
+        let log = File::create("/dev/null").unwrap();
+        let (scheduler, waker) = Scheduler::new(&log).unwrap();
+        return (scheduler, waker, log);
+    }
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        return Utc.with_ymd_and_hms(2026, 11, 2, 9, minute, 0).unwrap();
+    }
+
+    #[test]
+    fn takes_due_in_order() {
+        let (mut scheduler, _, log) = setup();
+        scheduler.requeue(vec![(1, at(30)), (2, at(10)), (3, at(20))], &log);
+        assert!(!scheduler.take_due(at(5)));
+        assert!(scheduler.take_due(at(10)));
+        assert!(!scheduler.take_due(at(15)));
+        // Both passed while asleep, taken together
+        assert!(scheduler.take_due(at(40)));
+        assert!(scheduler.queue.is_empty());
+        assert!(!scheduler.take_due(at(50)));
+    }
+
+    #[test]
+    fn alarm_is_taken_once() {
+        let (mut scheduler, _, _) = setup();
+        scheduler.wake_at(Some(at(10)));
+        assert!(!scheduler.take_due(at(5)));
+        assert!(scheduler.take_due(at(10)));
+        assert!(!scheduler.take_due(at(11)));
+    }
+
+    #[test]
+    fn puts_off_what_a_pass_left_due() {
+        let (mut scheduler, _, log) = setup();
+        scheduler.requeue(vec![(1, at(10)), (2, at(20))], &log);
+        assert!(scheduler.take_due(at(10)));
+        // The pass failed to move reminder 1 on
+        scheduler.requeue(vec![(1, at(10)), (2, at(20))], &log);
+        let retry_at = at(10) + Duration::from_std(STUCK_RETRY).unwrap();
+        assert!(!scheduler.take_due(at(10)));
+        assert!(scheduler.take_due(retry_at));
+
+        // Each pass in a row that leaves it waits longer, up to MAX_SLEEP
+        let mut wait = Duration::from_std(STUCK_RETRY).unwrap();
+        let mut now = retry_at;
+        for _ in 0..10 {
+            scheduler.requeue(vec![(1, at(10)), (2, at(20))], &log);
+            wait = (wait * 2).min(Duration::from_std(MAX_SLEEP).unwrap());
+            assert!(!scheduler.take_due(now + wait - Duration::seconds(1)));
+            now += wait;
+            assert!(scheduler.take_due(now));
+        }
+
+        // A wake up in between keeps it waiting, and moving on forgets it
+        scheduler.requeue(vec![(1, at(10)), (2, at(20))], &log);
+        scheduler.take_due(now);
+        scheduler.requeue(vec![(1, at(10)), (2, at(20))], &log);
+        assert!(!scheduler.take_due(now + Duration::seconds(1)));
+        scheduler.requeue(vec![(1, at(30)), (2, at(20))], &log);
+        assert!(scheduler.stuck.is_empty());
+    }
+
+    #[test]
+    fn past_time_is_taken_without_a_pass() {
+        let (mut scheduler, _, log) = setup();
+        // Such as a reminder just added for a time already gone, woken for rather than taken
+        scheduler.requeue(vec![(1, at(10))], &log);
+        assert!(scheduler.take_due(at(20)));
+    }
+
+    #[test]
+    fn puts_off_an_alarm_a_pass_left() {
+        let (mut scheduler, _, _) = setup();
+        scheduler.wake_at(Some(at(10)));
+        assert!(scheduler.take_due(at(10)));
+        scheduler.wake_at(Some(at(10)));
+        assert!(!scheduler.take_due(at(10)));
+        assert!(scheduler.take_due(at(11)));
+        // A later one is kept as it is
+        scheduler.wake_at(Some(at(11) + Duration::seconds(5)));
+        assert!(scheduler.take_due(at(11) + Duration::seconds(5)));
+    }
+
+    #[test]
+    fn waker_wakes_once() {
+        let (mut scheduler, waker, _) = setup();
+        let bus = Bus::default();
+        assert!(!scheduler.wait(&bus, Utc::now()));
+        waker.wake();
+        waker.wake();
+        assert!(scheduler.wait(&bus, Utc::now()));
+        assert!(!scheduler.wait(&bus, Utc::now()));
+    }
+
+    #[test]
+    fn waker_wakes_from_another_thread() {
+        let (mut scheduler, waker, _) = setup();
+        let bus = Bus::default();
+        let handle = std::thread::spawn(move || waker.wake());
+        handle.join().unwrap();
+        assert!(scheduler.wait(&bus, Utc::now()));
+    }
+}
@@ -1,11 +1,13 @@
 use std::fs::File;
 use std::io::Write;
-use zmq::{Context, Socket};
+use zmq::Socket;
 
 pub fn set_socket(mut log: &File) -> Option<Socket> {
     let context = zmq::Context::new();
     let socket = context.socket(zmq::REP);
     if let Err(err) = socket {
+        let fmt_str = format!("Error creating socket: {}", err);
+        let _ = log.write_all(fmt_str.as_bytes());
         return None;
     }
 